@@ -1,280 +1,6 @@
 _edamame_cli() {
-    local i cur prev opts cmd
-    COMPREPLY=()
-    if [[ "${BASH_VERSINFO[0]}" -ge 4 ]]; then
-        cur="$2"
-    else
-        cur="${COMP_WORDS[COMP_CWORD]}"
-    fi
-    prev="$3"
-    cmd=""
-    opts=""
-
-    for i in "${COMP_WORDS[@]:0:COMP_CWORD}"
-    do
-        case "${cmd},${i}" in
-            ",$1")
-                cmd="edamame_cli"
-                ;;
-            edamame_cli,completion)
-                cmd="edamame_cli__subcmd__completion"
-                ;;
-            edamame_cli,get-method-info)
-                cmd="edamame_cli__subcmd__get__subcmd__method__subcmd__info"
-                ;;
-            edamame_cli,help)
-                cmd="edamame_cli__subcmd__help"
-                ;;
-            edamame_cli,interactive)
-                cmd="edamame_cli__subcmd__interactive"
-                ;;
-            edamame_cli,list-method-infos)
-                cmd="edamame_cli__subcmd__list__subcmd__method__subcmd__infos"
-                ;;
-            edamame_cli,list-methods)
-                cmd="edamame_cli__subcmd__list__subcmd__methods"
-                ;;
-            edamame_cli,rpc)
-                cmd="edamame_cli__subcmd__rpc"
-                ;;
-            edamame_cli__subcmd__help,completion)
-                cmd="edamame_cli__subcmd__help__subcmd__completion"
-                ;;
-            edamame_cli__subcmd__help,get-method-info)
-                cmd="edamame_cli__subcmd__help__subcmd__get__subcmd__method__subcmd__info"
-                ;;
-            edamame_cli__subcmd__help,help)
-                cmd="edamame_cli__subcmd__help__subcmd__help"
-                ;;
-            edamame_cli__subcmd__help,interactive)
-                cmd="edamame_cli__subcmd__help__subcmd__interactive"
-                ;;
-            edamame_cli__subcmd__help,list-method-infos)
-                cmd="edamame_cli__subcmd__help__subcmd__list__subcmd__method__subcmd__infos"
-                ;;
-            edamame_cli__subcmd__help,list-methods)
-                cmd="edamame_cli__subcmd__help__subcmd__list__subcmd__methods"
-                ;;
-            edamame_cli__subcmd__help,rpc)
-                cmd="edamame_cli__subcmd__help__subcmd__rpc"
-                ;;
-            *)
-                ;;
-        esac
-    done
-
-    case "${cmd}" in
-        edamame_cli)
-            opts="-v -h -V --verbose --help --version completion list-methods get-method-info list-method-infos interactive rpc help"
-            if [[ ${cur} == -* || ${COMP_CWORD} -eq 1 ]] ; then
-                COMPREPLY=( $(compgen -W "${opts}" -- "${cur}") )
-                return 0
-            fi
-            case "${prev}" in
-                *)
-                    COMPREPLY=()
-                    ;;
-            esac
-            COMPREPLY=( $(compgen -W "${opts}" -- "${cur}") )
-            return 0
-            ;;
-        edamame_cli__subcmd__completion)
-            opts="-v -h --verbose --help bash elvish fish powershell zsh"
-            if [[ ${cur} == -* || ${COMP_CWORD} -eq 2 ]] ; then
-                COMPREPLY=( $(compgen -W "${opts}" -- "${cur}") )
-                return 0
-            fi
-            case "${prev}" in
-                *)
-                    COMPREPLY=()
-                    ;;
-            esac
-            COMPREPLY=( $(compgen -W "${opts}" -- "${cur}") )
-            return 0
-            ;;
-        edamame_cli__subcmd__get__subcmd__method__subcmd__info)
-            opts="-v -h --verbose --help"
-            if [[ ${cur} == -* || ${COMP_CWORD} -eq 2 ]] ; then
-                COMPREPLY=( $(compgen -W "${opts}" -- "${cur}") )
-                return 0
-            fi
-            case "${prev}" in
-                *)
-                    COMPREPLY=()
-                    ;;
-            esac
-            COMPREPLY=( $(compgen -W "${opts}" -- "${cur}") )
-            return 0
-            ;;
-        edamame_cli__subcmd__help)
-            opts="completion list-methods get-method-info list-method-infos interactive rpc help"
-            if [[ ${cur} == -* || ${COMP_CWORD} -eq 2 ]] ; then
-                COMPREPLY=( $(compgen -W "${opts}" -- "${cur}") )
-                return 0
-            fi
-            case "${prev}" in
-                *)
-                    COMPREPLY=()
-                    ;;
-            esac
-            COMPREPLY=( $(compgen -W "${opts}" -- "${cur}") )
-            return 0
-            ;;
-        edamame_cli__subcmd__help__subcmd__completion)
-            opts=""
-            if [[ ${cur} == -* || ${COMP_CWORD} -eq 3 ]] ; then
-                COMPREPLY=( $(compgen -W "${opts}" -- "${cur}") )
-                return 0
-            fi
-            case "${prev}" in
-                *)
-                    COMPREPLY=()
-                    ;;
-            esac
-            COMPREPLY=( $(compgen -W "${opts}" -- "${cur}") )
-            return 0
-            ;;
-        edamame_cli__subcmd__help__subcmd__get__subcmd__method__subcmd__info)
-            opts=""
-            if [[ ${cur} == -* || ${COMP_CWORD} -eq 3 ]] ; then
-                COMPREPLY=( $(compgen -W "${opts}" -- "${cur}") )
-                return 0
-            fi
-            case "${prev}" in
-                *)
-                    COMPREPLY=()
-                    ;;
-            esac
-            COMPREPLY=( $(compgen -W "${opts}" -- "${cur}") )
-            return 0
-            ;;
-        edamame_cli__subcmd__help__subcmd__help)
-            opts=""
-            if [[ ${cur} == -* || ${COMP_CWORD} -eq 3 ]] ; then
-                COMPREPLY=( $(compgen -W "${opts}" -- "${cur}") )
-                return 0
-            fi
-            case "${prev}" in
-                *)
-                    COMPREPLY=()
-                    ;;
-            esac
-            COMPREPLY=( $(compgen -W "${opts}" -- "${cur}") )
-            return 0
-            ;;
-        edamame_cli__subcmd__help__subcmd__interactive)
-            opts=""
-            if [[ ${cur} == -* || ${COMP_CWORD} -eq 3 ]] ; then
-                COMPREPLY=( $(compgen -W "${opts}" -- "${cur}") )
-                return 0
-            fi
-            case "${prev}" in
-                *)
-                    COMPREPLY=()
-                    ;;
-            esac
-            COMPREPLY=( $(compgen -W "${opts}" -- "${cur}") )
-            return 0
-            ;;
-        edamame_cli__subcmd__help__subcmd__list__subcmd__method__subcmd__infos)
-            opts=""
-            if [[ ${cur} == -* || ${COMP_CWORD} -eq 3 ]] ; then
-                COMPREPLY=( $(compgen -W "${opts}" -- "${cur}") )
-                return 0
-            fi
-            case "${prev}" in
-                *)
-                    COMPREPLY=()
-                    ;;
-            esac
-            COMPREPLY=( $(compgen -W "${opts}" -- "${cur}") )
-            return 0
-            ;;
-        edamame_cli__subcmd__help__subcmd__list__subcmd__methods)
-            opts=""
-            if [[ ${cur} == -* || ${COMP_CWORD} -eq 3 ]] ; then
-                COMPREPLY=( $(compgen -W "${opts}" -- "${cur}") )
-                return 0
-            fi
-            case "${prev}" in
-                *)
-                    COMPREPLY=()
-                    ;;
-            esac
-            COMPREPLY=( $(compgen -W "${opts}" -- "${cur}") )
-            return 0
-            ;;
-        edamame_cli__subcmd__help__subcmd__rpc)
-            opts=""
-            if [[ ${cur} == -* || ${COMP_CWORD} -eq 3 ]] ; then
-                COMPREPLY=( $(compgen -W "${opts}" -- "${cur}") )
-                return 0
-            fi
-            case "${prev}" in
-                *)
-                    COMPREPLY=()
-                    ;;
-            esac
-            COMPREPLY=( $(compgen -W "${opts}" -- "${cur}") )
-            return 0
-            ;;
-        edamame_cli__subcmd__interactive)
-            opts="-v -h --verbose --help"
-            if [[ ${cur} == -* || ${COMP_CWORD} -eq 2 ]] ; then
-                COMPREPLY=( $(compgen -W "${opts}" -- "${cur}") )
-                return 0
-            fi
-            case "${prev}" in
-                *)
-                    COMPREPLY=()
-                    ;;
-            esac
-            COMPREPLY=( $(compgen -W "${opts}" -- "${cur}") )
-            return 0
-            ;;
-        edamame_cli__subcmd__list__subcmd__method__subcmd__infos)
-            opts="-v -h --verbose --help"
-            if [[ ${cur} == -* || ${COMP_CWORD} -eq 2 ]] ; then
-                COMPREPLY=( $(compgen -W "${opts}" -- "${cur}") )
-                return 0
-            fi
-            case "${prev}" in
-                *)
-                    COMPREPLY=()
-                    ;;
-            esac
-            COMPREPLY=( $(compgen -W "${opts}" -- "${cur}") )
-            return 0
-            ;;
-        edamame_cli__subcmd__list__subcmd__methods)
-            opts="-v -h --pretty --verbose --help"
-            if [[ ${cur} == -* || ${COMP_CWORD} -eq 2 ]] ; then
-                COMPREPLY=( $(compgen -W "${opts}" -- "${cur}") )
-                return 0
-            fi
-            case "${prev}" in
-                *)
-                    COMPREPLY=()
-                    ;;
-            esac
-            COMPREPLY=( $(compgen -W "${opts}" -- "${cur}") )
-            return 0
-            ;;
-        edamame_cli__subcmd__rpc)
-            opts="-v -h --pretty --verbose --help"
-            if [[ ${cur} == -* || ${COMP_CWORD} -eq 2 ]] ; then
-                COMPREPLY=( $(compgen -W "${opts}" -- "${cur}") )
-                return 0
-            fi
-            case "${prev}" in
-                *)
-                    COMPREPLY=()
-                    ;;
-            esac
-            COMPREPLY=( $(compgen -W "${opts}" -- "${cur}") )
-            return 0
-            ;;
-    esac
+    local IFS=$'\n'
+    COMPREPLY=( $(edamame_cli __complete --shell bash "--current=${COMP_WORDS[COMP_CWORD]}" -- "${COMP_WORDS[@]:0:COMP_CWORD}" 2>/dev/null) )
 }
 
 if [[ "${BASH_VERSINFO[0]}" -eq 4 && "${BASH_VERSINFO[1]}" -ge 4 || "${BASH_VERSINFO[0]}" -gt 4 ]]; then
@@ -0,0 +1,432 @@
+use crate::{best_suggestion, fetch_method_meta, ERROR_CODE_PARAM};
+use serde_json::{Map, Value};
+
+/// Return type and `(name, type)` arguments of a method, from the daemon.
+type MethodMeta = (String, Vec<(String, String)>);
+
+/// Turn the user-supplied arguments of a call into the single JSON-object
+/// string the daemon expects on the wire. This is the one pipeline behind
+/// `rpc`, the REPL and batch mode, so the same input is accepted, mapped and
+/// diagnosed the same way everywhere. Supports three input shapes:
+///
+/// ```text
+/// (empty) or []     -> zero-argument call (no object sent)
+/// {"k": v, ...}     -> passed through; checked for missing and unknown
+///                      fields when the daemon knows the method
+/// [v1, v2, ...]     -> positional; mapped to the named-arg object via a
+///                      `get_api_info` lookup against the daemon (the daemon
+///                      is the source of truth for arg names)
+/// ```
+///
+/// The wire dispatch goes through `rpc_call_remote`, which forwards
+/// `(method, args)` directly to the daemon's gRPC `execute` endpoint
+/// without consulting the CLI's local RPC registry. This means
+/// `edamame_cli` does NOT have to be rebuilt every time a new RPC
+/// method is added to `edamame_core` -- the daemon dispatches by name,
+/// not by local stub.
+///
+/// Diagnostics are printed on stderr and the error is the exit code to
+/// report; `plan_args` also returns their text. The method help is left to the interactive callers (`rpc`, the
+/// REPL): it goes to stdout, which batch mode, `--dry-run` and scripts keep
+/// for their own output.
+pub fn prepare_args(method: &str, json_args: &str) -> Result<Option<String>, i32> {
+    plan_args(method, json_args)
+        .map(|plan| plan.args)
+        .map_err(|(_, code)| code)
+}
+
+/// What `prepare_args` made of the arguments of a call, shown by
+/// `--dry-run`.
+#[derive(Default)]
+pub struct ArgsPlan {
+    /// The argument object sent on the wire, if any.
+    pub args: Option<String>,
+    /// `(name, type)` given to each positional argument, in order.
+    pub mapping: Vec<(String, String)>,
+    /// Checks that could not be made; the call is still sent.
+    pub warnings: Vec<String>,
+}
+
+/// `prepare_args`, keeping the positional mapping and the warnings. The
+/// error carries the diagnostic, for callers that report it in their output.
+pub fn plan_args(method: &str, json_args: &str) -> Result<ArgsPlan, (String, i32)> {
+    plan_args_with(method, json_args, fetch_method_meta)
+}
+
+/// `prepare_args` with the metadata of the method given by the caller, when
+/// it had to fetch it anyway.
+pub fn prepare_args_with_meta(
+    method: &str,
+    json_args: &str,
+    meta: MethodMeta,
+) -> Result<Option<String>, i32> {
+    plan_args_with(method, json_args, |_| Ok(meta))
+        .map(|plan| plan.args)
+        .map_err(|(_, code)| code)
+}
+
+/// `plan_args`, with `fetch_meta` called when the metadata is needed.
+fn plan_args_with(
+    method: &str,
+    json_args: &str,
+    fetch_meta: impl FnOnce(&str) -> Result<MethodMeta, String>,
+) -> Result<ArgsPlan, (String, i32)> {
+    if json_args.trim().is_empty() {
+        return Ok(ArgsPlan::default());
+    }
+    let result = match serde_json::from_str::<Value>(json_args) {
+        Ok(Value::Array(values)) if values.is_empty() => return Ok(ArgsPlan::default()),
+        Ok(Value::Array(values)) => map_positional(method, values, fetch_meta(method)),
+        Ok(Value::Object(map)) => check_object(method, map, fetch_meta(method)),
+        Ok(_) => Err(vec![
+            ">>>> Error parsing JSON arguments: expected a JSON array or object".to_string(),
+        ]),
+        Err(e) => Err(vec![format_json_error(json_args, &e)]),
+    };
+    result.map_err(|diagnostic| {
+        for line in &diagnostic {
+            eprintln!("{}", line);
+        }
+        (diagnostic_text(&diagnostic), ERROR_CODE_PARAM)
+    })
+}
+
+/// The lines of a diagnostic as one message, without the `>>>>` markers.
+fn diagnostic_text(diagnostic: &[String]) -> String {
+    diagnostic
+        .iter()
+        .flat_map(|line| line.lines())
+        .map(|line| line.strip_prefix(">>>> ").unwrap_or(line))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn map_positional(
+    method: &str,
+    values: Vec<Value>,
+    meta: Result<MethodMeta, String>,
+) -> Result<ArgsPlan, Vec<String>> {
+    let args_meta = match meta {
+        Ok((_ret, args_meta)) => args_meta,
+        Err(e) => {
+            return Err(vec![
+                e,
+                format!(
+                    ">>>> Cannot map positional arguments without API metadata. \
+                     If the daemon supports this method but did not return metadata, \
+                     try the object form: {} {{\"arg1\": value, ...}}",
+                    method
+                ),
+            ]);
+        }
+    };
+    if values.len() != args_meta.len() {
+        return Err(vec![format!(
+            ">>>> Argument count mismatch for {}: provided {}, expected {}",
+            method,
+            values.len(),
+            args_meta.len()
+        )]);
+    }
+    let mut map = Map::with_capacity(args_meta.len());
+    for ((name, _ty), value) in args_meta.iter().zip(values) {
+        map.insert(name.clone(), value);
+    }
+    Ok(ArgsPlan {
+        args: Some(Value::Object(map).to_string()),
+        mapping: args_meta,
+        warnings: Vec::new(),
+    })
+}
+
+/// Object form: passed through as-is. We still consult the daemon for a
+/// friendlier missing/unknown-fields diagnostic when the daemon knows the
+/// method; if it doesn't (or metadata lookup fails for any other reason), we
+/// forward the object and let the daemon return the authoritative error.
+/// `meta` is the outcome of the metadata lookup.
+fn check_object(
+    method: &str,
+    map: Map<String, Value>,
+    meta: Result<MethodMeta, String>,
+) -> Result<ArgsPlan, Vec<String>> {
+    let mut warnings = Vec::new();
+    match meta {
+        Ok((_ret, args_meta)) => {
+            let expected_names: Vec<String> = args_meta.iter().map(|(n, _)| n.clone()).collect();
+            let missing: Vec<&String> = expected_names
+                .iter()
+                .filter(|n| !map.contains_key(*n))
+                .collect();
+            let unknown: Vec<&String> = map
+                .keys()
+                .filter(|n| !expected_names.contains(*n))
+                .collect();
+            let mut diagnostic: Vec<String> = missing
+                .iter()
+                .map(|m| {
+                    format!(
+                        ">>>> Missing field '{}' in provided JSON object for method {}",
+                        m, method
+                    )
+                })
+                .collect();
+            if !unknown.is_empty() {
+                diagnostic.push(format!(
+                    "Unknown fields present: {}",
+                    unknown
+                        .iter()
+                        .map(|u| u.as_str())
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ));
+                for u in &unknown {
+                    if let Some(sugg) = best_suggestion(u, &expected_names) {
+                        diagnostic.push(format!(
+                            "     '{}' is not expected. Did you mean '{}' ?",
+                            u, sugg
+                        ));
+                    }
+                }
+            }
+            if !diagnostic.is_empty() {
+                return Err(diagnostic);
+            }
+        }
+        Err(e) => warnings.push(format!("fields not checked: {}", e)),
+    }
+    Ok(ArgsPlan {
+        args: Some(Value::Object(map).to_string()),
+        mapping: Vec::new(),
+        warnings,
+    })
+}
+
+/// Build the JSON argument object from `--arg name=value` pairs. Each value is
+/// parsed as a JSON literal and taken as a plain string when it is not one.
+/// A name given twice is an error rather than the last value winning.
+pub fn named_args_to_json(named_args: &[String]) -> Result<String, String> {
+    let mut map = Map::with_capacity(named_args.len());
+    for named_arg in named_args {
+        let Some((name, raw_value)) = named_arg.split_once('=') else {
+            return Err(format!(
+                "Invalid --arg '{}': expected name=value",
+                named_arg
+            ));
+        };
+        let value = serde_json::from_str::<Value>(raw_value)
+            .unwrap_or_else(|_| Value::String(raw_value.to_string()));
+        if map.insert(name.to_string(), value).is_some() {
+            return Err(format!(
+                "Invalid --arg '{}': {} is given twice",
+                named_arg, name
+            ));
+        }
+    }
+    Ok(Value::Object(map).to_string())
+}
+
+/// Describe a JSON parse error with its position in a (possibly
+/// multi-line) argument blob, quoting the offending line with a caret.
+pub fn format_json_error(blob: &str, error: &serde_json::Error) -> String {
+    let line_number = error.line();
+    let column = error.column();
+    // serde_json appends its own "at line X column Y" to the message
+    let description = error.to_string();
+    let description = description
+        .rsplit_once(" at line ")
+        .map(|(d, _)| d)
+        .unwrap_or(&description);
+    let mut message = format!(
+        ">>>> Error parsing JSON arguments at line {}, column {}: {}",
+        line_number, column, description
+    );
+    if let Some(line) = blob.lines().nth(line_number.saturating_sub(1)) {
+        let gutter = format!("{:>4} | ", line_number);
+        let caret_offset: usize = line
+            .char_indices()
+            .take_while(|(i, _)| *i < column.saturating_sub(1))
+            .map(|(_, c)| if c == '\t' { 4 } else { 1 })
+            .sum();
+        message.push_str(&format!(
+            "\n{}{}\n{}{}^",
+            gutter,
+            line.replace('\t', "    "),
+            " ".repeat(gutter.len()),
+            " ".repeat(caret_offset)
+        ));
+    }
+    message
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn named(args: &[&str]) -> Result<Value, String> {
+        let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+        named_args_to_json(&args).map(|json| serde_json::from_str(&json).unwrap())
+    }
+
+    fn json_error(blob: &str) -> String {
+        format_json_error(blob, &serde_json::from_str::<Value>(blob).unwrap_err())
+    }
+
+    fn meta(names: &[&str]) -> Result<MethodMeta, String> {
+        Ok((
+            "String".to_string(),
+            names
+                .iter()
+                .map(|name| (name.to_string(), "String".to_string()))
+                .collect(),
+        ))
+    }
+
+    fn object(value: Value) -> Map<String, Value> {
+        value.as_object().unwrap().clone()
+    }
+
+    #[test]
+    fn coerces_named_values() {
+        assert_eq!(
+            named(&[
+                "enabled=true",
+                "count=3",
+                "ratio=-0.5",
+                "name=ssh",
+                "quoted=\"42\"",
+                "list=[1,2]",
+                "empty=",
+                "text=a=b",
+                "none=null",
+            ])
+            .unwrap(),
+            json!({
+                "enabled": true,
+                "count": 3,
+                "ratio": -0.5,
+                "name": "ssh",
+                "quoted": "42",
+                "list": [1, 2],
+                "empty": "",
+                "text": "a=b",
+                "none": null,
+            })
+        );
+        assert_eq!(named(&[]).unwrap(), json!({}));
+    }
+
+    #[test]
+    fn rejects_invalid_named_args() {
+        assert_eq!(
+            named(&["enabled"]).unwrap_err(),
+            "Invalid --arg 'enabled': expected name=value"
+        );
+        assert_eq!(
+            named(&["name=a", "name=b"]).unwrap_err(),
+            "Invalid --arg 'name=b': name is given twice"
+        );
+    }
+
+    #[test]
+    fn points_at_json_errors() {
+        let message = json_error("{\"a\": tru}");
+        let lines: Vec<&str> = message.lines().collect();
+        assert_eq!(
+            lines[0],
+            ">>>> Error parsing JSON arguments at line 1, column 10: expected ident"
+        );
+        assert_eq!(lines[1], "   1 | {\"a\": tru}");
+        // Under the last character read
+        assert_eq!(lines[2], format!("{}^", " ".repeat(7 + 9)));
+        // The caret follows the line of the error and expanded tabs
+        let message = json_error("{\n\t\"a\": 1,\n\t\"b\" 2\n}");
+        let lines: Vec<&str> = message.lines().collect();
+        assert!(lines[0].contains("line 3, column 6"), "{}", lines[0]);
+        assert_eq!(lines[1], "   3 |     \"b\" 2");
+        assert_eq!(lines[2], format!("{}^", " ".repeat(7 + 8)));
+    }
+
+    #[test]
+    fn checks_object_fields() {
+        let plan = check_object(
+            "set_pref",
+            object(json!({"name": "a", "value": "b"})),
+            meta(&["name", "value"]),
+        )
+        .unwrap();
+        assert_eq!(plan.args.as_deref(), Some(r#"{"name":"a","value":"b"}"#));
+        assert!(plan.warnings.is_empty());
+        // Missing and unknown fields are rejected
+        assert!(check_object(
+            "set_pref",
+            object(json!({"name": "a"})),
+            meta(&["name", "value"])
+        )
+        .is_err());
+        assert!(check_object(
+            "set_pref",
+            object(json!({"name": "a", "valeu": "b"})),
+            meta(&["name", "value"])
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn passes_objects_through_without_metadata() {
+        let plan = check_object(
+            "set_pref",
+            object(json!({"anything": 1})),
+            Err("no metadata for set_pref".to_string()),
+        )
+        .unwrap();
+        assert_eq!(plan.args.as_deref(), Some(r#"{"anything":1}"#));
+        assert_eq!(
+            plan.warnings,
+            ["fields not checked: no metadata for set_pref"]
+        );
+    }
+
+    #[test]
+    fn treats_empty_input_as_no_arguments() {
+        for input in ["", "  ", "[]"] {
+            let plan = plan_args("get_score", input).ok().unwrap();
+            assert!(plan.args.is_none() && plan.mapping.is_empty());
+        }
+    }
+
+    #[test]
+    fn returns_the_diagnostic_of_rejected_input() {
+        assert_eq!(
+            plan_args("get_score", "3").err(),
+            Some((
+                "Error parsing JSON arguments: expected a JSON array or object".to_string(),
+                ERROR_CODE_PARAM
+            ))
+        );
+        let (error, _) = plan_args("get_score", "{").err().unwrap();
+        assert!(
+            error.starts_with("Error parsing JSON arguments at line 1, column 1:"),
+            "{}",
+            error
+        );
+        assert!(error.ends_with("   1 | {\n       ^"), "{}", error);
+        let error = plan_args_with("set_pref", "[1]", |_| meta(&["name", "value"]))
+            .err()
+            .unwrap();
+        assert_eq!(
+            error.0,
+            "Argument count mismatch for set_pref: provided 1, expected 2"
+        );
+        let error = plan_args_with("set_pref", r#"{"valeu": 1}"#, |_| meta(&["name", "value"]))
+            .err()
+            .unwrap();
+        assert_eq!(
+            error.0,
+            "Missing field 'name' in provided JSON object for method set_pref\n\
+             Missing field 'value' in provided JSON object for method set_pref\n\
+             Unknown fields present: valeu\n     \
+             'valeu' is not expected. Did you mean 'value' ?"
+        );
+    }
+}
@@ -0,0 +1,629 @@
+use crate::catalog::now_secs;
+use crate::config::{user_config_file, user_data_dir};
+use crate::dispatch::CallError;
+use crate::profile;
+use crate::redact::redact_value;
+use crate::{
+    write_stdout, CORE_VERSION, ERROR_CODE_MISMATCH, ERROR_CODE_NOT_CONFIRMED, ERROR_CODE_PARAM,
+    ERROR_CODE_POLICY,
+};
+use hmac::{Hmac, Mac};
+use serde_json::{json, Map, Value};
+use sha2::{Digest, Sha256};
+use std::fs::{self, File, OpenOptions};
+use std::io::{ErrorKind, Read, Seek, SeekFrom, Write};
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Name of the audit log, in the user data directory.
+pub const AUDIT_FILE: &str = "audit.log";
+
+/// Secret key of the argument MACs, in the user configuration directory.
+/// It is created on first use, readable by the user only.
+pub const AUDIT_KEY_FILE: &str = "audit.key";
+
+/// `prev` of the first record.
+const GENESIS: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+/// Serializes the appends of the threads of one process; the file lock
+/// covers other processes.
+static APPEND: Mutex<()> = Mutex::new(());
+
+/// `~/.local/share/edamame_cli/audit.log` on Linux.
+pub fn audit_path() -> Option<PathBuf> {
+    user_data_dir().map(|dir| dir.join(AUDIT_FILE))
+}
+
+fn sha256_hex(data: &[u8]) -> String {
+    to_hex(&Sha256::digest(data))
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// The key of this installation, created if there is none yet.
+fn audit_key() -> Result<Vec<u8>, String> {
+    let path = user_config_file(AUDIT_KEY_FILE).ok_or("no user configuration directory")?;
+    match fs::read(&path) {
+        Ok(key) if !key.is_empty() => return Ok(key),
+        Ok(_) => return Err(format!("{} is empty", path.display())),
+        Err(e) if e.kind() == ErrorKind::NotFound => {}
+        Err(e) => return Err(format!("Error reading {}: {}", path.display(), e)),
+    }
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    }
+    let mut key = [0u8; 32];
+    getrandom::fill(&mut key).map_err(|e| e.to_string())?;
+    let key = to_hex(&key).into_bytes();
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    match options.open(&path) {
+        Ok(mut file) => {
+            file.write_all(&key).map_err(|e| e.to_string())?;
+            Ok(key)
+        }
+        // Created by another process in the meantime
+        Err(e) if e.kind() == ErrorKind::AlreadyExists => {
+            fs::read(&path).map_err(|e| format!("Error reading {}: {}", path.display(), e))
+        }
+        Err(e) => Err(format!("Error creating {}: {}", path.display(), e)),
+    }
+}
+
+/// HMAC-SHA256 of the arguments as sent, keyed with the secret of this
+/// installation. Identical arguments give identical MACs, so records can be
+/// matched, but without the key a low-entropy value such as a PIN cannot be
+/// recovered by trying every candidate.
+fn args_mac(args: &str) -> Result<String, String> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(&audit_key()?).map_err(|e| e.to_string())?;
+    mac.update(args.as_bytes());
+    Ok(to_hex(&mac.finalize().into_bytes()))
+}
+
+/// Hash of a record: the SHA-256 of its JSON without the `hash` field. It
+/// covers `prev`, which chains each record to the one before it.
+fn record_hash(record: &Map<String, Value>) -> String {
+    let mut record = record.clone();
+    record.remove("hash");
+    sha256_hex(Value::Object(record).to_string().as_bytes())
+}
+
+fn os_user() -> String {
+    ["USER", "LOGNAME", "USERNAME"]
+        .iter()
+        .find_map(|name| std::env::var(name).ok())
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+fn status(outcome: &Result<String, CallError>) -> &'static str {
+    match outcome {
+        Ok(_) => "ok",
+        Err(e) if e.code == ERROR_CODE_POLICY => "blocked",
+        Err(e) if e.code == ERROR_CODE_NOT_CONFIRMED => "declined",
+        Err(_) => "error",
+    }
+}
+
+/// Bytes read at a time when looking for the last line.
+const TAIL_CHUNK: u64 = 4096;
+
+/// The last non-blank line of the log, if any. The file is read backwards
+/// from its end, so that appending does not get slower as the log grows.
+fn last_line(file: &mut File) -> std::io::Result<Option<String>> {
+    let mut end = file.seek(SeekFrom::End(0))?;
+    let mut tail = Vec::new();
+    while end > 0 {
+        let start = end.saturating_sub(TAIL_CHUNK);
+        let mut chunk = vec![0; (end - start) as usize];
+        file.seek(SeekFrom::Start(start))?;
+        file.read_exact(&mut chunk)?;
+        chunk.append(&mut tail);
+        tail = chunk;
+        end = start;
+        if let Some(last) = tail.iter().rposition(|b| !b.is_ascii_whitespace()) {
+            if let Some(newline) = tail[..last].iter().rposition(|&b| b == b'\n') {
+                tail.drain(..=newline);
+                break;
+            }
+        }
+    }
+    let tail = String::from_utf8_lossy(&tail);
+    Ok(tail
+        .lines()
+        .rev()
+        .find(|line| !line.trim().is_empty())
+        .map(str::to_string))
+}
+
+/// Append a record to the log:
+///
+/// ```json
+/// {"args":{"pin":"<redacted>"},"args_hmac":"…","cli_version":"1.8.1","code":0,
+///  "core_version":"…","duration_ms":12,"hash":"…","method":"set_pin","prev":"…",
+///  "profile":"default","seq":42,"status":"ok","target":"127.0.0.1:50051",
+///  "time":1735689600,"user":"alice"}
+/// ```
+///
+/// `args` is redacted, while `args_hmac` is the MAC of the arguments as
+/// sent (see `args_mac`). `status` is `ok`, `error`, `blocked` (by the
+/// method policy) or `declined` (not confirmed). The log is append-only and
+/// hash-chained, see
+/// `handle_audit_verify`.
+fn append(
+    method: &str,
+    args: Option<&str>,
+    outcome: &Result<String, CallError>,
+    duration: Duration,
+) -> Result<(), String> {
+    let path = audit_path().ok_or("no user data directory")?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    }
+    let _guard = APPEND.lock().unwrap_or_else(|e| e.into_inner());
+    let mut file = OpenOptions::new()
+        .read(true)
+        .append(true)
+        .create(true)
+        .open(&path)
+        .map_err(|e| e.to_string())?;
+    file.lock().map_err(|e| e.to_string())?;
+
+    let (seq, prev) = match last_line(&mut file).map_err(|e| e.to_string())? {
+        None => (1, GENESIS.to_string()),
+        Some(line) => {
+            let last: Value = serde_json::from_str(&line)
+                .map_err(|e| format!("cannot chain to the last record: {}", e))?;
+            (
+                last["seq"].as_u64().unwrap_or(0) + 1,
+                last["hash"].as_str().unwrap_or(GENESIS).to_string(),
+            )
+        }
+    };
+
+    let active = profile::active();
+    let args_value = args
+        .and_then(|a| serde_json::from_str::<Value>(a).ok())
+        .map(redact_value)
+        .unwrap_or(Value::Null);
+    let mut record = Map::new();
+    record.insert("seq".to_string(), json!(seq));
+    record.insert("time".to_string(), json!(now_secs()));
+    record.insert("user".to_string(), json!(os_user()));
+    record.insert("profile".to_string(), json!(active.name));
+    record.insert("target".to_string(), json!(active.target));
+    record.insert("method".to_string(), json!(method));
+    record.insert(
+        "args_hmac".to_string(),
+        json!(args_mac(args.unwrap_or_default())?),
+    );
+    record.insert("args".to_string(), args_value);
+    record.insert("status".to_string(), json!(status(outcome)));
+    record.insert(
+        "code".to_string(),
+        json!(outcome.as_ref().map_or_else(|e| e.code, |_| 0)),
+    );
+    record.insert(
+        "duration_ms".to_string(),
+        json!(duration.as_millis() as u64),
+    );
+    record.insert("cli_version".to_string(), json!(env!("CARGO_PKG_VERSION")));
+    record.insert("core_version".to_string(), json!(CORE_VERSION));
+    record.insert("prev".to_string(), json!(prev));
+    let hash = record_hash(&record);
+    record.insert("hash".to_string(), json!(hash));
+
+    writeln!(file, "{}", Value::Object(record)).map_err(|e| e.to_string())
+}
+
+/// Log a call made through `dispatch::call`, whether it was sent or not. A
+/// log that cannot be written is reported but does not fail the call.
+pub fn record(
+    method: &str,
+    args: Option<&str>,
+    outcome: &Result<String, CallError>,
+    duration: Duration,
+) {
+    if let Err(e) = append(method, args, outcome, duration) {
+        eprintln!(">>>> Error writing the audit log: {}", e);
+    }
+}
+
+/// The records of the log, with their line numbers. `None` when there is no
+/// log yet.
+fn read_records() -> Result<Option<Vec<(usize, String)>>, String> {
+    let path = audit_path().ok_or("no user data directory")?;
+    let contents = match fs::read_to_string(&path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(format!("Error reading {}: {}", path.display(), e)),
+    };
+    Ok(Some(
+        contents
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(i, line)| (i + 1, line.to_string()))
+            .collect(),
+    ))
+}
+
+/// Check the chain of the log: every record must hash to its `hash`, point
+/// to the hash of the record before it and follow its sequence number.
+/// Edited records break the first check, removed or reordered ones the
+/// others. Returns the hash of the last record, or the line number of the
+/// first bad record and what is wrong with it.
+fn verify_chain(records: &[(usize, String)]) -> Result<String, (usize, String)> {
+    let mut prev = GENESIS.to_string();
+    let mut expected_seq = 1;
+    for (line_number, line) in records {
+        let problem = match serde_json::from_str::<Value>(line) {
+            Ok(Value::Object(record)) => {
+                if record.get("hash").and_then(Value::as_str) != Some(&record_hash(&record)) {
+                    Some("the record was modified".to_string())
+                } else if record.get("prev").and_then(Value::as_str) != Some(&prev) {
+                    Some(
+                        "the chain is broken: a record before it was removed or modified"
+                            .to_string(),
+                    )
+                } else if record.get("seq").and_then(Value::as_u64) != Some(expected_seq) {
+                    Some(format!(
+                        "expected record {}, found {}",
+                        expected_seq, record["seq"]
+                    ))
+                } else {
+                    prev = record["hash"].as_str().unwrap_or_default().to_string();
+                    expected_seq += 1;
+                    None
+                }
+            }
+            _ => Some("not a JSON record".to_string()),
+        };
+        if let Some(problem) = problem {
+            return Err((*line_number, problem));
+        }
+    }
+    Ok(prev)
+}
+
+/// Check the chain of the log (see `verify_chain`). Truncating the end of
+/// the log cannot be detected from the log alone, so the last sequence
+/// number and hash are printed to be compared with a previous run.
+pub fn handle_audit_verify() -> i32 {
+    let records = match read_records() {
+        Ok(Some(records)) => records,
+        Ok(None) => {
+            let _ = write_stdout("No audit log yet");
+            return 0;
+        }
+        Err(e) => {
+            eprintln!(">>>> {}", e);
+            return ERROR_CODE_PARAM;
+        }
+    };
+
+    match verify_chain(&records) {
+        Ok(last_hash) => {
+            let _ = write_stdout(&format!(
+                "OK: {} records, last hash {}",
+                records.len(),
+                last_hash
+            ));
+            0
+        }
+        Err((line_number, problem)) => {
+            eprintln!(
+                ">>>> Audit log check failed at line {}: {}",
+                line_number, problem
+            );
+            ERROR_CODE_MISMATCH
+        }
+    }
+}
+
+/// Days since 1970-01-01 of a date of the proleptic Gregorian calendar.
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+/// `(year, month, day)` of a number of days since 1970-01-01.
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days - era * 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+/// `2025-01-01T12:00:00Z`
+fn format_utc(secs: u64) -> String {
+    let secs = secs as i64;
+    let (year, month, day) = civil_from_days(secs.div_euclid(86400));
+    let time = secs.rem_euclid(86400);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        time / 3600,
+        time % 3600 / 60,
+        time % 60
+    )
+}
+
+/// Parse `YYYY-MM-DD` or `YYYY-MM-DDTHH:MM[:SS][Z]`, in UTC.
+fn parse_utc(text: &str) -> Option<u64> {
+    let text = text.trim_end_matches('Z');
+    let (date, time) = text.split_once(['T', ' ']).unwrap_or((text, ""));
+    let date: Vec<i64> = date
+        .split('-')
+        .map(|part| part.parse().ok())
+        .collect::<Option<_>>()?;
+    let time: Vec<i64> = if time.is_empty() {
+        Vec::new()
+    } else {
+        time.split(':')
+            .map(|part| part.parse().ok())
+            .collect::<Option<_>>()?
+    };
+    let [year, month, day] = date[..] else {
+        return None;
+    };
+    let (hour, minute, second) = match time[..] {
+        [] => (0, 0, 0),
+        [hour, minute] => (hour, minute, 0),
+        [hour, minute, second] => (hour, minute, second),
+        _ => return None,
+    };
+    if !(1..=12).contains(&month)
+        || !(1..=31).contains(&day)
+        || !(0..24).contains(&hour)
+        || !(0..60).contains(&minute)
+        || !(0..60).contains(&second)
+    {
+        return None;
+    }
+    let secs = days_from_civil(year, month, day) * 86400 + hour * 3600 + minute * 60 + second;
+    u64::try_from(secs).ok()
+}
+
+/// Value parser of `audit show --since`: a date in UTC, or a duration such
+/// as `2h` or `7d` before now. Returns a Unix time.
+pub fn parse_since(text: &str) -> Result<u64, String> {
+    if text.contains('-') {
+        return parse_utc(text).ok_or_else(|| {
+            format!(
+                "invalid date '{}', expected YYYY-MM-DD or YYYY-MM-DDTHH:MM:SS (UTC)",
+                text
+            )
+        });
+    }
+    let duration = match text.trim().strip_suffix('d') {
+        Some(days) => days
+            .parse::<u64>()
+            .map(|days| Duration::from_secs(days * 86400))
+            .map_err(|_| format!("invalid duration '{}'", text))?,
+        None => crate::poll::parse_duration(text)?,
+    };
+    Ok(now_secs().saturating_sub(duration.as_secs()))
+}
+
+/// Print the records of the log made at or after `since`, one per line, or
+/// as the raw NDJSON records with `json`.
+pub fn handle_audit_show(since: Option<u64>, json: bool) -> i32 {
+    let records = match read_records() {
+        Ok(Some(records)) => records,
+        Ok(None) => {
+            let _ = write_stdout("No audit log yet");
+            return 0;
+        }
+        Err(e) => {
+            eprintln!(">>>> {}", e);
+            return ERROR_CODE_PARAM;
+        }
+    };
+    for (line_number, line) in records {
+        let Ok(record) = serde_json::from_str::<Value>(&line) else {
+            eprintln!(
+                ">>>> Line {} of the audit log is not a JSON record",
+                line_number
+            );
+            continue;
+        };
+        let time = record["time"].as_u64().unwrap_or(0);
+        if since.is_some_and(|since| time < since) {
+            continue;
+        }
+        let output = if json {
+            line
+        } else {
+            let args = match &record["args"] {
+                Value::Null => String::new(),
+                args => format!(" {}", args),
+            };
+            format!(
+                "{:>5}  {}  {}@{}  {}{}  {} ({} ms)",
+                record["seq"].as_u64().unwrap_or(0),
+                format_utc(time),
+                record["user"].as_str().unwrap_or_default(),
+                record["profile"].as_str().unwrap_or_default(),
+                record["method"].as_str().unwrap_or_default(),
+                args,
+                record["status"].as_str().unwrap_or_default(),
+                record["duration_ms"].as_u64().unwrap_or(0)
+            )
+        };
+        if write_stdout(&output).is_err() {
+            break;
+        }
+    }
+    0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A chain of `count` records as log lines, numbered from line 1.
+    fn chain(count: u64) -> Vec<(usize, String)> {
+        let mut prev = GENESIS.to_string();
+        (1..=count)
+            .map(|seq| {
+                let mut record = Map::new();
+                record.insert("seq".to_string(), json!(seq));
+                record.insert("method".to_string(), json!("get_score"));
+                record.insert("prev".to_string(), json!(prev));
+                prev = record_hash(&record);
+                record.insert("hash".to_string(), json!(prev));
+                (seq as usize, Value::Object(record).to_string())
+            })
+            .collect()
+    }
+
+    fn renumber(records: Vec<(usize, String)>) -> Vec<(usize, String)> {
+        records
+            .into_iter()
+            .enumerate()
+            .map(|(i, (_, line))| (i + 1, line))
+            .collect()
+    }
+
+    #[test]
+    fn verifies_an_intact_chain() {
+        let records = chain(3);
+        let last: Value = serde_json::from_str(&records[2].1).unwrap();
+        assert_eq!(
+            verify_chain(&records),
+            Ok(last["hash"].as_str().unwrap().to_string())
+        );
+        assert_eq!(verify_chain(&[]), Ok(GENESIS.to_string()));
+    }
+
+    #[test]
+    fn detects_an_edited_record() {
+        let mut records = chain(3);
+        records[1].1 = records[1].1.replace("get_score", "get_threats");
+        assert_eq!(
+            verify_chain(&records),
+            Err((2, "the record was modified".to_string()))
+        );
+    }
+
+    #[test]
+    fn detects_a_removed_record() {
+        let mut records = chain(3);
+        records.remove(1);
+        let (line, problem) = verify_chain(&renumber(records)).unwrap_err();
+        assert_eq!(line, 2);
+        assert!(problem.starts_with("the chain is broken"));
+
+        // Removing the first record breaks the link to the genesis hash
+        let mut records = chain(3);
+        records.remove(0);
+        assert_eq!(verify_chain(&renumber(records)).unwrap_err().0, 1);
+    }
+
+    #[test]
+    fn detects_reordered_records() {
+        let mut records = chain(3);
+        records.swap(1, 2);
+        let (line, problem) = verify_chain(&renumber(records)).unwrap_err();
+        assert_eq!(line, 2);
+        assert!(problem.starts_with("the chain is broken"));
+    }
+
+    #[test]
+    fn detects_a_renumbered_record() {
+        // A record whose hash was recomputed after changing its seq
+        let mut records = chain(2);
+        let mut record: Map<String, Value> = serde_json::from_str(&records[1].1).unwrap();
+        record.insert("seq".to_string(), json!(5));
+        record.insert("hash".to_string(), json!(record_hash(&record)));
+        records[1].1 = Value::Object(record).to_string();
+        assert_eq!(
+            verify_chain(&records),
+            Err((2, "expected record 2, found 5".to_string()))
+        );
+        records[1].1 = "not json".to_string();
+        assert_eq!(
+            verify_chain(&records),
+            Err((2, "not a JSON record".to_string()))
+        );
+    }
+
+    #[test]
+    fn reads_the_last_line_backwards() {
+        let path = std::env::temp_dir().join(format!("audit-test-{}.log", std::process::id()));
+        let long = "x".repeat(3 * TAIL_CHUNK as usize);
+        for (contents, expected) in [
+            ("", None),
+            ("\n \n", None),
+            ("one", Some("one")),
+            ("one\ntwo\n", Some("two")),
+            ("one\ntwo\n\n  \n", Some("two")),
+        ] {
+            fs::write(&path, contents).unwrap();
+            let mut file = File::open(&path).unwrap();
+            assert_eq!(last_line(&mut file).unwrap().as_deref(), expected);
+        }
+        // Lines longer than a chunk, and a newline right at a chunk boundary
+        fs::write(&path, format!("{}\n{}\n", long, long)).unwrap();
+        let mut file = File::open(&path).unwrap();
+        assert_eq!(last_line(&mut file).unwrap(), Some(long.clone()));
+        let first = "y".repeat(2 * TAIL_CHUNK as usize - 1);
+        fs::write(
+            &path,
+            format!("{}\n{}", first, "z".repeat(TAIL_CHUNK as usize)),
+        )
+        .unwrap();
+        let mut file = File::open(&path).unwrap();
+        assert_eq!(
+            last_line(&mut file).unwrap(),
+            Some("z".repeat(TAIL_CHUNK as usize))
+        );
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn converts_civil_dates() {
+        assert_eq!(days_from_civil(1970, 1, 1), 0);
+        assert_eq!(days_from_civil(2000, 3, 1), 11017);
+        assert_eq!(days_from_civil(1969, 12, 31), -1);
+        assert_eq!(civil_from_days(-1), (1969, 12, 31));
+        assert_eq!(civil_from_days(11016), (2000, 2, 29));
+        for days in (-800_000..800_000).step_by(997) {
+            let (year, month, day) = civil_from_days(days);
+            assert_eq!(days_from_civil(year, month, day), days);
+        }
+    }
+
+    #[test]
+    fn parses_utc_dates() {
+        assert_eq!(parse_utc("1970-01-01"), Some(0));
+        assert_eq!(parse_utc("2025-01-01"), Some(1735689600));
+        assert_eq!(parse_utc("2025-01-01T12:30"), Some(1735734600));
+        assert_eq!(parse_utc("2025-01-01T12:30:15Z"), Some(1735734615));
+        assert_eq!(parse_utc("2025-01-01 12:30:15"), Some(1735734615));
+        assert_eq!(format_utc(1735734615), "2025-01-01T12:30:15Z");
+        assert_eq!(parse_utc("2025-13-01"), None);
+        assert_eq!(parse_utc("2025-01-01T24:00"), None);
+        assert_eq!(parse_utc("2025-01"), None);
+        assert_eq!(parse_utc("2025-01-01T12"), None);
+        assert_eq!(parse_utc("1969-12-31"), None);
+    }
+}
@@ -0,0 +1,460 @@
+use crate::args::{plan_args, ArgsPlan};
+use crate::classify::method_class;
+use crate::dispatch;
+use crate::output::result_value;
+use crate::redact::stdout_value;
+use crate::{write_stdout, ERROR_CODE_PARAM};
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{self, Read};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Instant;
+
+/// Result of a call: the parsed result and its duration in milliseconds, or
+/// the error and the exit code it maps to.
+type CallOutcome = Result<(Value, u128), (String, i32)>;
+
+/// One call of a batch, with its arguments already run through the same
+/// pipeline as `rpc`. Calls whose input is invalid are kept so that they are
+/// reported at their position.
+struct BatchCall {
+    /// Line in the batch file, or position on the `rpc-many` command line.
+    line: usize,
+    method: String,
+    args: Result<ArgsPlan, (String, i32)>,
+}
+
+impl BatchCall {
+    fn invalid(line: usize, method: &str, error: String) -> Self {
+        Self {
+            line,
+            method: method.to_string(),
+            args: Err((error, ERROR_CODE_PARAM)),
+        }
+    }
+
+    fn prepare(line: usize, method: &str, args: &str) -> Self {
+        Self {
+            line,
+            method: method.to_string(),
+            args: plan_args(method, args),
+        }
+    }
+}
+
+/// How a batch runs.
+pub struct BatchOptions {
+    pub continue_on_error: bool,
+    /// Maximum number of calls in flight.
+    pub parallel: usize,
+    /// Run mutating methods in parallel too.
+    pub allow_mutating: bool,
+    /// Report what each call would send instead of running it.
+    pub dry_run: bool,
+}
+
+/// Read the batch file, or stdin for `-`.
+fn read_input(path: &str) -> Result<String, String> {
+    if path == "-" {
+        let mut contents = String::new();
+        io::stdin()
+            .read_to_string(&mut contents)
+            .map_err(|e| format!("Error reading stdin: {}", e))?;
+        Ok(contents)
+    } else {
+        fs::read_to_string(path).map_err(|e| format!("Error reading {}: {}", path, e))
+    }
+}
+
+/// Parse one `{"method": ..., "args": ...}` line. Diagnostics are printed on
+/// stderr.
+fn parse_line(path: &str, line: usize, text: &str) -> BatchCall {
+    let entry = match serde_json::from_str::<Value>(text) {
+        Ok(entry) => entry,
+        Err(e) => {
+            let error = format!("invalid JSON: {}", e);
+            eprintln!(">>>> {}:{}: {}", path, line, error);
+            return BatchCall::invalid(line, "", error);
+        }
+    };
+    let Some(method) = entry.get("method").and_then(Value::as_str) else {
+        let error = "expected an object with a \"method\" string".to_string();
+        eprintln!(">>>> {}:{}: {}", path, line, error);
+        return BatchCall::invalid(line, "", error);
+    };
+    let args = match entry.get("args") {
+        None | Some(Value::Null) => String::new(),
+        Some(args) => args.to_string(),
+    };
+    BatchCall::prepare(line, method, &args)
+}
+
+/// Parse an `rpc-many` call: a method name optionally followed by its JSON
+/// arguments, as typed in the REPL.
+fn parse_word(position: usize, word: &str) -> BatchCall {
+    let word = word.trim();
+    let (method, args) = word.split_once(char::is_whitespace).unwrap_or((word, ""));
+    BatchCall::prepare(position, method, args.trim())
+}
+
+/// One output line per call:
+///
+/// ```json
+/// {"duration_ms":12,"line":1,"method":"get_score","ok":true,"result":{...}}
+/// {"error":"...","line":2,"method":"get_threats","ok":false}
+/// ```
+fn envelope(call: &BatchCall, outcome: &CallOutcome) -> Value {
+    match outcome {
+        Ok((result, duration_ms)) => json!({
+            "line": call.line,
+            "method": call.method,
+            "ok": true,
+            "result": result,
+            "duration_ms": *duration_ms as u64,
+        }),
+        Err((error, _)) => json!({
+            "line": call.line,
+            "method": call.method,
+            "ok": false,
+            "error": error,
+        }),
+    }
+}
+
+fn run_call(call: &BatchCall, name: &str) -> CallOutcome {
+    let plan = call.args.as_ref().map_err(Clone::clone)?;
+    let started = Instant::now();
+    dispatch::call(&call.method, plan.args.as_deref())
+        .map(|result| {
+            (
+                stdout_value(result_value(&result)),
+                started.elapsed().as_millis(),
+            )
+        })
+        .map_err(|e| {
+            eprintln!(
+                ">>>> {}:{}: error calling {}: {}",
+                name, call.line, call.method, e
+            );
+            (e.message, e.code)
+        })
+}
+
+/// Run a batch file (`-` for stdin).
+pub fn handle_batch(path: &str, options: &BatchOptions) -> i32 {
+    let contents = match read_input(path) {
+        Ok(contents) => contents,
+        Err(e) => {
+            eprintln!(">>>> {}", e);
+            return ERROR_CODE_PARAM;
+        }
+    };
+    let name = if path == "-" { "stdin" } else { path };
+    let calls = contents
+        .lines()
+        .enumerate()
+        .map(|(index, text)| (index + 1, text.trim()))
+        .filter(|(_, text)| !text.is_empty())
+        .map(|(line, text)| parse_line(name, line, text))
+        .collect();
+    run_batch(name, calls, options)
+}
+
+/// Run the calls given on the command line of `rpc-many`.
+pub fn handle_rpc_many(words: &[String], options: &BatchOptions) -> i32 {
+    let calls = words
+        .iter()
+        .enumerate()
+        .map(|(index, word)| parse_word(index + 1, word))
+        .collect();
+    run_batch("call", calls, options)
+}
+
+/// Run the calls with up to `options.parallel` of them in flight and print
+/// one result envelope per call, in input order whatever the completion
+/// order. Stops starting new calls at the first failure unless
+/// `continue_on_error`.
+fn run_batch(name: &str, calls: Vec<BatchCall>, options: &BatchOptions) -> i32 {
+    if options.parallel > 1 && !options.allow_mutating {
+        let mutating = mutating_calls(name, &calls, |method| method_class(method).is_mutating());
+        if !mutating.is_empty() {
+            eprintln!(
+                ">>>> Refusing to run methods that may change state in parallel: {}",
+                mutating.join(", ")
+            );
+            eprintln!("     Use --parallel 1, or --allow-mutating if the calls are independent");
+            return ERROR_CODE_PARAM;
+        }
+    }
+
+    if options.dry_run {
+        return dry_run_batch(&calls);
+    }
+
+    let (succeeded, failed, exit_code) = run_pool(
+        &calls,
+        options,
+        |call| run_call(call, name),
+        |call, outcome| {
+            let _ = write_stdout(&envelope(call, outcome).to_string());
+        },
+    );
+
+    let skipped = calls.len() - succeeded - failed;
+    eprintln!(
+        "Summary: {} succeeded, {} failed, {} skipped",
+        succeeded, failed, skipped
+    );
+    exit_code
+}
+
+/// The valid calls that may change state, as `method (name:line)`.
+fn mutating_calls(
+    name: &str,
+    calls: &[BatchCall],
+    is_mutating: impl Fn(&str) -> bool,
+) -> Vec<String> {
+    calls
+        .iter()
+        .filter(|call| call.args.is_ok() && is_mutating(&call.method))
+        .map(|call| format!("{} ({}:{})", call.method, name, call.line))
+        .collect()
+}
+
+/// Run the calls through `run` on up to `options.parallel` threads and hand
+/// each outcome to `report` in input order. Returns the number of calls
+/// that succeeded and failed, and the exit code of the first failure.
+fn run_pool(
+    calls: &[BatchCall],
+    options: &BatchOptions,
+    run: impl Fn(&BatchCall) -> CallOutcome + Sync,
+    mut report: impl FnMut(&BatchCall, &CallOutcome),
+) -> (usize, usize, i32) {
+    let next = AtomicUsize::new(0);
+    let stop = AtomicBool::new(false);
+    let mut succeeded = 0;
+    let mut failed = 0;
+    // Exit code of the first failing call
+    let mut exit_code = 0;
+
+    thread::scope(|scope| {
+        let (sender, receiver) = mpsc::channel();
+        for _ in 0..options.parallel.min(calls.len()) {
+            let sender = sender.clone();
+            let (next, stop, run) = (&next, &stop, &run);
+            scope.spawn(move || {
+                while !stop.load(Ordering::SeqCst) {
+                    let index = next.fetch_add(1, Ordering::SeqCst);
+                    let Some(call) = calls.get(index) else {
+                        break;
+                    };
+                    let outcome = run(call);
+                    if outcome.is_err() && !options.continue_on_error {
+                        stop.store(true, Ordering::SeqCst);
+                    }
+                    if sender.send((index, outcome)).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+        drop(sender);
+
+        // Completed calls wait here until every call before them is printed.
+        // Calls are started in input order, so the ones that ran always form
+        // a prefix of the input.
+        let mut pending = BTreeMap::new();
+        let mut printed = 0;
+        for (index, outcome) in receiver {
+            pending.insert(index, outcome);
+            while let Some(outcome) = pending.remove(&printed) {
+                let call = &calls[printed];
+                match &outcome {
+                    Ok(_) => succeeded += 1,
+                    Err((_, code)) => {
+                        failed += 1;
+                        if exit_code == 0 {
+                            exit_code = *code;
+                            if !options.continue_on_error {
+                                eprintln!(">>>> Stopping at the first error (use --continue-on-error to run the remaining calls)");
+                            }
+                        }
+                    }
+                }
+                report(call, &outcome);
+                printed += 1;
+            }
+        }
+    });
+
+    (succeeded, failed, exit_code)
+}
+
+/// `--dry-run`: one `dispatch::dry_run` report per call, with its line.
+/// Every call is checked, whatever `continue_on_error`; the exit code is the
+/// one of the first call that would be rejected.
+fn dry_run_batch(calls: &[BatchCall]) -> i32 {
+    let mut invalid = 0;
+    let mut exit_code = 0;
+    for call in calls {
+        let plan = call
+            .args
+            .as_ref()
+            .map_err(|(error, code)| (error.as_str(), *code));
+        let (mut report, code) = dispatch::dry_run(&call.method, plan);
+        report["line"] = json!(call.line);
+        if code != 0 {
+            invalid += 1;
+            if exit_code == 0 {
+                exit_code = code;
+            }
+        }
+        let _ = write_stdout(&report.to_string());
+    }
+    eprintln!(
+        "Dry run: {} call(s) would be sent, {} rejected",
+        calls.len() - invalid,
+        invalid
+    );
+    exit_code
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::args::ArgsPlan;
+    use std::time::Duration;
+
+    fn call(line: usize, method: &str) -> BatchCall {
+        BatchCall {
+            line,
+            method: method.to_string(),
+            args: Ok(ArgsPlan::default()),
+        }
+    }
+
+    fn options(parallel: usize, continue_on_error: bool) -> BatchOptions {
+        BatchOptions {
+            continue_on_error,
+            parallel,
+            allow_mutating: false,
+            dry_run: false,
+        }
+    }
+
+    /// Runs the calls, the first ones slowest, and returns the lines in the
+    /// order they were reported.
+    fn run(calls: &[BatchCall], options: &BatchOptions) -> (Vec<usize>, (usize, usize, i32)) {
+        let mut reported = Vec::new();
+        let counts = run_pool(
+            calls,
+            options,
+            |call| {
+                thread::sleep(Duration::from_millis(5 * (calls.len() - call.line) as u64));
+                if call.method.starts_with("fail") {
+                    Err(("failed".to_string(), 2))
+                } else {
+                    Ok((json!(call.line), 0))
+                }
+            },
+            |call, _| reported.push(call.line),
+        );
+        (reported, counts)
+    }
+
+    #[test]
+    fn reports_parallel_calls_in_input_order() {
+        let calls: Vec<BatchCall> = (1..=8).map(|line| call(line, "get_score")).collect();
+        let (reported, counts) = run(&calls, &options(4, false));
+        assert_eq!(reported, (1..=8).collect::<Vec<_>>());
+        assert_eq!(counts, (8, 0, 0));
+    }
+
+    #[test]
+    fn stops_at_the_first_failure() {
+        let calls = vec![
+            call(1, "get_score"),
+            call(2, "fail"),
+            call(3, "get_score"),
+            call(4, "get_score"),
+        ];
+        let (reported, counts) = run(&calls, &options(1, false));
+        assert_eq!(reported, [1, 2]);
+        assert_eq!(counts, (1, 1, 2));
+        // Calls already in flight are still reported, in order
+        let (reported, (_, failed, exit_code)) = run(&calls, &options(4, false));
+        assert_eq!(reported[..2], [1, 2]);
+        assert!(reported.windows(2).all(|pair| pair[0] < pair[1]));
+        assert_eq!((failed, exit_code), (1, 2));
+    }
+
+    #[test]
+    fn runs_every_call_with_continue_on_error() {
+        let calls = vec![call(1, "fail"), call(2, "get_score"), call(3, "fail_too")];
+        let (reported, counts) = run(&calls, &options(2, true));
+        assert_eq!(reported, [1, 2, 3]);
+        assert_eq!(counts, (1, 2, 2));
+    }
+
+    #[test]
+    fn lists_mutating_calls() {
+        let calls = vec![
+            call(1, "get_score"),
+            call(2, "set_pref"),
+            BatchCall::invalid(3, "reset_all", "invalid JSON".to_string()),
+            call(4, "reset_all"),
+        ];
+        let is_mutating = |method: &str| !method.starts_with("get_");
+        assert_eq!(
+            mutating_calls("calls.ndjson", &calls, is_mutating),
+            ["set_pref (calls.ndjson:2)", "reset_all (calls.ndjson:4)"]
+        );
+        assert!(mutating_calls("call", &calls[..1], is_mutating).is_empty());
+    }
+
+    #[test]
+    fn refuses_mutating_calls_in_parallel() {
+        let calls = vec![call(1, "get_score"), call(2, "reset_all")];
+        let refused = run_batch("call", calls, &options(2, false));
+        assert_eq!(refused, ERROR_CODE_PARAM);
+    }
+
+    #[test]
+    fn reports_invalid_lines_with_their_diagnostic() {
+        let call = parse_line(
+            "calls.ndjson",
+            3,
+            "{\"method\": \"get_score\", \"args\": 3}",
+        );
+        assert_eq!(
+            envelope(&call, &Err(call.args.as_ref().err().unwrap().clone())),
+            json!({
+                "line": 3,
+                "method": "get_score",
+                "ok": false,
+                "error": "Error parsing JSON arguments: expected a JSON array or object",
+            })
+        );
+        let call = parse_line("calls.ndjson", 4, "[\"get_score\"]");
+        assert_eq!(call.method, "");
+        assert_eq!(
+            call.args.err().unwrap().0,
+            "expected an object with a \"method\" string"
+        );
+        let call = parse_line("calls.ndjson", 5, "{");
+        assert!(call.args.err().unwrap().0.starts_with("invalid JSON: "));
+    }
+
+    #[test]
+    fn splits_rpc_many_words() {
+        let call = parse_word(2, " get_score ");
+        assert_eq!((call.line, call.method.as_str()), (2, "get_score"));
+        assert!(call.args.unwrap().args.is_none());
+        let call = parse_word(1, "get_threats  []");
+        assert_eq!(call.method, "get_threats");
+        assert!(call.args.is_ok());
+    }
+}
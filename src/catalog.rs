@@ -0,0 +1,169 @@
+use crate::config::user_cache_dir;
+use crate::fetch_method_meta;
+use crate::profile::{self, active_profile, profile_file_stem};
+use edamame_core::api::api_rpc::rpc_get_api_methods;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Age after which the cached method list is refreshed from the daemon.
+const CATALOG_TTL_SECS: u64 = 24 * 60 * 60;
+
+/// Return type and `(name, type)` argument list of a method.
+pub type MethodMeta = (String, Vec<(String, String)>);
+
+/// On-disk cache of the daemon catalog. It lets latency-sensitive callers
+/// (shell completion) answer without a round trip to the daemon.
+#[derive(Default)]
+pub struct Catalog {
+    methods: Vec<String>,
+    infos: BTreeMap<String, MethodMeta>,
+    fetched_at: u64,
+}
+
+pub fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Cache file of the active profile, e.g. `~/.cache/edamame_cli/catalog-default.json`.
+/// Profiles may point at daemons of different versions, so each has its own.
+fn catalog_path() -> Option<PathBuf> {
+    user_cache_dir().map(|dir| {
+        dir.join(format!(
+            "catalog-{}.json",
+            profile_file_stem(&active_profile())
+        ))
+    })
+}
+
+impl Catalog {
+    /// Load the cached catalog, or an empty one if there is none.
+    pub fn load() -> Self {
+        let Some(path) = catalog_path() else {
+            return Self::default();
+        };
+        let Ok(contents) = fs::read_to_string(path) else {
+            return Self::default();
+        };
+        let Ok(value) = serde_json::from_str::<Value>(&contents) else {
+            return Self::default();
+        };
+
+        let mut methods: Vec<String> = value["methods"]
+            .as_array()
+            .map(|m| {
+                m.iter()
+                    .filter_map(|v| v.as_str().map(str::to_string))
+                    .collect()
+            })
+            .unwrap_or_default();
+        methods.sort();
+        let mut infos = BTreeMap::new();
+        if let Some(map) = value["infos"].as_object() {
+            for (method, info) in map {
+                let return_type = info["return_type"].as_str().unwrap_or("").to_string();
+                let args = info["args"]
+                    .as_array()
+                    .map(|args| {
+                        args.iter()
+                            .filter_map(|a| {
+                                Some((a[0].as_str()?.to_string(), a[1].as_str()?.to_string()))
+                            })
+                            .collect()
+                    })
+                    .unwrap_or_default();
+                infos.insert(method.clone(), (return_type, args));
+            }
+        }
+        Self {
+            methods,
+            infos,
+            fetched_at: value["fetched_at"].as_u64().unwrap_or(0),
+        }
+    }
+
+    /// Persist the catalog. Failures are ignored: the cache is an optimization.
+    pub fn save(&self) {
+        let Some(path) = catalog_path() else {
+            return;
+        };
+        if let Some(dir) = path.parent() {
+            let _ = fs::create_dir_all(dir);
+        }
+        let infos: serde_json::Map<String, Value> = self
+            .infos
+            .iter()
+            .map(|(method, (return_type, args))| {
+                (
+                    method.clone(),
+                    json!({ "return_type": return_type, "args": args }),
+                )
+            })
+            .collect();
+        let value = json!({
+            "fetched_at": self.fetched_at,
+            "methods": self.methods,
+            "infos": infos,
+        });
+        let _ = fs::write(path, value.to_string());
+    }
+
+    pub fn is_fresh(&self) -> bool {
+        !self.methods.is_empty() && now_secs().saturating_sub(self.fetched_at) < CATALOG_TTL_SECS
+    }
+
+    /// Method names, sorted.
+    pub fn methods(&self) -> &[String] {
+        &self.methods
+    }
+
+    pub fn meta(&self, method: &str) -> Option<&MethodMeta> {
+        self.infos.get(method)
+    }
+
+    /// Replace the method list. Cached metadata of methods that disappeared
+    /// is dropped; metadata of the others is kept.
+    pub fn set_methods(&mut self, mut methods: Vec<String>) {
+        methods.sort();
+        self.infos.retain(|m, _| methods.binary_search(m).is_ok());
+        self.methods = methods;
+        self.fetched_at = now_secs();
+    }
+
+    pub fn set_meta(&mut self, method: &str, meta: MethodMeta) {
+        self.infos.insert(method.to_string(), meta);
+    }
+
+    /// Fetch the method list from the daemon. The core must be initialized.
+    pub fn refresh(&mut self) -> Result<(), String> {
+        let active = profile::active();
+        match rpc_get_api_methods(
+            &active.ca_pem,
+            &active.client_pem,
+            &active.client_key,
+            &active.target,
+        ) {
+            Ok(methods) => {
+                self.set_methods(methods);
+                Ok(())
+            }
+            Err(e) => Err(format!("Error getting API methods: {:?}", e)),
+        }
+    }
+
+    /// Method metadata from the cache, falling back to the daemon. The core
+    /// must be initialized for the fallback to work.
+    pub fn meta_or_fetch(&mut self, method: &str) -> Result<MethodMeta, String> {
+        if let Some(meta) = self.meta(method) {
+            return Ok(meta.clone());
+        }
+        let meta = fetch_method_meta(method)?;
+        self.set_meta(method, meta.clone());
+        Ok(meta)
+    }
+}
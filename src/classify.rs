@@ -0,0 +1,290 @@
+use crate::config::{load_json_file, user_config_file};
+use lazy_static::lazy_static;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+
+/// Name of the local override file in the per-user configuration directory.
+/// It is a flat JSON object mapping method names to a class label:
+///
+/// ```json
+/// { "compute_score": "long-running", "export_logs": "mutating", "rollback_updates": "destructive" }
+/// ```
+pub const CLASSIFICATION_FILE: &str = "classification.json";
+
+/// What calling a method does to the daemon state.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum MethodClass {
+    /// Only reads state; safe to retry and to run in parallel.
+    ReadOnly,
+    /// Changes daemon state.
+    Mutating,
+    /// Deletes data or resets settings; calls are confirmed first.
+    Destructive,
+    /// Triggers work that can take a while (and usually changes state too).
+    LongRunning,
+}
+
+impl MethodClass {
+    pub fn label(&self) -> &'static str {
+        match self {
+            MethodClass::ReadOnly => "read-only",
+            MethodClass::Mutating => "mutating",
+            MethodClass::Destructive => "destructive",
+            MethodClass::LongRunning => "long-running",
+        }
+    }
+
+    /// Whether a call may change daemon state. Long-running methods count as
+    /// state-changing because they typically refresh what the daemon reports.
+    pub fn is_mutating(&self) -> bool {
+        !matches!(self, MethodClass::ReadOnly)
+    }
+
+    /// Whether a call has to be confirmed before it is sent.
+    pub fn is_destructive(&self) -> bool {
+        matches!(self, MethodClass::Destructive)
+    }
+}
+
+impl fmt::Display for MethodClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.label())
+    }
+}
+
+impl FromStr for MethodClass {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "read-only" | "readonly" | "read_only" => Ok(MethodClass::ReadOnly),
+            "mutating" => Ok(MethodClass::Mutating),
+            "destructive" => Ok(MethodClass::Destructive),
+            "long-running" | "long_running" => Ok(MethodClass::LongRunning),
+            _ => Err(format!(
+                "unknown method class '{}' (expected read-only, mutating, destructive or long-running)",
+                s
+            )),
+        }
+    }
+}
+
+/// Verb prefixes and the class they imply. Methods whose verb is not listed
+/// are treated as mutating so that unknown methods never get the benefit of
+/// the doubt.
+const VERB_CLASSES: &[(&str, MethodClass)] = &[
+    ("get", MethodClass::ReadOnly),
+    ("list", MethodClass::ReadOnly),
+    ("is", MethodClass::ReadOnly),
+    ("has", MethodClass::ReadOnly),
+    ("export", MethodClass::ReadOnly),
+    ("describe", MethodClass::ReadOnly),
+    ("search", MethodClass::ReadOnly),
+    ("set", MethodClass::Mutating),
+    ("clear", MethodClass::Destructive),
+    ("reset", MethodClass::Destructive),
+    ("delete", MethodClass::Destructive),
+    ("remove", MethodClass::Destructive),
+    ("add", MethodClass::Mutating),
+    ("update", MethodClass::Mutating),
+    ("enable", MethodClass::Mutating),
+    ("disable", MethodClass::Mutating),
+    ("start", MethodClass::Mutating),
+    ("stop", MethodClass::Mutating),
+    ("apply", MethodClass::Mutating),
+    ("remediate", MethodClass::Mutating),
+    ("rollback", MethodClass::Mutating),
+    ("dismiss", MethodClass::Mutating),
+    ("undismiss", MethodClass::Mutating),
+    ("import", MethodClass::Mutating),
+    ("compute", MethodClass::LongRunning),
+    ("scan", MethodClass::LongRunning),
+    ("run", MethodClass::LongRunning),
+    ("analyze", MethodClass::LongRunning),
+];
+
+/// Class applied to methods whose verb is not in [`VERB_CLASSES`].
+const DEFAULT_CLASS: MethodClass = MethodClass::Mutating;
+
+lazy_static! {
+    static ref OVERRIDES: HashMap<String, MethodClass> = load_overrides();
+}
+
+fn load_overrides() -> HashMap<String, MethodClass> {
+    let Some(path) = user_config_file(CLASSIFICATION_FILE) else {
+        return HashMap::new();
+    };
+    let Some(value) = load_json_file(&path) else {
+        return HashMap::new();
+    };
+    parse_overrides(&value, &path.display().to_string())
+}
+
+/// The overrides of a classification file. Entries that cannot be used are
+/// reported and skipped.
+fn parse_overrides(value: &Value, source: &str) -> HashMap<String, MethodClass> {
+    let mut overrides = HashMap::new();
+    let Some(map) = value.as_object() else {
+        eprintln!(
+            ">>>> Ignoring {}: expected a JSON object of method -> class",
+            source
+        );
+        return overrides;
+    };
+    for (method, class) in map {
+        match class.as_str().map(MethodClass::from_str) {
+            Some(Ok(class)) => {
+                overrides.insert(method.clone(), class);
+            }
+            Some(Err(e)) => eprintln!(">>>> {}: {}: {}", source, method, e),
+            None => eprintln!(">>>> {}: {}: expected a string class label", source, method),
+        }
+    }
+    overrides
+}
+
+/// The verb of a method name, i.e. everything before the first underscore.
+pub fn verb_of(method: &str) -> &str {
+    method
+        .split_once('_')
+        .map(|(verb, _)| verb)
+        .unwrap_or(method)
+}
+
+/// Where a method's class comes from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClassSource {
+    Verb,
+    Default,
+    Override,
+}
+
+/// Classify a method by its verb prefix, honouring the local override file.
+pub fn classify(method: &str) -> (MethodClass, ClassSource) {
+    classify_with(method, &OVERRIDES)
+}
+
+fn classify_with(
+    method: &str,
+    overrides: &HashMap<String, MethodClass>,
+) -> (MethodClass, ClassSource) {
+    match overrides.get(method) {
+        Some(class) => (*class, ClassSource::Override),
+        None => verb_class(method),
+    }
+}
+
+/// The class implied by the verb prefix alone, ignoring the override file.
+pub fn verb_class(method: &str) -> (MethodClass, ClassSource) {
+    let verb = verb_of(method);
+    match VERB_CLASSES.iter().find(|(v, _)| *v == verb) {
+        Some((_, class)) => (*class, ClassSource::Verb),
+        None => (DEFAULT_CLASS, ClassSource::Default),
+    }
+}
+
+pub fn method_class(method: &str) -> MethodClass {
+    classify(method).0
+}
+
+/// Human readable class description, e.g. `mutating (local override)`.
+pub fn describe_class(method: &str) -> String {
+    match classify(method) {
+        (class, ClassSource::Verb) => class.to_string(),
+        (class, ClassSource::Default) => format!("{} (unrecognized verb)", class),
+        (class, ClassSource::Override) => format!("{} (local override)", class),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn classifies_by_verb() {
+        for (method, class) in [
+            ("get_score", MethodClass::ReadOnly),
+            ("list_threats", MethodClass::ReadOnly),
+            ("is_connected", MethodClass::ReadOnly),
+            ("export_logs", MethodClass::ReadOnly),
+            ("set_demo_mode", MethodClass::Mutating),
+            ("add_pwned_email", MethodClass::Mutating),
+            ("remediate", MethodClass::Mutating),
+            ("clear_vulnerability_history", MethodClass::Destructive),
+            ("reset_settings", MethodClass::Destructive),
+            ("delete_profile", MethodClass::Destructive),
+            ("remove_pwned_email", MethodClass::Destructive),
+            ("compute_score", MethodClass::LongRunning),
+            ("scan_network", MethodClass::LongRunning),
+        ] {
+            assert_eq!(verb_class(method), (class, ClassSource::Verb), "{}", method);
+        }
+    }
+
+    #[test]
+    fn defaults_unknown_verbs_to_mutating() {
+        // Only the whole verb counts: "getter" is not "get"
+        for method in ["frobnicate", "getter_value", "purge_all", ""] {
+            assert_eq!(
+                verb_class(method),
+                (MethodClass::Mutating, ClassSource::Default),
+                "{}",
+                method
+            );
+        }
+        assert_eq!(verb_of("get_score_history"), "get");
+        assert_eq!(verb_of("remediate"), "remediate");
+    }
+
+    #[test]
+    fn applies_overrides() {
+        let overrides = parse_overrides(
+            &json!({
+                "compute_score": "read-only",
+                "get_secret": "destructive",
+                "set_mode": "bogus",
+                "add_item": 3
+            }),
+            "test",
+        );
+        assert_eq!(overrides.len(), 2);
+        assert_eq!(
+            classify_with("compute_score", &overrides),
+            (MethodClass::ReadOnly, ClassSource::Override)
+        );
+        assert_eq!(
+            classify_with("get_secret", &overrides),
+            (MethodClass::Destructive, ClassSource::Override)
+        );
+        // Invalid entries fall back to the verb
+        assert_eq!(
+            classify_with("set_mode", &overrides),
+            (MethodClass::Mutating, ClassSource::Verb)
+        );
+        assert!(parse_overrides(&json!(["get_score"]), "test").is_empty());
+    }
+
+    #[test]
+    fn parses_class_labels() {
+        for class in [
+            MethodClass::ReadOnly,
+            MethodClass::Mutating,
+            MethodClass::Destructive,
+            MethodClass::LongRunning,
+        ] {
+            assert_eq!(class.label().parse::<MethodClass>(), Ok(class));
+        }
+        assert_eq!(
+            "read_only".parse::<MethodClass>(),
+            Ok(MethodClass::ReadOnly)
+        );
+        assert!("safe".parse::<MethodClass>().is_err());
+        assert!(MethodClass::LongRunning.is_mutating());
+        assert!(MethodClass::Destructive.is_mutating());
+        assert!(!MethodClass::Mutating.is_destructive());
+        assert!(!MethodClass::ReadOnly.is_mutating());
+    }
+}
@@ -0,0 +1,498 @@
+use crate::catalog::Catalog;
+use crate::profile::set_active_profile;
+use crate::{build_cli, initialize_core, make_example_value, write_stdout};
+use clap::{Arg, Command};
+use clap_complete::Shell;
+use edamame_core::api::api_core::terminate;
+
+/// Name of the hidden subcommand the completion scripts call back into.
+pub const COMPLETE_SUBCOMMAND: &str = "__complete";
+
+/// Positional argument ids that take an RPC method name.
+const METHOD_ARG_IDS: &[&str] = &["METHOD"];
+/// Positional argument ids that take the JSON argument blob of the method.
+const JSON_ARGS_ARG_IDS: &[&str] = &["JSON_ARGS_ARRAY"];
+/// Option ids that take a `name=value` method argument.
+const NAMED_ARG_IDS: &[&str] = &["arg"];
+
+/// Completion scripts that delegate to `edamame_cli __complete`. The words
+/// before the cursor are passed after `--`, the word under the cursor via
+/// `--current=` (so that an empty word survives shells that drop empty
+/// arguments).
+const BASH_SCRIPT: &str = r#"_edamame_cli() {
+    local IFS=$'\n'
+    COMPREPLY=( $(edamame_cli __complete --shell bash "--current=${COMP_WORDS[COMP_CWORD]}" -- "${COMP_WORDS[@]:0:COMP_CWORD}" 2>/dev/null) )
+}
+
+if [[ "${BASH_VERSINFO[0]}" -eq 4 && "${BASH_VERSINFO[1]}" -ge 4 || "${BASH_VERSINFO[0]}" -gt 4 ]]; then
+    complete -F _edamame_cli -o nosort -o bashdefault -o default edamame_cli
+else
+    complete -F _edamame_cli -o bashdefault -o default edamame_cli
+fi
+"#;
+
+const ZSH_SCRIPT: &str = r#"#compdef edamame_cli
+
+_edamame_cli() {
+    local -a candidates
+    candidates=("${(@f)$(edamame_cli __complete --shell zsh "--current=${words[CURRENT]}" -- "${(@)words[1,CURRENT-1]}" 2>/dev/null)}")
+    candidates=("${(@)candidates:#}")
+    (( ${#candidates} )) && compadd -Q -S '' -- "${candidates[@]}"
+}
+
+if [ "$funcstack[1]" = "_edamame_cli" ]; then
+    _edamame_cli "$@"
+else
+    compdef _edamame_cli edamame_cli
+fi
+"#;
+
+const FISH_SCRIPT: &str = r#"function __edamame_cli_complete
+    set -l tokens (commandline -opc)
+    set -l current (commandline -ct)
+    edamame_cli __complete --shell fish "--current=$current" -- $tokens 2>/dev/null
+end
+
+complete -c edamame_cli -f -a '(__edamame_cli_complete)'
+"#;
+
+const POWERSHELL_SCRIPT: &str = r#"Register-ArgumentCompleter -Native -CommandName 'edamame_cli' -ScriptBlock {
+    param($wordToComplete, $commandAst, $cursorPosition)
+    $words = @($commandAst.CommandElements |
+        Where-Object { $_.Extent.EndOffset -lt $cursorPosition -or ($_.Extent.EndOffset -eq $cursorPosition -and $wordToComplete -eq '') } |
+        ForEach-Object { $_.ToString() })
+    edamame_cli __complete --shell powershell "--current=$wordToComplete" -- @words 2>$null |
+        ForEach-Object {
+            $text, $description = $_ -split "`t", 2
+            if (-not $description) { $description = $text }
+            [System.Management.Automation.CompletionResult]::new($text, $text, 'ParameterValue', $description)
+        }
+}
+"#;
+
+/// The dynamic completion script for a shell, if it has one.
+pub fn completion_script(shell: Shell) -> Option<&'static str> {
+    match shell {
+        Shell::Bash => Some(BASH_SCRIPT),
+        Shell::Zsh => Some(ZSH_SCRIPT),
+        Shell::Fish => Some(FISH_SCRIPT),
+        Shell::PowerShell => Some(POWERSHELL_SCRIPT),
+        _ => None,
+    }
+}
+
+/// Print the completion script for a shell. Elvish has no dynamic script and
+/// falls back to the static clap_complete output.
+pub fn print_completion_script(shell: Shell, cmd: &mut Command) {
+    match completion_script(shell) {
+        Some(script) => {
+            let _ = write_stdout(script.trim_end());
+        }
+        None => crate::print_completions(shell, cmd),
+    }
+}
+
+/// A completion candidate and an optional description (shown by fish and
+/// PowerShell).
+struct Candidate {
+    value: String,
+    help: Option<String>,
+}
+
+impl Candidate {
+    fn new(value: impl Into<String>) -> Self {
+        Self {
+            value: value.into(),
+            help: None,
+        }
+    }
+
+    fn with_help(value: impl Into<String>, help: Option<String>) -> Self {
+        Self {
+            value: value.into(),
+            help,
+        }
+    }
+}
+
+/// Lazily connects to the daemon only when the cache cannot answer.
+struct CatalogSource {
+    catalog: Catalog,
+    core_initialized: bool,
+    dirty: bool,
+}
+
+impl CatalogSource {
+    fn new() -> Self {
+        Self {
+            catalog: Catalog::load(),
+            core_initialized: false,
+            dirty: false,
+        }
+    }
+
+    fn ensure_core(&mut self) {
+        if !self.core_initialized {
+            initialize_core(false);
+            self.core_initialized = true;
+        }
+    }
+
+    fn methods(&mut self) -> Vec<String> {
+        if !self.catalog.is_fresh() {
+            self.ensure_core();
+            // On failure keep whatever stale list we have.
+            if self.catalog.refresh().is_ok() {
+                self.dirty = true;
+            }
+        }
+        self.catalog.methods().to_vec()
+    }
+
+    fn args_meta(&mut self, method: &str) -> Vec<(String, String)> {
+        if self.catalog.meta(method).is_none() {
+            self.ensure_core();
+            self.dirty = true;
+        }
+        self.catalog
+            .meta_or_fetch(method)
+            .map(|(_, args)| args)
+            .unwrap_or_default()
+    }
+
+    fn finish(self) {
+        if self.dirty {
+            self.catalog.save();
+        }
+        if self.core_initialized {
+            terminate(false);
+        }
+    }
+}
+
+/// What the word under the cursor is expected to be.
+enum Slot<'a> {
+    Flag,
+    OptionValue(&'a Arg),
+    Positional(Option<&'a Arg>),
+}
+
+/// Where the cursor is on a command line.
+struct Cursor<'a> {
+    /// The innermost subcommand.
+    cmd: &'a Command,
+    slot: Slot<'a>,
+    /// Positional values of `cmd` before the cursor.
+    positionals: Vec<String>,
+    /// The RPC method given before the cursor, if any.
+    method: Option<String>,
+    /// Values of the `--arg` options before the cursor.
+    named_values: Vec<String>,
+    profile_name: Option<String>,
+}
+
+/// Walk the words before the cursor through the command tree of `root`.
+fn locate<'a>(root: &'a Command, current: &str, words: &[String]) -> Cursor<'a> {
+    let mut cmd = root;
+    let mut positionals: Vec<String> = Vec::new();
+    let mut pending_option: Option<&Arg> = None;
+    let mut named_values: Vec<String> = Vec::new();
+    let mut profile_name: Option<String> = None;
+
+    for word in words.iter().skip(1) {
+        if let Some(arg) = pending_option.take() {
+            if NAMED_ARG_IDS.contains(&arg.get_id().as_str()) {
+                named_values.push(word.clone());
+            } else if arg.get_id() == "profile" {
+                profile_name = Some(word.clone());
+            }
+            continue;
+        }
+        if word == "--" {
+            continue;
+        }
+        if let Some(long) = word.strip_prefix("--") {
+            let (name, inline_value) = match long.split_once('=') {
+                Some((name, value)) => {
+                    if name == "profile" {
+                        profile_name = Some(value.to_string());
+                    }
+                    (name, true)
+                }
+                None => (long, false),
+            };
+            if let Some(arg) = cmd.get_arguments().find(|a| a.get_long() == Some(name)) {
+                if arg.get_action().takes_values() && !inline_value {
+                    pending_option = Some(arg);
+                }
+            }
+            continue;
+        }
+        if word.len() > 1 && word.starts_with('-') {
+            if let Some(last) = word.chars().last() {
+                if let Some(arg) = cmd.get_arguments().find(|a| a.get_short() == Some(last)) {
+                    if arg.get_action().takes_values() {
+                        pending_option = Some(arg);
+                    }
+                }
+            }
+            continue;
+        }
+        if positionals.is_empty() {
+            if let Some(sub) = cmd.find_subcommand(word) {
+                cmd = sub;
+                continue;
+            }
+        }
+        positionals.push(word.clone());
+    }
+
+    let slot = if let Some(arg) = pending_option {
+        Slot::OptionValue(arg)
+    } else if current.starts_with('-') {
+        Slot::Flag
+    } else {
+        Slot::Positional(cmd.get_positionals().nth(positionals.len()))
+    };
+
+    let method = cmd
+        .get_positionals()
+        .zip(positionals.iter())
+        .find(|(arg, _)| METHOD_ARG_IDS.contains(&arg.get_id().as_str()))
+        .map(|(_, value)| value.clone());
+
+    Cursor {
+        cmd,
+        slot,
+        positionals,
+        method,
+        named_values,
+        profile_name,
+    }
+}
+
+/// Entry point of the hidden `__complete` subcommand. `words` are the words
+/// before the cursor (starting with the program name) and `current` is the
+/// partial word under the cursor. Candidates are printed one per line.
+pub fn handle_complete(shell: &str, current: &str, words: &[String]) -> i32 {
+    let mut root = build_cli();
+    root.build();
+
+    let Cursor {
+        cmd,
+        slot,
+        positionals,
+        method,
+        named_values,
+        profile_name,
+    } = locate(&root, current, words);
+
+    // Complete against the catalog of the profile on the command line. An
+    // unknown profile keeps the one from the environment.
+    if let Some(name) = &profile_name {
+        let _ = set_active_profile(name);
+    }
+
+    let mut source = CatalogSource::new();
+    let mut candidates: Vec<Candidate> = Vec::new();
+
+    match slot {
+        Slot::Flag => {
+            for arg in cmd.get_arguments().filter(|a| !a.is_hide_set()) {
+                if let Some(long) = arg.get_long() {
+                    candidates.push(Candidate::with_help(
+                        format!("--{}", long),
+                        arg.get_help().map(|h| h.to_string()),
+                    ));
+                }
+            }
+        }
+        Slot::OptionValue(arg) => {
+            if NAMED_ARG_IDS.contains(&arg.get_id().as_str()) {
+                if let Some(method) = &method {
+                    for (name, arg_type) in source.args_meta(method) {
+                        let already_set = named_values
+                            .iter()
+                            .any(|v| v.split_once('=').map(|(n, _)| n) == Some(name.as_str()));
+                        if !already_set {
+                            candidates
+                                .push(Candidate::with_help(format!("{}=", name), Some(arg_type)));
+                        }
+                    }
+                }
+            } else {
+                candidates.extend(possible_values(arg));
+            }
+        }
+        Slot::Positional(arg) => {
+            if positionals.is_empty() {
+                for sub in cmd.get_subcommands().filter(|s| !s.is_hide_set()) {
+                    candidates.push(Candidate::with_help(
+                        sub.get_name(),
+                        sub.get_about().map(|a| a.to_string()),
+                    ));
+                }
+            }
+            if let Some(arg) = arg {
+                let id = arg.get_id().as_str();
+                if METHOD_ARG_IDS.contains(&id) {
+                    candidates.extend(source.methods().into_iter().map(Candidate::new));
+                } else if JSON_ARGS_ARG_IDS.contains(&id) {
+                    if let Some(method) = &method {
+                        let args_meta = source.args_meta(method);
+                        if !args_meta.is_empty() {
+                            candidates.push(Candidate::with_help(
+                                json_skeleton(&args_meta, shell),
+                                Some("named arguments".to_string()),
+                            ));
+                        }
+                    }
+                } else {
+                    candidates.extend(possible_values(arg));
+                }
+            }
+        }
+    }
+
+    source.finish();
+
+    let with_help = matches!(shell, "fish" | "powershell");
+    for candidate in candidates
+        .into_iter()
+        .filter(|c| c.value.starts_with(current))
+    {
+        let line = match (&candidate.help, with_help) {
+            (Some(help), true) => format!("{}\t{}", candidate.value, first_line(help)),
+            _ => candidate.value,
+        };
+        let _ = write_stdout(&line);
+    }
+    0
+}
+
+fn possible_values(arg: &Arg) -> Vec<Candidate> {
+    arg.get_possible_values()
+        .into_iter()
+        .filter(|v| !v.is_hide_set())
+        .map(|v| Candidate::with_help(v.get_name(), v.get_help().map(|h| h.to_string())))
+        .collect()
+}
+
+fn first_line(s: &str) -> &str {
+    s.lines().next().unwrap_or("")
+}
+
+/// A JSON object with every argument set to an example value. Shells that
+/// insert candidates verbatim get it single-quoted; fish quotes on its own.
+fn json_skeleton(args_meta: &[(String, String)], shell: &str) -> String {
+    let fields: Vec<String> = args_meta
+        .iter()
+        .map(|(name, ty)| format!("\"{}\": {}", name, make_example_value(ty, name)))
+        .collect();
+    let object = format!("{{{}}}", fields.join(", "));
+    if shell == "fish" {
+        object
+    } else {
+        format!("'{}'", object)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cli() -> Command {
+        let mut root = build_cli();
+        root.build();
+        root
+    }
+
+    fn words(line: &str) -> Vec<String> {
+        line.split_whitespace().map(String::from).collect()
+    }
+
+    fn positional_id(cursor: &Cursor) -> Option<String> {
+        match cursor.slot {
+            Slot::Positional(arg) => arg.map(|a| a.get_id().to_string()),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn completes_subcommands_at_the_top_level() {
+        let root = cli();
+        let cursor = locate(&root, "rp", &words("edamame_cli"));
+        assert_eq!(cursor.cmd.get_name(), "edamame_cli");
+        assert!(cursor.positionals.is_empty());
+        assert!(matches!(cursor.slot, Slot::Positional(_)));
+    }
+
+    #[test]
+    fn finds_the_method_position() {
+        let root = cli();
+        for line in ["edamame_cli rpc", "edamame_cli get-method-info"] {
+            let cursor = locate(&root, "get_", &words(line));
+            assert!(
+                METHOD_ARG_IDS.contains(&positional_id(&cursor).unwrap().as_str()),
+                "{}",
+                line
+            );
+            assert_eq!(cursor.method, None, "{}", line);
+        }
+    }
+
+    #[test]
+    fn finds_the_json_arguments_position() {
+        let root = cli();
+        for line in [
+            "edamame_cli rpc get_score",
+            "edamame_cli rpc --pretty get_score",
+        ] {
+            let cursor = locate(&root, "", &words(line));
+            assert_eq!(cursor.cmd.get_name(), "rpc");
+            assert!(
+                JSON_ARGS_ARG_IDS.contains(&positional_id(&cursor).unwrap().as_str()),
+                "{}",
+                line
+            );
+            assert_eq!(cursor.method.as_deref(), Some("get_score"), "{}", line);
+        }
+        let cursor = locate(&root, "", &words("edamame_cli --profile dev rpc get_score"));
+        assert_eq!(cursor.profile_name.as_deref(), Some("dev"));
+    }
+
+    #[test]
+    fn finds_named_argument_values() {
+        let root = cli();
+        let cursor = locate(
+            &root,
+            "",
+            &words("edamame_cli rpc set_pref --arg name=x --arg"),
+        );
+        match cursor.slot {
+            Slot::OptionValue(arg) => assert!(NAMED_ARG_IDS.contains(&arg.get_id().as_str())),
+            _ => panic!("expected an --arg value"),
+        }
+        assert_eq!(cursor.method.as_deref(), Some("set_pref"));
+        assert_eq!(cursor.named_values, ["name=x"]);
+    }
+
+    #[test]
+    fn completes_flags() {
+        let root = cli();
+        let cursor = locate(&root, "--pr", &words("edamame_cli rpc get_score"));
+        assert!(matches!(cursor.slot, Slot::Flag));
+        assert_eq!(cursor.cmd.get_name(), "rpc");
+    }
+
+    #[test]
+    fn quotes_skeletons_except_for_fish() {
+        let meta = vec![
+            ("name".to_string(), "String".to_string()),
+            ("on".to_string(), "bool".to_string()),
+        ];
+        let object = r#"{"name": "example_name", "on": true}"#;
+        assert_eq!(json_skeleton(&meta, "fish"), object);
+        assert_eq!(json_skeleton(&meta, "bash"), format!("'{}'", object));
+    }
+}
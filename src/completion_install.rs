@@ -0,0 +1,319 @@
+use crate::complete::completion_script;
+use crate::{write_stdout, ERROR_CODE_PARAM};
+use clap_complete::Shell;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Line added to the PowerShell profile to load the completion script.
+/// The marker comment makes install idempotent and uninstall precise.
+const POWERSHELL_PROFILE_MARKER: &str = "# edamame_cli completion";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Scope {
+    User,
+    System,
+}
+
+impl Scope {
+    fn label(&self) -> &'static str {
+        match self {
+            Scope::User => "user",
+            Scope::System => "system",
+        }
+    }
+}
+
+/// Resolve `--shell`: `auto` looks at `$SHELL` (PowerShell on Windows).
+pub fn resolve_shell(name: &str) -> Result<Shell, String> {
+    match name {
+        "auto" => Shell::from_env().ok_or_else(|| {
+            "Cannot detect the current shell from $SHELL, use --shell <bash|zsh|fish|powershell>"
+                .to_string()
+        }),
+        "bash" => Ok(Shell::Bash),
+        "zsh" => Ok(Shell::Zsh),
+        "fish" => Ok(Shell::Fish),
+        "powershell" => Ok(Shell::PowerShell),
+        _ => Err(format!("Unsupported shell: {}", name)),
+    }
+}
+
+fn home_dir() -> Result<PathBuf, String> {
+    dirs::home_dir().ok_or_else(|| "Cannot determine the home directory".to_string())
+}
+
+/// `$XDG_DATA_HOME`, defaulting to `~/.local/share` on every Unix (including
+/// macOS, where bash-completion and fish also follow the XDG layout).
+fn xdg_data_home() -> Result<PathBuf, String> {
+    match std::env::var_os("XDG_DATA_HOME") {
+        Some(dir) if !dir.is_empty() => Ok(PathBuf::from(dir)),
+        _ => Ok(home_dir()?.join(".local").join("share")),
+    }
+}
+
+fn xdg_config_home() -> Result<PathBuf, String> {
+    match std::env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) if !dir.is_empty() => Ok(PathBuf::from(dir)),
+        _ => Ok(home_dir()?.join(".config")),
+    }
+}
+
+/// Prefix for system-wide completion files: `/usr` on Linux (matching the
+/// Debian package), the Homebrew prefix on macOS.
+fn system_prefix() -> PathBuf {
+    if cfg!(target_os = "macos") {
+        if Path::new("/opt/homebrew").is_dir() {
+            PathBuf::from("/opt/homebrew")
+        } else {
+            PathBuf::from("/usr/local")
+        }
+    } else {
+        PathBuf::from("/usr")
+    }
+}
+
+/// PowerShell profile of the current user (`$PROFILE`).
+fn powershell_profile() -> Result<PathBuf, String> {
+    if cfg!(windows) {
+        dirs::document_dir()
+            .map(|dir| {
+                dir.join("PowerShell")
+                    .join("Microsoft.PowerShell_profile.ps1")
+            })
+            .ok_or_else(|| "Cannot determine the Documents directory".to_string())
+    } else {
+        Ok(xdg_config_home()?
+            .join("powershell")
+            .join("Microsoft.PowerShell_profile.ps1"))
+    }
+}
+
+/// Where the completion script of a shell goes.
+pub fn script_path(shell: Shell, scope: Scope) -> Result<PathBuf, String> {
+    match (shell, scope) {
+        (Shell::Bash, Scope::User) => Ok(xdg_data_home()?
+            .join("bash-completion")
+            .join("completions")
+            .join("edamame_cli")),
+        (Shell::Bash, Scope::System) => Ok(system_prefix()
+            .join("share")
+            .join("bash-completion")
+            .join("completions")
+            .join("edamame_cli")),
+        (Shell::Zsh, Scope::User) => {
+            let base = match std::env::var_os("ZDOTDIR") {
+                Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+                _ => home_dir()?,
+            };
+            Ok(base.join(".zsh").join("completions").join("_edamame_cli"))
+        }
+        (Shell::Zsh, Scope::System) => {
+            let dir = if cfg!(target_os = "macos") {
+                "site-functions"
+            } else {
+                "vendor-completions"
+            };
+            Ok(system_prefix()
+                .join("share")
+                .join("zsh")
+                .join(dir)
+                .join("_edamame_cli"))
+        }
+        (Shell::Fish, Scope::User) => Ok(xdg_config_home()?
+            .join("fish")
+            .join("completions")
+            .join("edamame_cli.fish")),
+        (Shell::Fish, Scope::System) => Ok(system_prefix()
+            .join("share")
+            .join("fish")
+            .join("vendor_completions.d")
+            .join("edamame_cli.fish")),
+        (Shell::PowerShell, Scope::User) => {
+            Ok(powershell_profile()?.with_file_name("edamame_cli_completion.ps1"))
+        }
+        (Shell::PowerShell, Scope::System) => {
+            Err("System-wide installation is not supported for PowerShell, use --user".to_string())
+        }
+        _ => Err(format!("Completion install is not supported for {}", shell)),
+    }
+}
+
+fn powershell_profile_line(script: &Path) -> String {
+    format!(". '{}' {}", script.display(), POWERSHELL_PROFILE_MARKER)
+}
+
+fn write_file(path: &Path, contents: &str) -> Result<(), String> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|e| format!("Error creating {}: {}", dir.display(), e))?;
+    }
+    fs::write(path, contents).map_err(|e| format!("Error writing {}: {}", path.display(), e))
+}
+
+/// State of an installed script compared to what this binary would install.
+#[derive(Debug, PartialEq, Eq)]
+enum InstallState {
+    UpToDate,
+    Outdated,
+    Missing,
+}
+
+fn install_state(path: &Path, script: &str) -> InstallState {
+    match fs::read_to_string(path) {
+        Ok(existing) if existing == script => InstallState::UpToDate,
+        Ok(_) => InstallState::Outdated,
+        Err(_) => InstallState::Missing,
+    }
+}
+
+fn install(shell: Shell, scope: Scope) -> Result<(), String> {
+    let script = completion_script(shell)
+        .ok_or_else(|| format!("Completion install is not supported for {}", shell))?;
+    let path = script_path(shell, scope)?;
+
+    match install_state(&path, script) {
+        InstallState::UpToDate => {
+            let _ = write_stdout(&format!(
+                "{} completion already up to date: {}",
+                shell,
+                path.display()
+            ));
+        }
+        state => {
+            write_file(&path, script)?;
+            let _ = write_stdout(&format!(
+                "{} {} completion: {}",
+                if state == InstallState::Outdated {
+                    "Updated"
+                } else {
+                    "Installed"
+                },
+                shell,
+                path.display()
+            ));
+        }
+    }
+
+    if shell == Shell::PowerShell {
+        let profile = powershell_profile()?;
+        let existing = fs::read_to_string(&profile).unwrap_or_default();
+        if !existing.contains(POWERSHELL_PROFILE_MARKER) {
+            let mut contents = existing;
+            if !contents.is_empty() && !contents.ends_with('\n') {
+                contents.push('\n');
+            }
+            contents.push_str(&powershell_profile_line(&path));
+            contents.push('\n');
+            write_file(&profile, &contents)?;
+            let _ = write_stdout(&format!("Added completion loader to {}", profile.display()));
+        }
+    }
+
+    if shell == Shell::Zsh && scope == Scope::User {
+        if let Some(dir) = path.parent() {
+            let _ = write_stdout(&format!(
+                "Make sure {} is in your fpath before compinit, e.g. in ~/.zshrc:\n  fpath=({} $fpath)",
+                dir.display(),
+                dir.display()
+            ));
+        }
+    }
+    let _ = write_stdout("Restart your shell to enable the completions.");
+    Ok(())
+}
+
+fn uninstall(shell: Shell, scope: Scope) -> Result<(), String> {
+    let path = script_path(shell, scope)?;
+    if path.exists() {
+        fs::remove_file(&path).map_err(|e| format!("Error removing {}: {}", path.display(), e))?;
+        let _ = write_stdout(&format!("Removed {} completion: {}", shell, path.display()));
+    } else {
+        let _ = write_stdout(&format!(
+            "{} completion is not installed at {}",
+            shell,
+            path.display()
+        ));
+    }
+
+    if shell == Shell::PowerShell {
+        let profile = powershell_profile()?;
+        if let Ok(existing) = fs::read_to_string(&profile) {
+            if existing.contains(POWERSHELL_PROFILE_MARKER) {
+                let contents: String = existing
+                    .lines()
+                    .filter(|line| !line.contains(POWERSHELL_PROFILE_MARKER))
+                    .map(|line| format!("{}\n", line))
+                    .collect();
+                write_file(&profile, &contents)?;
+                let _ = write_stdout(&format!(
+                    "Removed completion loader from {}",
+                    profile.display()
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+fn status(shells: &[Shell]) {
+    for shell in shells {
+        let Some(script) = completion_script(*shell) else {
+            continue;
+        };
+        for scope in [Scope::User, Scope::System] {
+            let line = match script_path(*shell, scope) {
+                Ok(path) => {
+                    let state = match install_state(&path, script) {
+                        InstallState::UpToDate => "installed",
+                        InstallState::Outdated => "installed (outdated, run 'completion install')",
+                        InstallState::Missing => "not installed",
+                    };
+                    format!(
+                        "{:<11} {:<7} {}: {}",
+                        shell.to_string(),
+                        scope.label(),
+                        state,
+                        path.display()
+                    )
+                }
+                Err(e) => format!("{:<11} {:<7} {}", shell.to_string(), scope.label(), e),
+            };
+            let _ = write_stdout(&line);
+        }
+    }
+}
+
+pub fn handle_completion_install(shell: &str, scope: Scope, remove: bool) -> i32 {
+    let result = resolve_shell(shell).and_then(|shell| {
+        if remove {
+            uninstall(shell, scope)
+        } else {
+            install(shell, scope)
+        }
+    });
+    match result {
+        Ok(()) => 0,
+        Err(e) => {
+            eprintln!(">>>> {}", e);
+            if scope == Scope::System {
+                eprintln!(">>>> System-wide locations usually require root (sudo)");
+            }
+            ERROR_CODE_PARAM
+        }
+    }
+}
+
+pub fn handle_completion_status(shell: &str) -> i32 {
+    let shells = if shell == "all" {
+        vec![Shell::Bash, Shell::Zsh, Shell::Fish, Shell::PowerShell]
+    } else {
+        match resolve_shell(shell) {
+            Ok(shell) => vec![shell],
+            Err(e) => {
+                eprintln!(">>>> {}", e);
+                return ERROR_CODE_PARAM;
+            }
+        }
+    };
+    status(&shells);
+    0
+}
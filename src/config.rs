@@ -0,0 +1,58 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Per-user configuration directory, e.g. `~/.config/edamame_cli` on Linux.
+pub fn user_config_dir() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("edamame_cli"))
+}
+
+/// Path of a file in the per-user configuration directory.
+pub fn user_config_file(name: &str) -> Option<PathBuf> {
+    user_config_dir().map(|dir| dir.join(name))
+}
+
+/// System-wide configuration directory, managed by administrators:
+/// `/etc/edamame_cli` on Linux, `/Library/Application Support/edamame_cli`
+/// on macOS and `%ProgramData%\edamame_cli` on Windows.
+pub fn system_config_dir() -> PathBuf {
+    if cfg!(windows) {
+        let program_data =
+            std::env::var_os("ProgramData").unwrap_or_else(|| "C:\\ProgramData".into());
+        PathBuf::from(program_data).join("edamame_cli")
+    } else if cfg!(target_os = "macos") {
+        PathBuf::from("/Library/Application Support/edamame_cli")
+    } else {
+        PathBuf::from("/etc/edamame_cli")
+    }
+}
+
+/// Per-user cache directory, e.g. `~/.cache/edamame_cli` on Linux.
+pub fn user_cache_dir() -> Option<PathBuf> {
+    dirs::cache_dir().map(|dir| dir.join("edamame_cli"))
+}
+
+/// Per-user data directory, e.g. `~/.local/share/edamame_cli` on Linux.
+pub fn user_data_dir() -> Option<PathBuf> {
+    dirs::data_local_dir().map(|dir| dir.join("edamame_cli"))
+}
+
+/// Read a JSON configuration file. A missing file is not an error and yields
+/// `None`; an unreadable or malformed file is reported on stderr and ignored,
+/// so a broken local override never prevents the CLI from running.
+pub fn load_json_file(path: &Path) -> Option<serde_json::Value> {
+    let contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return None,
+        Err(e) => {
+            eprintln!(">>>> Error reading {}: {}", path.display(), e);
+            return None;
+        }
+    };
+    match serde_json::from_str(&contents) {
+        Ok(value) => Some(value),
+        Err(e) => {
+            eprintln!(">>>> Error parsing {}: {}", path.display(), e);
+            None
+        }
+    }
+}
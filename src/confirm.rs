@@ -0,0 +1,110 @@
+use crate::classify::method_class;
+use crate::dispatch::CallError;
+use crate::profile;
+use crate::ERROR_CODE_NOT_CONFIRMED;
+use std::io::{self, IsTerminal, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+/// Set by `--yes`: destructive calls are sent without asking.
+static ASSUME_YES: AtomicBool = AtomicBool::new(false);
+
+/// Held while a question is on screen, so that calls running in parallel
+/// (`batch --parallel`) ask one at a time.
+static PROMPT: Mutex<()> = Mutex::new(());
+
+pub fn set_assume_yes(yes: bool) {
+    ASSUME_YES.store(yes, Ordering::SeqCst);
+}
+
+/// Whether there is a terminal to ask on.
+fn can_ask() -> bool {
+    io::stdin().is_terminal() && io::stderr().is_terminal()
+}
+
+/// The refusal of a call that needs a confirmation nobody can give: a
+/// destructive method, without `--yes` and without a terminal.
+pub(crate) fn unattended_refusal(
+    method: &str,
+    destructive: bool,
+    assume_yes: bool,
+    can_ask: bool,
+) -> Option<CallError> {
+    (destructive && !assume_yes && !can_ask).then(|| CallError {
+        code: ERROR_CODE_NOT_CONFIRMED,
+        message: format!(
+            "{} is destructive and was not confirmed: pass --yes to run it without a terminal",
+            method
+        ),
+    })
+}
+
+/// The part of `confirm` that does not ask: whether the call would be
+/// refused for lack of a terminal. Used by `--dry-run`.
+pub fn check_unattended(method: &str) -> Result<(), CallError> {
+    match unattended_refusal(
+        method,
+        method_class(method).is_destructive(),
+        ASSUME_YES.load(Ordering::SeqCst),
+        can_ask(),
+    ) {
+        Some(refusal) => Err(refusal),
+        None => Ok(()),
+    }
+}
+
+/// Ask before a call to a destructive method is sent. Other methods, and
+/// every method with `--yes`, go through. Without a terminal to ask on the
+/// call is refused, so that scripts have to opt in with `--yes`. Methods
+/// are destructive by their verb (`clear_`, `reset_`, `delete_`,
+/// `remove_`) or when `classification.json` says so, which is how more
+/// methods are marked as dangerous.
+pub fn confirm(method: &str, args: Option<&str>) -> Result<(), CallError> {
+    if !method_class(method).is_destructive() || ASSUME_YES.load(Ordering::SeqCst) {
+        return Ok(());
+    }
+    check_unattended(method)?;
+
+    let _prompt = PROMPT.lock().unwrap_or_else(|e| e.into_inner());
+    let active = profile::active();
+    eprintln!(
+        "{} is destructive: it may delete data or reset settings of the daemon.",
+        method
+    );
+    eprintln!("  Profile:   {} ({})", active.name, active.target);
+    eprintln!("  Arguments: {}", args.unwrap_or("none"));
+    eprint!("Proceed? [y/N] ");
+    let _ = io::stderr().flush();
+
+    let mut answer = String::new();
+    let _ = io::stdin().read_line(&mut answer);
+    if matches!(answer.trim().to_lowercase().as_str(), "y" | "yes") {
+        Ok(())
+    } else {
+        Err(CallError {
+            code: ERROR_CODE_NOT_CONFIRMED,
+            message: format!("{} was not confirmed and was not sent", method),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn refuses_unattended_destructive_calls() {
+        let refusal = unattended_refusal("clear_history", true, false, false).unwrap();
+        assert_eq!(refusal.code, ERROR_CODE_NOT_CONFIRMED);
+        assert!(refusal.message.contains("pass --yes"));
+        assert!(!refusal.is_daemon_error());
+    }
+
+    #[test]
+    fn lets_other_calls_through() {
+        // --yes, a terminal to ask on, or a method that is not destructive
+        assert!(unattended_refusal("clear_history", true, true, false).is_none());
+        assert!(unattended_refusal("clear_history", true, false, true).is_none());
+        assert!(unattended_refusal("get_score", false, false, false).is_none());
+    }
+}
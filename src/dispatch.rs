@@ -0,0 +1,237 @@
+use crate::args::ArgsPlan;
+use crate::audit;
+use crate::best_suggestion;
+use crate::catalog::Catalog;
+use crate::classify::method_class;
+use crate::confirm::{self, confirm};
+use crate::policy;
+use crate::profile;
+use crate::redact;
+use crate::ERROR_CODE_SERVER_ERROR;
+use edamame_core::api::api_rpc::rpc_call_remote;
+use serde_json::{json, Value};
+use std::fmt;
+use std::time::Instant;
+
+/// A call that failed on the daemon side, or that was stopped before it was
+/// sent.
+#[derive(Debug, Clone)]
+pub struct CallError {
+    /// Exit code to report for it.
+    pub code: i32,
+    pub message: String,
+}
+
+impl CallError {
+    /// Whether the daemon was called and failed, as opposed to the call
+    /// being stopped on the client side (for example not confirmed).
+    pub fn is_daemon_error(&self) -> bool {
+        self.code == ERROR_CODE_SERVER_ERROR
+    }
+}
+
+impl fmt::Display for CallError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+/// Send a call to the daemon of the active profile. `args` is the argument
+/// object produced by `args::prepare_args`. Every call the CLI makes on the
+/// user's behalf goes through here, whichever command issued it, so that
+/// the method policy applies and calls to destructive methods are confirmed
+/// whatever their origin, and that every call ends up in the audit log,
+/// including the ones that were not sent.
+pub fn call(method: &str, args: Option<&str>) -> Result<String, CallError> {
+    let start = Instant::now();
+    let outcome = send(method, args);
+    audit::record(method, args, &outcome, start.elapsed());
+    outcome
+}
+
+fn send(method: &str, args: Option<&str>) -> Result<String, CallError> {
+    policy::check(method)?;
+    confirm(method, args)?;
+    let active = profile::active();
+    rpc_call_remote(
+        method,
+        args,
+        &active.ca_pem,
+        &active.client_pem,
+        &active.client_key,
+        &active.target,
+    )
+    .map_err(|e| CallError {
+        code: ERROR_CODE_SERVER_ERROR,
+        message: redact::stdout_text(format!("{:?}", e)),
+    })
+}
+
+/// What `call` would be given for `method`, instead of calling it
+/// (`--dry-run`):
+///
+/// ```json
+/// {"method":"get_threat_info","class":"read-only","ok":true,
+///  "args":{"name":"ssh"},"mapping":[{"position":0,"name":"name","type":"String"}],
+///  "warnings":[]}
+/// ```
+///
+/// `plan` is the outcome of `args::plan_args`; when the input was rejected,
+/// `ok` is false with the error and the diagnostics were already printed.
+/// A call that the method policy blocks, or that could not be confirmed
+/// (destructive, without `--yes` or a terminal), is not `ok` either. The
+/// catalog is checked for the method name, which `call` leaves to the
+/// daemon. Returns the report and the exit code the call would fail with,
+/// 0 if it passes.
+pub fn dry_run(method: &str, plan: Result<&ArgsPlan, (&str, i32)>) -> (Value, i32) {
+    let warnings = catalog_warnings(method);
+    // The checks of `send`, without the question
+    let refused = if method.is_empty() {
+        None
+    } else {
+        policy::check(method)
+            .and_then(|()| confirm::check_unattended(method))
+            .err()
+    };
+    dry_run_report(method, plan, refused, warnings)
+}
+
+/// What the catalog says about a method name, for `dry_run`.
+fn catalog_warnings(method: &str) -> Vec<String> {
+    let mut warnings = Vec::new();
+    // Lines rejected before their method was read have no name to check
+    if !method.is_empty() {
+        let mut catalog = Catalog::load();
+        if !catalog.is_fresh() {
+            match catalog.refresh() {
+                Ok(()) => catalog.save(),
+                Err(e) => warnings.push(format!("method name not checked: {}", e)),
+            }
+        }
+        let known = catalog
+            .methods()
+            .binary_search_by(|m| m.as_str().cmp(method))
+            .is_ok();
+        if catalog.is_fresh() && !known {
+            let hint = best_suggestion(method, catalog.methods())
+                .map(|s| format!(", did you mean '{}'?", s))
+                .unwrap_or_default();
+            warnings.push(format!(
+                "'{}' is not in the daemon's method list{}",
+                method, hint
+            ));
+        }
+    }
+    warnings
+}
+
+/// The report of `dry_run`, given why the call would be refused, if it
+/// would be.
+fn dry_run_report(
+    method: &str,
+    plan: Result<&ArgsPlan, (&str, i32)>,
+    refused: Option<CallError>,
+    mut warnings: Vec<String>,
+) -> (Value, i32) {
+    let mut report = json!({
+        "method": method,
+        "class": method_class(method).label(),
+    });
+    let code = match (plan, refused) {
+        (Ok(plan), None) => {
+            warnings.extend(plan.warnings.iter().cloned());
+            report["ok"] = json!(true);
+            report["args"] = redact::stdout_value(
+                plan.args
+                    .as_deref()
+                    .and_then(|args| serde_json::from_str(args).ok())
+                    .unwrap_or(Value::Null),
+            );
+            report["mapping"] = plan
+                .mapping
+                .iter()
+                .enumerate()
+                .map(|(position, (name, arg_type))| {
+                    json!({ "position": position, "name": name, "type": arg_type })
+                })
+                .collect();
+            0
+        }
+        (Ok(_), Some(refused)) => {
+            report["ok"] = json!(false);
+            report["error"] = json!(refused.message);
+            refused.code
+        }
+        (Err((error, code)), _) => {
+            report["ok"] = json!(false);
+            report["error"] = json!(error);
+            code
+        }
+    };
+    report["warnings"] = json!(warnings);
+    (report, code)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::confirm::unattended_refusal;
+    use crate::{ERROR_CODE_NOT_CONFIRMED, ERROR_CODE_PARAM, ERROR_CODE_POLICY};
+
+    fn plan() -> ArgsPlan {
+        ArgsPlan {
+            args: Some(r#"{"name":"ssh"}"#.to_string()),
+            mapping: vec![("name".to_string(), "String".to_string())],
+            warnings: vec!["fields not checked".to_string()],
+        }
+    }
+
+    #[test]
+    fn reports_a_call_that_would_be_sent() {
+        let (report, code) = dry_run_report(
+            "get_threat_info",
+            Ok(&plan()),
+            None,
+            vec!["catalog".to_string()],
+        );
+        assert_eq!(code, 0);
+        assert_eq!(report["ok"], json!(true));
+        assert_eq!(report["args"], json!({"name": "ssh"}));
+        assert_eq!(
+            report["mapping"],
+            json!([{"position": 0, "name": "name", "type": "String"}])
+        );
+        assert_eq!(report["warnings"], json!(["catalog", "fields not checked"]));
+    }
+
+    #[test]
+    fn reports_an_unconfirmed_destructive_call() {
+        let refused = unattended_refusal("clear_vulnerability_history", true, false, false);
+        let (report, code) = dry_run_report(
+            "clear_vulnerability_history",
+            Ok(&plan()),
+            refused,
+            Vec::new(),
+        );
+        assert_eq!(code, ERROR_CODE_NOT_CONFIRMED);
+        assert_eq!(report["ok"], json!(false));
+        assert!(report["error"].as_str().unwrap().contains("--yes"));
+        assert!(report.get("args").is_none());
+    }
+
+    #[test]
+    fn reports_rejected_arguments_before_refusals() {
+        let refused = CallError {
+            code: ERROR_CODE_POLICY,
+            message: "Blocked by policy".to_string(),
+        };
+        let (report, code) = dry_run_report(
+            "set_pref",
+            Err(("invalid arguments", ERROR_CODE_PARAM)),
+            Some(refused),
+            Vec::new(),
+        );
+        assert_eq!(code, ERROR_CODE_PARAM);
+        assert_eq!(report["error"], json!("invalid arguments"));
+    }
+}
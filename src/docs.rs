@@ -0,0 +1,333 @@
+use crate::classify::describe_class;
+use crate::{
+    fetch_method_meta, format_signature, initialize_core, make_example_value, profile,
+    usage_examples, ARGUMENT_FORM_NOTES, ERROR_CODE_PARAM, ERROR_CODE_SERVER_ERROR,
+};
+use edamame_core::api::api_rpc::rpc_get_api_methods;
+use std::fs;
+use std::path::Path;
+
+/// One entry of the daemon catalog, as rendered in the generated documentation.
+struct MethodDoc {
+    name: String,
+    /// `None` when the daemon lists the method but returns no metadata for it.
+    meta: Option<(String, Vec<(String, String)>)>,
+}
+
+pub fn handle_docs_generate(format: &str, out_dir: &str, verbose: bool) -> i32 {
+    initialize_core(verbose);
+
+    let active = profile::active();
+    let mut methods = match rpc_get_api_methods(
+        &active.ca_pem,
+        &active.client_pem,
+        &active.client_key,
+        &active.target,
+    ) {
+        Ok(methods) => methods,
+        Err(e) => {
+            eprintln!(">>>> Error getting API methods: {:?}", e);
+            return ERROR_CODE_SERVER_ERROR;
+        }
+    };
+    methods.sort();
+
+    let docs: Vec<MethodDoc> = methods
+        .into_iter()
+        .map(|name| {
+            let meta = match fetch_method_meta(&name) {
+                Ok(meta) => Some(meta),
+                Err(e) => {
+                    eprintln!(">>>> {}", e);
+                    None
+                }
+            };
+            MethodDoc { name, meta }
+        })
+        .collect();
+
+    let out = Path::new(out_dir);
+    if let Err(e) = fs::create_dir_all(out) {
+        eprintln!(">>>> Error creating output directory {}: {}", out_dir, e);
+        return ERROR_CODE_PARAM;
+    }
+
+    let result = match format {
+        "markdown" => fs::write(out.join("edamame_cli-methods.md"), render_markdown(&docs)),
+        "html" => fs::write(out.join("edamame_cli-methods.html"), render_html(&docs)),
+        "man" => fs::write(out.join("edamame_cli-methods.7"), render_man(&docs))
+            .and_then(|_| clap_mangen::generate_to(crate::build_cli(), out)),
+        _ => {
+            eprintln!(">>>> Unsupported documentation format: {}", format);
+            return ERROR_CODE_PARAM;
+        }
+    };
+
+    if let Err(e) = result {
+        eprintln!(">>>> Error writing documentation to {}: {}", out_dir, e);
+        return ERROR_CODE_PARAM;
+    }
+
+    eprintln!(
+        "Generated {} documentation for {} methods in {}",
+        format,
+        docs.len(),
+        out_dir
+    );
+    0
+}
+
+fn render_markdown(docs: &[MethodDoc]) -> String {
+    let mut md = String::new();
+    md.push_str("# EDAMAME CLI RPC methods\n\n");
+    md.push_str("Generated from the live daemon catalog with `edamame_cli docs generate`.\n\n");
+    for doc in docs {
+        md.push_str(&format!("- [`{}`](#{})\n", doc.name, doc.name));
+    }
+
+    for doc in docs {
+        md.push_str(&format!("\n## {}\n\n", doc.name));
+        let Some((return_type, args_meta)) = &doc.meta else {
+            md.push_str("_No metadata available for this method._\n");
+            continue;
+        };
+
+        md.push_str(&format!(
+            "```text\n{}\n```\n\n",
+            format_signature(&doc.name, return_type, args_meta)
+        ));
+        md.push_str(&format!("Class: {}\n\n", describe_class(&doc.name)));
+
+        if args_meta.is_empty() {
+            md.push_str("Arguments: none\n\n");
+        } else {
+            md.push_str("| Position | Name | Type | Example |\n");
+            md.push_str("|----------|------|------|---------|\n");
+            for (i, (name, arg_type)) in args_meta.iter().enumerate() {
+                md.push_str(&format!(
+                    "| {} | `{}` | `{}` | `{}` |\n",
+                    i,
+                    name,
+                    arg_type.replace('|', "\\|"),
+                    make_example_value(arg_type, name)
+                ));
+            }
+            md.push('\n');
+        }
+
+        md.push_str("Usage examples:\n\n```bash\n");
+        for example in usage_examples(&doc.name, args_meta) {
+            md.push_str(&example);
+            md.push('\n');
+        }
+        md.push_str("```\n");
+
+        if !args_meta.is_empty() {
+            md.push('\n');
+            for note in ARGUMENT_FORM_NOTES {
+                md.push_str(&format!("- {}\n", note));
+            }
+        }
+    }
+    md
+}
+
+fn escape_html(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+fn render_html(docs: &[MethodDoc]) -> String {
+    let mut html = String::new();
+    html.push_str("<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n");
+    html.push_str("<title>EDAMAME CLI RPC methods</title>\n</head>\n<body>\n");
+    html.push_str("<h1>EDAMAME CLI RPC methods</h1>\n<ul>\n");
+    for doc in docs {
+        let name = escape_html(&doc.name);
+        html.push_str(&format!(
+            "<li><a href=\"#{}\"><code>{}</code></a></li>\n",
+            name, name
+        ));
+    }
+    html.push_str("</ul>\n");
+
+    for doc in docs {
+        let name = escape_html(&doc.name);
+        html.push_str(&format!("<h2 id=\"{}\">{}</h2>\n", name, name));
+        let Some((return_type, args_meta)) = &doc.meta else {
+            html.push_str("<p><em>No metadata available for this method.</em></p>\n");
+            continue;
+        };
+
+        html.push_str(&format!(
+            "<pre>{}</pre>\n",
+            escape_html(&format_signature(&doc.name, return_type, args_meta))
+        ));
+        html.push_str(&format!(
+            "<p>Class: {}</p>\n",
+            escape_html(&describe_class(&doc.name))
+        ));
+
+        if args_meta.is_empty() {
+            html.push_str("<p>Arguments: none</p>\n");
+        } else {
+            html.push_str(
+                "<table>\n<tr><th>Position</th><th>Name</th><th>Type</th><th>Example</th></tr>\n",
+            );
+            for (i, (name, arg_type)) in args_meta.iter().enumerate() {
+                html.push_str(&format!(
+                    "<tr><td>{}</td><td><code>{}</code></td><td><code>{}</code></td><td><code>{}</code></td></tr>\n",
+                    i,
+                    escape_html(name),
+                    escape_html(arg_type),
+                    escape_html(&make_example_value(arg_type, name))
+                ));
+            }
+            html.push_str("</table>\n");
+        }
+
+        html.push_str("<p>Usage examples:</p>\n<pre>");
+        for example in usage_examples(&doc.name, args_meta) {
+            html.push_str(&escape_html(&example));
+            html.push('\n');
+        }
+        html.push_str("</pre>\n");
+    }
+    html.push_str("</body>\n</html>\n");
+    html
+}
+
+/// Escape a line of text for roff: backslashes, hyphens, and leading control characters.
+fn escape_roff(s: &str) -> String {
+    let escaped = s.replace('\\', "\\e").replace('-', "\\-");
+    if escaped.starts_with('.') || escaped.starts_with('\'') {
+        format!("\\&{}", escaped)
+    } else {
+        escaped
+    }
+}
+
+fn render_man(docs: &[MethodDoc]) -> String {
+    let mut man = String::new();
+    man.push_str(".TH EDAMAME_CLI\\-METHODS 7 \"\" \"edamame_cli\" \"EDAMAME CLI\"\n");
+    man.push_str(".SH NAME\nedamame_cli\\-methods \\- RPC methods exposed by the EDAMAME daemon\n");
+    man.push_str(".SH DESCRIPTION\n");
+    man.push_str("Each method can be called with \\fBedamame_cli rpc\\fR using either a JSON array of positional values or a JSON object of named arguments.\n");
+    man.push_str(".SH METHODS\n");
+
+    for doc in docs {
+        man.push_str(&format!(".SS {}\n", escape_roff(&doc.name)));
+        let Some((return_type, args_meta)) = &doc.meta else {
+            man.push_str("No metadata available for this method.\n");
+            continue;
+        };
+
+        man.push_str(&format!(
+            ".PP\n\\fB{}\\fR\n",
+            escape_roff(&format_signature(&doc.name, return_type, args_meta))
+        ));
+        man.push_str(&format!(
+            ".PP\nClass: {}\n",
+            escape_roff(&describe_class(&doc.name))
+        ));
+
+        if args_meta.is_empty() {
+            man.push_str(".PP\nArguments: none\n");
+        } else {
+            man.push_str(".PP\nArguments (array position, name, type):\n");
+            for (i, (name, arg_type)) in args_meta.iter().enumerate() {
+                man.push_str(&format!(
+                    ".TP\n[{}] \\fI{}\\fR\n{}\n",
+                    i,
+                    escape_roff(name),
+                    escape_roff(arg_type)
+                ));
+            }
+        }
+
+        man.push_str(".PP\nUsage examples:\n.PP\n.nf\n");
+        for example in usage_examples(&doc.name, args_meta) {
+            man.push_str(&escape_roff(&example));
+            man.push('\n');
+        }
+        man.push_str(".fi\n");
+    }
+
+    man.push_str(".SH SEE ALSO\n\\fBedamame_cli\\fR(1), \\fBedamame_cli\\-rpc\\fR(1)\n");
+    man
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn catalog() -> Vec<MethodDoc> {
+        vec![
+            MethodDoc {
+                name: "get_score".to_string(),
+                meta: Some((
+                    "String".to_string(),
+                    vec![("complete_only".to_string(), "bool".to_string())],
+                )),
+            },
+            MethodDoc {
+                name: "get_threats".to_string(),
+                meta: Some(("String".to_string(), Vec::new())),
+            },
+            MethodDoc {
+                name: "get-hidden".to_string(),
+                meta: None,
+            },
+        ]
+    }
+
+    #[test]
+    fn renders_markdown() {
+        let md = render_markdown(&catalog());
+        assert!(md.starts_with("# EDAMAME CLI RPC methods\n"));
+        assert!(md.contains("- [`get_score`](#get_score)\n"));
+        assert!(md.contains("\n## get_score\n"));
+        assert!(md.contains("| 0 | `complete_only` | `bool` | `true` |\n"));
+        assert!(md.contains("\n## get_threats\n"));
+        assert!(md.contains("Arguments: none\n"));
+        assert!(md.contains("_No metadata available for this method._\n"));
+    }
+
+    #[test]
+    fn renders_html() {
+        let html = render_html(&catalog());
+        assert!(html.contains("get_score"));
+        assert!(html.contains("complete_only"));
+        assert!(html.trim_end().ends_with("</html>"));
+    }
+
+    #[test]
+    fn renders_man_pages() {
+        let man = render_man(&catalog());
+        assert!(man.starts_with(".TH EDAMAME_CLI\\-METHODS 7 "));
+        assert!(man.contains(".SS get_score\n"));
+        assert!(man.contains(".TP\n[0] \\fIcomplete_only\\fR\nbool\n"));
+        assert!(man.contains(".SS get\\-hidden\n"));
+
+        // The pages of the subcommands come from the command line definition
+        let out = std::env::temp_dir().join(format!("docs-test-{}", std::process::id()));
+        fs::create_dir_all(&out).unwrap();
+        clap_mangen::generate_to(crate::build_cli(), &out).unwrap();
+        let rpc_page = fs::read_to_string(out.join("edamame_cli-rpc.1"));
+        let root_page = fs::read_to_string(out.join("edamame_cli.1"));
+        fs::remove_dir_all(&out).unwrap();
+        assert!(rpc_page.unwrap().contains("Call a specific RPC method"));
+        assert!(root_page.unwrap().contains("rpc"));
+    }
+
+    #[test]
+    fn escapes_text() {
+        assert_eq!(escape_html("<a & \"b\">"), "&lt;a &amp; &quot;b&quot;&gt;");
+        assert_eq!(escape_roff("a-b\\c"), "a\\-b\\ec");
+        assert_eq!(escape_roff(".TH"), "\\&.TH");
+        assert_eq!(escape_roff("'quoted"), "\\&'quoted");
+    }
+}
@@ -0,0 +1,157 @@
+use crate::args::plan_args;
+use crate::dispatch::{self, CallError};
+use crate::output::result_value;
+use crate::predicate::Predicate;
+use crate::redact::stdout_value;
+use crate::{write_stdout, ERROR_CODE_MISMATCH, ERROR_CODE_PARAM};
+use serde_json::Value;
+use std::fs;
+
+/// A method call and the conditions its result must satisfy.
+pub struct Check {
+    /// Shown instead of the method name when set.
+    pub name: Option<String>,
+    pub method: String,
+    /// JSON arguments as given by the user, before `prepare_args`.
+    pub args: String,
+    pub expect: Vec<Predicate>,
+}
+
+/// Load the checks of a checks file:
+///
+/// ```json
+/// {
+///   "assertions": [
+///     { "method": "get_score", "expect": ["stars >= 4"] },
+///     { "name": "no active threats", "method": "get_threats", "expect": "len(active) == 0" }
+///   ]
+/// }
+/// ```
+///
+/// `args` takes the same array or object forms as `rpc`, and `expect` a
+/// condition or a list of them.
+fn load_checks(path: &str) -> Result<Vec<Check>, String> {
+    let contents =
+        fs::read_to_string(path).map_err(|e| format!("Error reading {}: {}", path, e))?;
+    let file: Value =
+        serde_json::from_str(&contents).map_err(|e| format!("Error parsing {}: {}", path, e))?;
+    let Some(entries) = file["assertions"].as_array() else {
+        return Err(format!("{}: expected an \"assertions\" array", path));
+    };
+
+    let mut checks = Vec::with_capacity(entries.len());
+    for (index, entry) in entries.iter().enumerate() {
+        let context = format!("{}: assertion {}", path, index + 1);
+        let Some(method) = entry["method"].as_str() else {
+            return Err(format!("{}: expected a \"method\" string", context));
+        };
+        let expressions: Vec<&str> = match &entry["expect"] {
+            Value::String(expression) => vec![expression],
+            Value::Array(expressions) => expressions
+                .iter()
+                .map(|expression| {
+                    expression
+                        .as_str()
+                        .ok_or_else(|| format!("{}: conditions must be strings", context))
+                })
+                .collect::<Result<_, _>>()?,
+            _ => Vec::new(),
+        };
+        if expressions.is_empty() {
+            return Err(format!(
+                "{}: expected an \"expect\" condition or list of conditions",
+                context
+            ));
+        }
+        let expect = expressions
+            .iter()
+            .map(|expression| {
+                Predicate::parse(expression)
+                    .map_err(|e| format!("{}: invalid condition '{}': {}", context, expression, e))
+            })
+            .collect::<Result<_, _>>()?;
+        checks.push(Check {
+            name: entry["name"].as_str().map(str::to_string),
+            method: method.to_string(),
+            args: match &entry["args"] {
+                Value::Null => String::new(),
+                args => args.to_string(),
+            },
+            expect,
+        });
+    }
+    Ok(checks)
+}
+
+/// Run the check given on the command line, then those of the checks file.
+/// Each method is called once and its conditions evaluated, printing one
+/// PASS/FAIL line per condition with the values it looked at. Exits with the
+/// code of the first check that could not be run (`ERROR_CODE_PARAM` for
+/// invalid arguments, `ERROR_CODE_SERVER_ERROR` for daemon errors),
+/// otherwise with `ERROR_CODE_MISMATCH` when a condition does not hold.
+pub fn handle_assert(inline: Option<Check>, checks_file: Option<&str>) -> i32 {
+    let mut checks: Vec<Check> = inline.into_iter().collect();
+    if let Some(path) = checks_file {
+        match load_checks(path) {
+            Ok(file_checks) => checks.extend(file_checks),
+            Err(e) => {
+                eprintln!(">>>> {}", e);
+                return ERROR_CODE_PARAM;
+            }
+        }
+    }
+
+    let mut passed = 0;
+    let mut failed = 0;
+    let mut errors = 0;
+    // Exit code of the first check that could not be run
+    let mut error_code = 0;
+
+    for check in &checks {
+        let label = check.name.as_deref().unwrap_or(&check.method);
+        let result = match plan_args(&check.method, &check.args)
+            .map_err(|(message, code)| CallError { code, message })
+            .and_then(|plan| dispatch::call(&check.method, plan.args.as_deref()))
+        {
+            Ok(result) => result_value(&result),
+            Err(e) => {
+                errors += 1;
+                if error_code == 0 {
+                    error_code = e.code;
+                }
+                let _ = write_stdout(&format!("ERROR {}: {}", label, e));
+                continue;
+            }
+        };
+        // Conditions are checked on the result, values shown redacted
+        let shown = stdout_value(result.clone());
+        for predicate in &check.expect {
+            let outcome = if predicate.matches(&result) {
+                passed += 1;
+                "PASS"
+            } else {
+                failed += 1;
+                "FAIL"
+            };
+            let observed = predicate.observed(&shown);
+            let observed = if observed.is_empty() {
+                String::new()
+            } else {
+                format!(" ({})", observed)
+            };
+            let _ = write_stdout(&format!("{} {}: {}{}", outcome, label, predicate, observed));
+        }
+    }
+
+    eprintln!(
+        "Summary: {} passed, {} failed, {} error(s)",
+        passed, failed, errors
+    );
+    if errors > 0 {
+        error_code
+    } else if failed > 0 {
+        ERROR_CODE_MISMATCH
+    } else {
+        0
+    }
+}
@@ -1,15 +1,54 @@
 use crate::CORE_VERSION;
-use clap::{arg, ArgAction, Command};
+use clap::{arg, ArgAction, ArgMatches, Command};
 use clap_complete::{generate, Generator, Shell};
 use edamame_core::api::api_core::*;
 use edamame_core::api::api_rpc::*;
 use envcrypt::envc;
 use lazy_static::lazy_static;
-use std::io::{self, BufRead, ErrorKind, Write};
+use predicate::Predicate;
+use std::io::{self, ErrorKind, Write};
 use std::process::exit;
+use std::time::Duration;
+
+mod args;
+mod audit;
+mod batch;
+mod catalog;
+mod classify;
+mod complete;
+mod completion_install;
+mod config;
+mod confirm;
+mod dispatch;
+mod docs;
+mod gate;
+mod output;
+mod policy;
+mod poll;
+mod predicate;
+mod profile;
+mod reconnect;
+mod recording;
+mod redact;
+mod repl;
+mod script;
+mod wait_for;
+mod watch;
+mod workflow;
 
 const ERROR_CODE_SERVER_ERROR: i32 = 2;
 const ERROR_CODE_PARAM: i32 = 3;
+/// Results differ from what was expected (e.g. `replay`, `assert`), or the
+/// audit log failed its check (`audit verify`).
+const ERROR_CODE_MISMATCH: i32 = 4;
+/// A condition did not hold in time (e.g. `wait-for`).
+const ERROR_CODE_TIMEOUT: i32 = 5;
+/// A script stopped on an error it did not catch (`script`).
+const ERROR_CODE_SCRIPT: i32 = 6;
+/// A call to a destructive method was declined, or not confirmed with `--yes`.
+const ERROR_CODE_NOT_CONFIRMED: i32 = 7;
+/// A call was blocked by the method policy (`--read-only`, policy.json).
+const ERROR_CODE_POLICY: i32 = 8;
 
 lazy_static! {
     pub static ref EDAMAME_TARGET: String =
@@ -74,6 +113,102 @@ fn print_completions<G: Generator>(gen: G, cmd: &mut Command) {
     generate(gen, cmd, cmd.get_name().to_string(), &mut io::stdout());
 }
 
+/// `--shell` and `--user`/`--system` options shared by `completion install`
+/// and `completion uninstall`.
+fn completion_scope_args(cmd: Command) -> Command {
+    cmd.arg(
+        arg!(--shell <SHELL> "Shell to install for ('auto' detects it from $SHELL)")
+            .required(false)
+            .value_parser(["auto", "bash", "zsh", "fish", "powershell"])
+            .default_value("auto"),
+    )
+    .arg(
+        arg!(--user "Use the per-user completion directory (default)")
+            .required(false)
+            .action(ArgAction::SetTrue)
+            .conflicts_with("system"),
+    )
+    .arg(
+        arg!(--system "Use the system-wide completion directory (usually requires root)")
+            .required(false)
+            .action(ArgAction::SetTrue),
+    )
+}
+
+/// Method and arguments of the commands that call a single method.
+fn call_args(cmd: Command) -> Command {
+    cmd.arg(
+        arg!(<METHOD> "Method name")
+            .required(true)
+            .value_parser(clap::value_parser!(String)),
+    )
+    .arg(
+        arg!([JSON_ARGS_ARRAY] "JSON arguments array")
+            .required(false)
+            .value_parser(clap::value_parser!(String)),
+    )
+    .arg(
+        arg!(--arg <NAME_VALUE> "Named argument as name=value; the value is parsed as JSON and falls back to a string")
+            .required(false)
+            .action(ArgAction::Append)
+            .conflicts_with("JSON_ARGS_ARRAY")
+            .value_parser(clap::value_parser!(String)),
+    )
+}
+
+/// JSON arguments given to a `call_args` command, either as a blob or as
+/// `--arg` pairs.
+fn call_json_args(args: &ArgMatches) -> Result<String, String> {
+    let named_args: Vec<String> = args
+        .get_many::<String>("arg")
+        .map(|a| a.cloned().collect())
+        .unwrap_or_default();
+    match args.get_one::<String>("JSON_ARGS_ARRAY") {
+        Some(json_args_array) => Ok(json_args_array.to_string()),
+        None if !named_args.is_empty() => args::named_args_to_json(&named_args),
+        None => Ok("[]".to_string()),
+    }
+}
+
+/// `--dry-run`, shared by the commands that send calls built from user input.
+fn dry_run_args(cmd: Command) -> Command {
+    cmd.arg(
+        arg!(--"dry-run" "Print the method and argument object that would be sent, and any warnings, without calling it")
+            .required(false)
+            .action(ArgAction::SetTrue),
+    )
+}
+
+/// Options shared by `batch` and `rpc-many`.
+fn batch_args(cmd: Command) -> Command {
+    dry_run_args(cmd)
+        .arg(
+            arg!(--"continue-on-error" "Run the remaining calls after a failure")
+                .required(false)
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            arg!(--parallel <N> "Run up to N calls at once")
+                .required(false)
+                .value_parser(clap::value_parser!(u16).range(1..))
+                .default_value("1"),
+        )
+        .arg(
+            arg!(--"allow-mutating" "Also run methods that may change state in parallel")
+                .required(false)
+                .action(ArgAction::SetTrue),
+        )
+}
+
+fn batch_options(args: &ArgMatches) -> batch::BatchOptions {
+    batch::BatchOptions {
+        continue_on_error: args.get_flag("continue-on-error"),
+        parallel: usize::from(*args.get_one::<u16>("parallel").unwrap()),
+        allow_mutating: args.get_flag("allow-mutating"),
+        dry_run: args.get_flag("dry-run"),
+    }
+}
+
 pub fn build_cli() -> Command {
     // Turn it into a &'static str by leaking it
     let core_version_runtime: String = CORE_VERSION.to_string();
@@ -86,9 +221,53 @@ pub fn build_cli() -> Command {
         .subcommand(
             Command::new("completion")
                 .about("Generate shell completion scripts")
+                .long_about("Generate shell completion scripts\n\nThe bash, zsh, fish and PowerShell scripts call back into edamame_cli to complete RPC method names and their arguments from the daemon catalog.\n\nUse 'completion install' to write the script to the right location for your shell.")
+                .args_conflicts_with_subcommands(true)
+                .subcommand_negates_reqs(true)
                 .arg(
                     arg!(<SHELL> "The shell to generate completions for")
                         .value_parser(clap::value_parser!(Shell)),
+                )
+                .subcommand(
+                    completion_scope_args(
+                        Command::new("install")
+                            .about("Install the completion script for the current shell")
+                            .long_about("Install the completion script for the current shell\n\nThe script is written to the standard user or system completion directory of the shell. Running it again only rewrites the script when it changed."),
+                    ),
+                )
+                .subcommand(completion_scope_args(
+                    Command::new("uninstall").about("Remove an installed completion script"),
+                ))
+                .subcommand(
+                    Command::new("status")
+                        .about("Show where completion scripts are installed")
+                        .arg(
+                            arg!(--shell <SHELL> "Shell to check")
+                                .required(false)
+                                .value_parser(["all", "auto", "bash", "zsh", "fish", "powershell"])
+                                .default_value("all"),
+                        ),
+                ),
+        )
+        .subcommand(
+            Command::new(complete::COMPLETE_SUBCOMMAND)
+                .about("Print completion candidates (used by the completion scripts)")
+                .hide(true)
+                .arg(
+                    arg!(--shell <SHELL> "Shell requesting the candidates")
+                        .required(false)
+                        .default_value("bash"),
+                )
+                .arg(
+                    arg!(--current <WORD> "Word under the cursor")
+                        .required(false)
+                        .allow_hyphen_values(true)
+                        .default_value(""),
+                )
+                .arg(
+                    arg!([WORDS] ... "Words before the cursor")
+                        .last(true)
+                        .allow_hyphen_values(true),
                 ),
         )
         .arg(
@@ -99,6 +278,31 @@ pub fn build_cli() -> Command {
             .action(ArgAction::Count)
             .global(true),
         )
+        .arg(
+            arg!(--profile <PROFILE> "Connection profile from profiles.json; local state such as the interactive history is kept per profile")
+                .required(false)
+                .env("EDAMAME_CLI_PROFILE")
+                .default_value(profile::DEFAULT_PROFILE)
+                .global(true),
+        )
+        .arg(
+            arg!(--"read-only" "Only allow calls to read-only methods, on top of the method policy files")
+                .required(false)
+                .action(ArgAction::SetTrue)
+                .global(true),
+        )
+        .arg(
+            arg!(--redact "Redact sensitive fields (PINs, passwords, tokens, ... and the rules of redaction.json) in the results and errors printed")
+                .required(false)
+                .action(ArgAction::SetTrue)
+                .global(true),
+        )
+        .arg(
+            arg!(-y --yes "Call destructive methods (clear_*, reset_*, ...) without asking for confirmation; required when there is no terminal to ask on")
+                .required(false)
+                .action(ArgAction::SetTrue)
+                .global(true),
+        )
         .subcommand(
             Command::new("list-methods")
                 .about("List all available RPC methods")
@@ -108,6 +312,11 @@ pub fn build_cli() -> Command {
                         .required(false)
                         .action(ArgAction::SetTrue),
                 )
+                .arg(
+                    arg!(--grouped "Group methods by verb and show whether each one is read-only, mutating, destructive or long-running")
+                        .required(false)
+                        .action(ArgAction::SetTrue),
+                )
         )
         .subcommand(
             Command::new("get-method-info")
@@ -124,26 +333,234 @@ pub fn build_cli() -> Command {
                 .about("List information about all available RPC methods")
                 .long_about("List information about all available RPC methods\n\nThis shows detailed info for every method, including required parameters")
         )
-        .subcommand(Command::new("interactive").about("Enter interactive mode"))
         .subcommand(
-            Command::new("rpc")
-                .about("Call a specific RPC method")
+            Command::new("interactive")
+                .about("Enter interactive mode")
+                .long_about("Enter interactive mode\n\nWhen stdin is not a terminal, the commands read from it run as a script: no banner or prompts are printed, a summary of succeeded and failed commands goes to stderr and the exit code is the one of the first failure.")
+                .arg(
+                    arg!(--record <FILE> "Record the session (commands, resolved arguments, results and timings) as NDJSON; sensitive fields are redacted")
+                        .required(false)
+                        .value_parser(clap::value_parser!(String)),
+                )
+                .arg(
+                    arg!(--"no-redact" "Record arguments, results and errors as they are, so that every call can be replayed")
+                        .required(false)
+                        .requires("record")
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(
+                    arg!(--echo "Print each command before its output when stdin is not a terminal")
+                        .required(false)
+                        .action(ArgAction::SetTrue),
+                )
                 .arg(
-                    arg!(<METHOD> "Method name")
+                    arg!(--"fail-fast" "Stop at the first failing command when stdin is not a terminal")
+                        .required(false)
+                        .action(ArgAction::SetTrue),
+                ),
+        )
+        .subcommand(batch_args(
+            Command::new("batch")
+                .about("Run the calls of an NDJSON file in a single process")
+                .long_about("Run the calls of an NDJSON file in a single process\n\nEach line is one call: {\"method\": \"get_score\", \"args\": {...}}. The args are optional and take the same array or object forms as 'rpc'. One result envelope is printed per call, in input order, and a summary on stderr. Stops at the first failure unless --continue-on-error is given; the exit code is the one of the first failure.")
+                .arg(
+                    arg!(<FILE> "Call file (NDJSON), or - for stdin")
+                        .required(true)
+                        .value_parser(clap::value_parser!(String)),
+                ),
+        ))
+        .subcommand(batch_args(
+            Command::new("rpc-many")
+                .about("Run several RPC calls in a single process")
+                .long_about("Run several RPC calls in a single process\n\nEach CALL is a method name, optionally followed by its JSON arguments as in the interactive mode, e.g. 'get_score [true]'. Output and exit codes are the same as for 'batch'. With --parallel, calls run concurrently but results are still printed in the order given; methods that may change state are refused unless --allow-mutating is given.")
+                .arg(
+                    arg!(<CALL> ... "Method name and optional JSON arguments")
+                        .required(true)
+                        .value_parser(clap::value_parser!(String)),
+                ),
+        ))
+        .subcommand(
+            Command::new("replay")
+                .about("Re-run the calls of a recorded session and report differing results")
+                .long_about("Re-run the calls of a recorded session and report differing results\n\nRecord a session with 'interactive --record FILE'. Use --profile to replay it against another daemon. Exits with code 4 when any result differs from the recording.")
+                .arg(
+                    arg!(<FILE> "Session recording (NDJSON)")
+                        .required(true)
+                        .value_parser(clap::value_parser!(String)),
+                ),
+        )
+        .subcommand(
+            Command::new("audit")
+                .about("Show or check the local audit log of RPC calls")
+                .long_about("Show or check the local audit log of RPC calls\n\nEvery call the CLI makes, including the ones blocked by the method policy or not confirmed, is appended to audit.log in the user data directory (~/.local/share/edamame_cli/ on Linux). Sensitive argument fields are redacted. The records are hash-chained so that edited or removed records are detected by 'audit verify'.")
+                .subcommand_required(true)
+                .subcommand(
+                    Command::new("verify")
+                        .about("Check that no record of the audit log was modified or removed")
+                        .long_about("Check that no record of the audit log was modified or removed\n\nPrints the number of records and the hash of the last one, and exits with code 4 at the first record that fails the check. Records removed from the end of the log leave an intact chain: compare the last hash with the one of a previous check to detect it."),
+                )
+                .subcommand(
+                    Command::new("show")
+                        .about("Print the records of the audit log")
+                        .arg(
+                            arg!(--since <WHEN> "Only records made since a date (YYYY-MM-DD[THH:MM:SS], UTC) or for a duration (e.g. 2h, 7d)")
+                                .required(false)
+                                .value_parser(audit::parse_since),
+                        )
+                        .arg(
+                            arg!(--json "Print the raw NDJSON records")
+                                .required(false)
+                                .action(ArgAction::SetTrue),
+                        ),
+                ),
+        )
+        .subcommand(
+            Command::new("docs")
+                .about("Generate documentation from the daemon catalog")
+                .subcommand_required(true)
+                .subcommand(
+                    Command::new("generate")
+                        .about("Render every RPC method as Markdown, man pages or HTML")
+                        .long_about("Render every RPC method as Markdown, man pages or HTML\n\nThe method catalog is fetched from the running daemon. The man format also renders man pages for the edamame_cli subcommands.")
+                        .arg(
+                            arg!(--format <FORMAT> "Output format")
+                                .required(false)
+                                .value_parser(["markdown", "man", "html"])
+                                .default_value("markdown"),
+                        )
+                        .arg(
+                            arg!(--out <DIR> "Output directory")
+                                .required(true)
+                                .value_parser(clap::value_parser!(String)),
+                        ),
+                ),
+        )
+        .subcommand(
+            dry_run_args(call_args(
+                Command::new("rpc").about("Call a specific RPC method"),
+            ))
+            .arg(
+                arg!(--pretty "Pretty print the JSON response")
+                    .required(false)
+                    .action(ArgAction::SetTrue),
+            ),
+        )
+        .subcommand(
+            call_args(
+                Command::new("watch")
+                    .about("Call a method repeatedly and highlight what changes")
+                    .long_about("Call a method repeatedly and highlight what changes\n\nOn a terminal the result is redrawn in place and the fields that changed since the previous call are highlighted. Otherwise each result that differs from the previous one is printed as an NDJSON line with the list of changed fields. Stops after --count calls, at the first change with --until-change, or on Ctrl-C."),
+            )
+            .arg(
+                arg!(--interval <DURATION> "Time between calls, e.g. 500ms, 5s or 1m")
+                    .required(false)
+                    .value_parser(poll::parse_duration)
+                    .default_value("2s"),
+            )
+            .arg(
+                arg!(--count <N> "Stop after N calls")
+                    .required(false)
+                    .value_parser(clap::value_parser!(u64).range(1..)),
+            )
+            .arg(
+                arg!(--"until-change" "Stop at the first result that differs from the previous one")
+                    .required(false)
+                    .action(ArgAction::SetTrue),
+            ),
+        )
+        .subcommand(
+            dry_run_args(
+                Command::new("run")
+                    .about("Run the steps of a YAML workflow file")
+                    .long_about("Run the steps of a YAML workflow file\n\nEach step calls a method with optional arguments and can capture values from its result into variables, run only 'when' conditions on the variables hold, check 'expect' conditions on its result, 'retry' and run 'on_failure' cleanup steps. Arguments go through the same checks as 'rpc'. A report line is printed per step; the run stops at the first failing step and exits with its error code.")
+                    .arg(
+                        arg!(<WORKFLOW> "Workflow file (YAML)")
+                            .required(true)
+                            .value_parser(clap::value_parser!(String)),
+                    )
+                    .arg(
+                        arg!(--var <NAME_VALUE> "Set a workflow variable as name=value; the value is parsed as JSON and falls back to a string")
+                            .required(false)
+                            .action(ArgAction::Append)
+                            .value_parser(workflow::parse_var),
+                    ),
+            ),
+        )
+        .subcommand(
+            Command::new("script")
+                .about("Run a script that calls RPC methods")
+                .long_about("Run a script that calls RPC methods\n\nScripts are written in Rhai (https://rhai.rs) and can use rpc(method, args), methods(), info(method), to_json(value), parse_json(text) and print(). They have no access to files or the network other than the daemon connection of the active profile. The exit code is the one given to exit(), the one of an rpc() error the script did not catch, or 6 for any other script error.")
+                .arg(
+                    arg!(<SCRIPT> "Script file")
                         .required(true)
                         .value_parser(clap::value_parser!(String)),
                 )
+                .arg(
+                    arg!(--var <NAME_VALUE> "Set a script constant as name=value; the value is parsed as JSON and falls back to a string")
+                        .required(false)
+                        .action(ArgAction::Append)
+                        .value_parser(workflow::parse_var),
+                ),
+        )
+        .subcommand(
+            Command::new("assert")
+                .about("Check conditions on RPC results, for CI gates")
+                .long_about("Check conditions on RPC results, for CI gates\n\nCalls METHOD and checks each --expect condition on its result, e.g. 'stars >= 4' or 'len(active) == 0', and/or runs the checks of a --checks file. Prints one PASS or FAIL line per condition with the values it looked at. Exits with code 4 when a condition does not hold, 2 when a call fails and 3 when the arguments of a check are invalid.")
+                .arg(
+                    arg!([METHOD] "Method name")
+                        .required_unless_present("checks")
+                        .requires("expect")
+                        .value_parser(clap::value_parser!(String)),
+                )
                 .arg(
                     arg!([JSON_ARGS_ARRAY] "JSON arguments array")
                         .required(false)
                         .value_parser(clap::value_parser!(String)),
                 )
                 .arg(
-                    arg!(--pretty "Pretty print the JSON response")
+                    arg!(--arg <NAME_VALUE> "Named argument as name=value; the value is parsed as JSON and falls back to a string")
                         .required(false)
-                        .action(ArgAction::SetTrue),
+                        .action(ArgAction::Append)
+                        .conflicts_with("JSON_ARGS_ARRAY")
+                        .value_parser(clap::value_parser!(String)),
+                )
+                .arg(
+                    arg!(--expect <EXPR> "Condition on the result (repeatable)")
+                        .required(false)
+                        .action(ArgAction::Append)
+                        .requires("METHOD")
+                        .value_parser(Predicate::parse),
+                )
+                .arg(
+                    arg!(--checks <FILE> "JSON file with a list of assertions")
+                        .required(false)
+                        .value_parser(clap::value_parser!(String)),
                 ),
         )
+        .subcommand(
+            call_args(
+                Command::new("wait-for")
+                    .about("Poll a method until its result satisfies a condition")
+                    .long_about("Poll a method until its result satisfies a condition\n\nThe condition is evaluated on the JSON result, e.g. 'status == \"ready\"', 'stars >= 4' or 'len(threats) == 0 && scanned'. Failing calls count as not ready, so this also waits for the daemon to start. Prints the result that satisfied the condition and exits with 0, or prints the last result and exits with code 5 on timeout."),
+            )
+            .arg(
+                arg!(--until <EXPR> "Condition on the result")
+                    .required(true)
+                    .value_parser(Predicate::parse),
+            )
+            .arg(
+                arg!(--timeout <DURATION> "Give up after this long, e.g. 30s or 5m")
+                    .required(false)
+                    .value_parser(poll::parse_duration)
+                    .default_value("1m"),
+            )
+            .arg(
+                arg!(--interval <DURATION> "Time between calls")
+                    .required(false)
+                    .value_parser(poll::parse_duration)
+                    .default_value("2s"),
+            ),
+        )
 }
 
 fn run() {
@@ -152,13 +569,56 @@ fn run() {
 
     // Handle completion subcommand before other commands
     if let Some(("completion", sub_matches)) = matches.subcommand() {
-        let shell = sub_matches.get_one::<Shell>("SHELL").unwrap();
-        print_completions(*shell, &mut cmd);
-        return;
+        let scope = |args: &clap::ArgMatches| {
+            if args.get_flag("system") {
+                completion_install::Scope::System
+            } else {
+                completion_install::Scope::User
+            }
+        };
+        match sub_matches.subcommand() {
+            Some(("install", args)) => exit(completion_install::handle_completion_install(
+                args.get_one::<String>("shell").unwrap(),
+                scope(args),
+                false,
+            )),
+            Some(("uninstall", args)) => exit(completion_install::handle_completion_install(
+                args.get_one::<String>("shell").unwrap(),
+                scope(args),
+                true,
+            )),
+            Some(("status", args)) => exit(completion_install::handle_completion_status(
+                args.get_one::<String>("shell").unwrap(),
+            )),
+            _ => {
+                let shell = sub_matches.get_one::<Shell>("SHELL").unwrap();
+                complete::print_completion_script(*shell, &mut cmd);
+                return;
+            }
+        }
+    }
+    if let Some((complete::COMPLETE_SUBCOMMAND, sub_matches)) = matches.subcommand() {
+        let words: Vec<String> = sub_matches
+            .get_many::<String>("WORDS")
+            .map(|w| w.cloned().collect())
+            .unwrap_or_default();
+        // Best effort: an unknown profile completes with the default catalog
+        let _ = profile::set_active_profile(matches.get_one::<String>("profile").unwrap());
+        exit(complete::handle_complete(
+            sub_matches.get_one::<String>("shell").unwrap(),
+            sub_matches.get_one::<String>("current").unwrap(),
+            &words,
+        ));
     }
 
     // Check for verbose flag count
-    let verbose_level = matches.get_count("verbose");
+    let mut verbose_level = matches.get_count("verbose");
+    // The debug and trace logs of edamame_core include call arguments and
+    // results, which the CLI cannot redact
+    if matches.get_flag("redact") && verbose_level > 1 {
+        eprintln!("Debug and trace logging are not available with --redact.");
+        verbose_level = 1;
+    }
     let log_level = match verbose_level {
         0 => None,
         1 => {
@@ -181,25 +641,152 @@ fn run() {
 
     let verbose = verbose_level > 0;
 
+    if let Err(e) = profile::set_active_profile(matches.get_one::<String>("profile").unwrap()) {
+        eprintln!(">>>> {}", e);
+        exit(ERROR_CODE_PARAM);
+    }
+
+    confirm::set_assume_yes(matches.get_flag("yes"));
+    policy::set_read_only(matches.get_flag("read-only"));
+    redact::set_redact_stdout(matches.get_flag("redact"));
+
     let exit_code = match matches.subcommand() {
-        Some(("list-methods", args)) => handle_list_methods(args.get_flag("pretty"), verbose),
+        Some(("list-methods", args)) => {
+            handle_list_methods(args.get_flag("pretty"), args.get_flag("grouped"), verbose)
+        }
         Some(("get-method-info", args)) => handle_get_method_info(
             args.get_one::<String>("METHOD").unwrap().to_string(),
             verbose,
         ),
         Some(("list-method-infos", _)) => handle_list_method_infos(verbose),
-        Some(("rpc", args)) => handle_rpc(
-            args.get_one::<String>("METHOD").unwrap().to_string(),
-            match args.get_one::<String>("JSON_ARGS_ARRAY") {
-                Some(json_args_array) => json_args_array.to_string(),
-                None => "[]".to_string(),
-            },
-            args.get_flag("pretty"),
+        Some(("rpc", args)) => match call_json_args(args) {
+            Ok(json_args) => handle_rpc(
+                args.get_one::<String>("METHOD").unwrap().to_string(),
+                json_args,
+                args.get_flag("pretty"),
+                args.get_flag("dry-run"),
+                verbose,
+            ),
+            Err(e) => {
+                eprintln!(">>>> {}", e);
+                ERROR_CODE_PARAM
+            }
+        },
+        Some(("run", args)) => {
+            initialize_core(verbose);
+            let vars: Vec<(String, serde_json::Value)> = args
+                .get_many::<(String, serde_json::Value)>("var")
+                .map(|v| v.cloned().collect())
+                .unwrap_or_default();
+            workflow::handle_run(
+                args.get_one::<String>("WORKFLOW").unwrap(),
+                &vars,
+                args.get_flag("dry-run"),
+            )
+        }
+        Some(("script", args)) => {
+            initialize_core(verbose);
+            let vars: Vec<(String, serde_json::Value)> = args
+                .get_many::<(String, serde_json::Value)>("var")
+                .map(|v| v.cloned().collect())
+                .unwrap_or_default();
+            script::handle_script(args.get_one::<String>("SCRIPT").unwrap(), &vars)
+        }
+        Some(("assert", args)) => match call_json_args(args) {
+            Ok(json_args) => {
+                initialize_core(verbose);
+                let inline = args.get_one::<String>("METHOD").map(|method| gate::Check {
+                    name: None,
+                    method: method.to_string(),
+                    args: json_args,
+                    expect: args
+                        .get_many::<Predicate>("expect")
+                        .map(|e| e.cloned().collect())
+                        .unwrap_or_default(),
+                });
+                gate::handle_assert(inline, args.get_one::<String>("checks").map(String::as_str))
+            }
+            Err(e) => {
+                eprintln!(">>>> {}", e);
+                ERROR_CODE_PARAM
+            }
+        },
+        Some(("wait-for", args)) => match call_json_args(args) {
+            Ok(json_args) => {
+                initialize_core(verbose);
+                wait_for::handle_wait_for(
+                    args.get_one::<String>("METHOD").unwrap(),
+                    &json_args,
+                    &wait_for::WaitOptions {
+                        until: args.get_one::<Predicate>("until").unwrap().clone(),
+                        timeout: *args.get_one::<Duration>("timeout").unwrap(),
+                        interval: *args.get_one::<Duration>("interval").unwrap(),
+                    },
+                )
+            }
+            Err(e) => {
+                eprintln!(">>>> {}", e);
+                ERROR_CODE_PARAM
+            }
+        },
+        Some(("watch", args)) => match call_json_args(args) {
+            Ok(json_args) => {
+                initialize_core(verbose);
+                watch::handle_watch(
+                    args.get_one::<String>("METHOD").unwrap(),
+                    &json_args,
+                    &watch::WatchOptions {
+                        interval: *args.get_one::<Duration>("interval").unwrap(),
+                        count: args.get_one::<u64>("count").copied(),
+                        until_change: args.get_flag("until-change"),
+                    },
+                )
+            }
+            Err(e) => {
+                eprintln!(">>>> {}", e);
+                ERROR_CODE_PARAM
+            }
+        },
+        Some(("audit", args)) => match args.subcommand() {
+            Some(("verify", _)) => audit::handle_audit_verify(),
+            Some(("show", args)) => audit::handle_audit_show(
+                args.get_one::<u64>("since").copied(),
+                args.get_flag("json"),
+            ),
+            _ => ERROR_CODE_PARAM,
+        },
+        Some(("docs", args)) => match args.subcommand() {
+            Some(("generate", args)) => docs::handle_docs_generate(
+                args.get_one::<String>("format").unwrap(),
+                args.get_one::<String>("out").unwrap(),
+                verbose,
+            ),
+            _ => ERROR_CODE_PARAM,
+        },
+        Some(("interactive", args)) => repl::interactive_mode(
             verbose,
+            &repl::ReplOptions {
+                record: args.get_one::<String>("record").map(String::as_str),
+                redact_record: !args.get_flag("no-redact"),
+                echo: args.get_flag("echo"),
+                fail_fast: args.get_flag("fail-fast"),
+            },
         ),
-        Some(("interactive", _)) => {
-            interactive_mode(verbose);
-            0
+        Some(("batch", args)) => {
+            initialize_core(verbose);
+            batch::handle_batch(
+                args.get_one::<String>("FILE").unwrap(),
+                &batch_options(args),
+            )
+        }
+        Some(("rpc-many", args)) => {
+            initialize_core(verbose);
+            let calls: Vec<String> = args.get_many::<String>("CALL").unwrap().cloned().collect();
+            batch::handle_rpc_many(&calls, &batch_options(args))
+        }
+        Some(("replay", args)) => {
+            initialize_core(verbose);
+            recording::handle_replay(args.get_one::<String>("FILE").unwrap())
         }
         _ => {
             initialize_core(verbose);
@@ -265,9 +852,64 @@ fn make_example_value(arg_type: &str, arg_name: &str) -> String {
     }
 }
 
+fn format_signature(method: &str, return_type: &str, args_meta: &[(String, String)]) -> String {
+    let args: Vec<String> = args_meta
+        .iter()
+        .map(|(name, arg_type)| format!("{}: {}", name, arg_type))
+        .collect();
+    format!("{}({}) -> {}", method, args.join(", "), return_type)
+}
+
+fn usage_examples(method: &str, args_meta: &[(String, String)]) -> Vec<String> {
+    if args_meta.is_empty() {
+        return vec![
+            format!("edamame_cli rpc {}", method),
+            format!("edamame_cli rpc {} --pretty", method),
+        ];
+    }
+
+    let example_values: Vec<String> = args_meta
+        .iter()
+        .map(|(name, ty)| make_example_value(ty, name))
+        .collect();
+    let example_object_fields: Vec<String> = args_meta
+        .iter()
+        .map(|(name, ty)| format!("\"{}\": {}", name, make_example_value(ty, name)))
+        .collect();
+    vec![
+        format!(
+            "edamame_cli rpc {} '[{}]'",
+            method,
+            example_values.join(", ")
+        ),
+        format!(
+            "edamame_cli rpc {} '[{}]' --pretty",
+            method,
+            example_values.join(", ")
+        ),
+        format!(
+            "edamame_cli rpc {} '{{{}}}'",
+            method,
+            example_object_fields.join(", ")
+        ),
+        format!(
+            "edamame_cli rpc {} '{{{}}}' --pretty",
+            method,
+            example_object_fields.join(", ")
+        ),
+    ]
+}
+
+const ARGUMENT_FORM_NOTES: [&str; 3] = [
+    "You can pass arguments as a JSON array of values or a single JSON object.",
+    "In array form, each element must be a valid JSON literal of the expected type.",
+    "In object form, use the exact argument names shown above as keys.",
+];
+
 fn print_method_help_with_meta(method: &str, return_type: &str, args_meta: &[(String, String)]) {
     let _ = write_stdout(&format!("Method: {}", method));
     let _ = write_stdout(&format!("Return type: {}", return_type));
+    let _ = write_stdout(&format!("Class: {}", classify::describe_class(method)));
     if !args_meta.is_empty() {
         let _ = write_stdout("Arguments:");
         for (name, arg_type) in args_meta {
@@ -278,64 +920,31 @@ fn print_method_help_with_meta(method: &str, return_type: &str, args_meta: &[(St
     }
 
     let _ = write_stdout("\nUsage examples:");
-    if !args_meta.is_empty() {
-        let example_values: Vec<String> = args_meta
-            .iter()
-            .map(|(name, ty)| make_example_value(ty, name))
-            .collect();
-        let _ = write_stdout(&format!(
-            "  edamame_cli rpc {} '[{}]'",
-            method,
-            example_values.join(", ")
-        ));
-        let _ = write_stdout(&format!(
-            "  edamame_cli rpc {} '[{}]' --pretty",
-            method,
-            example_values.join(", ")
-        ));
-
-        let example_object_fields: Vec<String> = args_meta
-            .iter()
-            .map(|(name, ty)| format!("\"{}\": {}", name, make_example_value(ty, name)))
-            .collect();
-        let _ = write_stdout(&format!(
-            "  edamame_cli rpc {} '{{{}}}'",
-            method,
-            example_object_fields.join(", ")
-        ));
-        let _ = write_stdout(&format!(
-            "  edamame_cli rpc {} '{{{}}}' --pretty",
-            method,
-            example_object_fields.join(", ")
-        ));
+    for example in usage_examples(method, args_meta) {
+        let _ = write_stdout(&format!("  {}", example));
+    }
 
+    if !args_meta.is_empty() {
         let _ = write_stdout("\nParameter mapping (array form):");
         for (i, (name, arg_type)) in args_meta.iter().enumerate() {
             let _ = write_stdout(&format!("  [{}] -> {} ({})", i, name, arg_type));
         }
 
         let _ = write_stdout("\nNotes:");
-        let _ = write_stdout(
-            "  - You can pass arguments as a JSON array of values or a single JSON object.",
-        );
-        let _ = write_stdout(
-            "  - In array form, each element must be a valid JSON literal of the expected type.",
-        );
-        let _ =
-            write_stdout("  - In object form, use the exact argument names shown above as keys.");
-    } else {
-        let _ = write_stdout(&format!("  edamame_cli rpc {}", method));
-        let _ = write_stdout(&format!("  edamame_cli rpc {} --pretty", method));
+        for note in ARGUMENT_FORM_NOTES {
+            let _ = write_stdout(&format!("  - {}", note));
+        }
     }
 }
 
 fn fetch_method_meta(method: &str) -> Result<(String, Vec<(String, String)>), String> {
+    let active = profile::active();
     match rpc_get_api_info(
         method.to_string(),
-        &EDAMAME_CA_PEM,
-        &EDAMAME_CLIENT_PEM,
-        &EDAMAME_CLIENT_KEY,
-        &EDAMAME_TARGET,
+        &active.ca_pem,
+        &active.client_pem,
+        &active.client_key,
+        &active.target,
     ) {
         Ok(Some(api_info)) => Ok((
             api_info.return_type,
@@ -406,175 +1015,73 @@ fn best_suggestion(input: &str, candidates: &[String]) -> Option<String> {
     }
 }
 
-fn handle_rpc(method: String, json_args_array: String, pretty: bool, verbose: bool) -> i32 {
+fn handle_rpc(
+    method: String,
+    json_args_array: String,
+    pretty: bool,
+    dry_run: bool,
+    verbose: bool,
+) -> i32 {
     initialize_core(verbose);
 
-    // Parse the user-supplied JSON args into the single JSON-object string
-    // the daemon expects on the wire. Supports three input shapes:
-    //
-    //   * `[]` or omitted        -> zero-argument call (no object sent)
-    //   * `{"k": v, ...}`        -> passed through verbatim
-    //   * `[v1, v2, ...]`        -> positional; mapped to the named-arg
-    //                               object via a `get_api_info` lookup against
-    //                               the daemon (the daemon is the source of
-    //                               truth for arg names).
-    //
-    // The wire dispatch goes through `rpc_call_remote`, which forwards
-    // `(method, args)` directly to the daemon's gRPC `execute` endpoint
-    // without consulting the CLI's local RPC registry. This means
-    // `edamame_cli` does NOT have to be rebuilt every time a new RPC
-    // method is added to `edamame_core` -- the daemon dispatches by name,
-    // not by local stub.
-    let args_object_json: Option<String> =
-        match serde_json::from_str::<serde_json::Value>(&json_args_array) {
-            Ok(serde_json::Value::Array(values)) => {
-                if values.is_empty() {
-                    None
-                } else {
-                    match fetch_method_meta(&method) {
-                        Ok((_ret, args_meta)) => {
-                            if values.len() != args_meta.len() {
-                                eprintln!(
-                                    ">>>> Argument count mismatch for {}: provided {}, expected {}",
-                                    method,
-                                    values.len(),
-                                    args_meta.len()
-                                );
-                                print_method_help_from_core(&method);
-                                return ERROR_CODE_PARAM;
-                            }
-                            let mut map = serde_json::Map::with_capacity(args_meta.len());
-                            for ((name, _ty), value) in args_meta.iter().zip(values.into_iter()) {
-                                map.insert(name.clone(), value);
-                            }
-                            match serde_json::to_string(&serde_json::Value::Object(map)) {
-                                Ok(s) => Some(s),
-                                Err(e) => {
-                                    eprintln!(
-                                        ">>>> Error serializing positional arguments: {:?}",
-                                        e
-                                    );
-                                    return ERROR_CODE_PARAM;
-                                }
-                            }
-                        }
-                        Err(e) => {
-                            eprintln!("{}", e);
-                            eprintln!(
-                                ">>>> Cannot map positional arguments without API metadata. \
-                             If the daemon supports this method but did not return metadata, \
-                             try the object form: edamame_cli rpc {} '{{\"arg1\": value, ...}}'",
-                                method
-                            );
-                            return ERROR_CODE_PARAM;
-                        }
-                    }
-                }
-            }
-            Ok(serde_json::Value::Object(map)) => {
-                // Object form: pass through verbatim. We still consult the daemon
-                // for a friendlier missing/unknown-fields diagnostic when the
-                // daemon knows the method; if it doesn't (or metadata lookup
-                // fails for any other reason), we forward the object as-is and
-                // let the daemon return the authoritative error.
-                if let Ok((_ret, args_meta)) = fetch_method_meta(&method) {
-                    let expected_names: Vec<String> =
-                        args_meta.iter().map(|(n, _)| n.clone()).collect();
-                    let provided_names: Vec<String> = map.keys().cloned().collect();
-                    let missing: Vec<String> = expected_names
-                        .iter()
-                        .filter(|n| !map.contains_key(*n))
-                        .cloned()
-                        .collect();
-                    let unknown: Vec<String> = provided_names
-                        .iter()
-                        .filter(|n| !expected_names.contains(*n))
-                        .cloned()
-                        .collect();
-                    if !missing.is_empty() || !unknown.is_empty() {
-                        if !missing.is_empty() {
-                            for m in &missing {
-                                eprintln!(
-                                    ">>>> Missing field '{}' in provided JSON object for method {}",
-                                    m, method
-                                );
-                            }
-                        }
-                        if !unknown.is_empty() {
-                            eprintln!("Unknown fields present: {}", unknown.join(", "));
-                            for u in &unknown {
-                                if let Some(sugg) = best_suggestion(u, &expected_names) {
-                                    eprintln!(
-                                        "     '{}' is not expected. Did you mean '{}' ?",
-                                        u, sugg
-                                    );
-                                }
-                            }
-                        }
-                        print_method_help_from_core(&method);
-                        return ERROR_CODE_PARAM;
-                    }
-                }
-                match serde_json::to_string(&serde_json::Value::Object(map)) {
-                    Ok(s) => Some(s),
-                    Err(e) => {
-                        eprintln!(">>>> Error serializing object arguments: {:?}", e);
-                        return ERROR_CODE_PARAM;
-                    }
-                }
-            }
-            Ok(_) => {
-                eprintln!(">>>> Error parsing JSON arguments: expected a JSON array or object");
-                print_method_help_from_core(&method);
-                return ERROR_CODE_PARAM;
-            }
-            Err(e) => {
-                eprintln!(">>>> Error parsing JSON arguments: {:?}", e);
-                print_method_help_from_core(&method);
-                return ERROR_CODE_PARAM;
-            }
+    if dry_run {
+        let plan = args::plan_args(&method, &json_args_array);
+        let (report, code) = dispatch::dry_run(
+            &method,
+            plan.as_ref()
+                .map_err(|(error, code)| (error.as_str(), *code)),
+        );
+        let text = if pretty {
+            serde_json::to_string_pretty(&report).unwrap_or_default()
+        } else {
+            report.to_string()
         };
+        let _ = write_stdout(&text);
+        return code;
+    }
+
+    let args_object_json = match args::prepare_args(&method, &json_args_array) {
+        Ok(args_object_json) => args_object_json,
+        Err(code) => {
+            print_method_help_from_core(&method);
+            return code;
+        }
+    };
     let method_name_for_help = method.clone();
-    match rpc_call_remote(
-        &method,
-        args_object_json.as_deref(),
-        &EDAMAME_CA_PEM,
-        &EDAMAME_CLIENT_PEM,
-        &EDAMAME_CLIENT_KEY,
-        &EDAMAME_TARGET,
-    ) {
+    match dispatch::call(&method, args_object_json.as_deref()) {
         Ok(result) => {
-            let output = if pretty {
-                if let Ok(json_value) = serde_json::from_str::<serde_json::Value>(&result) {
-                    serde_json::to_string_pretty(&json_value).unwrap()
-                } else {
-                    format!("Result: {}", result)
-                }
+            let format = if pretty {
+                output::OutputFormat::Pretty
             } else {
-                format!("Result: {}", result)
+                output::OutputFormat::Json
             };
-            if let Err(e) = write_stdout(&output) {
+            if let Err(e) = write_stdout(&output::render_result(&result, format)) {
                 eprintln!(">>>> Error writing to stdout: {}", e);
                 return ERROR_CODE_SERVER_ERROR;
             }
         }
-        Err(e) => {
-            eprintln!(">>>> Error calling RPC method: {:?}", e);
+        Err(e) if e.is_daemon_error() => {
+            eprintln!(">>>> Error calling RPC method: {}", e);
             print_method_help_from_core(&method_name_for_help);
-            return ERROR_CODE_SERVER_ERROR;
+            return e.code;
+        }
+        Err(e) => {
+            eprintln!(">>>> {}", e);
+            return e.code;
         }
     }
     0
 }
 
-fn handle_list_methods(pretty: bool, verbose: bool) -> i32 {
+fn handle_list_methods(pretty: bool, grouped: bool, verbose: bool) -> i32 {
     initialize_core(verbose);
 
+    let active = profile::active();
     let mut methods = match rpc_get_api_methods(
-        &EDAMAME_CA_PEM,
-        &EDAMAME_CLIENT_PEM,
-        &EDAMAME_CLIENT_KEY,
-        &EDAMAME_TARGET,
+        &active.ca_pem,
+        &active.client_pem,
+        &active.client_key,
+        &active.target,
     ) {
         Ok(methods) => methods,
         Err(e) => {
@@ -586,7 +1093,40 @@ fn handle_list_methods(pretty: bool, verbose: bool) -> i32 {
     // Sort methods alphabetically
     methods.sort();
 
-    if pretty {
+    // Keep the completion cache in sync with what we just listed
+    let mut catalog = catalog::Catalog::load();
+    catalog.set_methods(methods.clone());
+    catalog.save();
+
+    if grouped {
+        let mut groups: std::collections::BTreeMap<&str, Vec<&String>> =
+            std::collections::BTreeMap::new();
+        for method in &methods {
+            groups
+                .entry(classify::verb_of(method))
+                .or_default()
+                .push(method);
+        }
+        let mutating_count = methods
+            .iter()
+            .filter(|m| classify::method_class(m).is_mutating())
+            .count();
+        let _ = write_stdout(&format!(
+            "Available RPC methods by verb ({} read-only, {} state-changing):",
+            methods.len() - mutating_count,
+            mutating_count
+        ));
+        for (verb, group) in groups {
+            let _ = write_stdout(&format!("{}_* ({}):", verb, group.len()));
+            for method in group {
+                let _ = write_stdout(&format!(
+                    "  {:<50} {}",
+                    method,
+                    classify::describe_class(method)
+                ));
+            }
+        }
+    } else if pretty {
         let _ = write_stdout("Available RPC methods:");
         for method in methods {
             let _ = write_stdout(&format!("  {}", method));
@@ -600,12 +1140,13 @@ fn handle_list_methods(pretty: bool, verbose: bool) -> i32 {
 fn handle_get_method_info(method: String, verbose: bool) -> i32 {
     initialize_core(verbose);
 
+    let active = profile::active();
     let info = match rpc_get_api_info(
         method.clone(),
-        &EDAMAME_CA_PEM,
-        &EDAMAME_CLIENT_PEM,
-        &EDAMAME_CLIENT_KEY,
-        &EDAMAME_TARGET,
+        &active.ca_pem,
+        &active.client_pem,
+        &active.client_key,
+        &active.target,
     ) {
         Ok(info) => info,
         Err(e) => {
@@ -622,6 +1163,10 @@ fn handle_get_method_info(method: String, verbose: bool) -> i32 {
                 .map(|a| (a.name.clone(), a.arg_type.clone()))
                 .collect();
             print_method_help_with_meta(&method, &api_info.return_type, &args_meta);
+
+            let mut catalog = catalog::Catalog::load();
+            catalog.set_meta(&method, (api_info.return_type.clone(), args_meta));
+            catalog.save();
         }
         None => {
             println!("No information available for method: {}", method);
@@ -635,11 +1180,12 @@ fn handle_list_method_infos(verbose: bool) -> i32 {
     initialize_core(verbose);
 
     // Get the list of all methods
+    let active = profile::active();
     let methods = match rpc_get_api_methods(
-        &EDAMAME_CA_PEM,
-        &EDAMAME_CLIENT_PEM,
-        &EDAMAME_CLIENT_KEY,
-        &EDAMAME_TARGET,
+        &active.ca_pem,
+        &active.client_pem,
+        &active.client_key,
+        &active.target,
     ) {
         Ok(methods) => methods,
         Err(e) => {
@@ -648,14 +1194,15 @@ fn handle_list_method_infos(verbose: bool) -> i32 {
         }
     };
 
+    let active = profile::active();
     // Iterate over the list of methods and get the info for each one
     for method in methods {
         let info = match rpc_get_api_info(
             method.clone(),
-            &EDAMAME_CA_PEM,
-            &EDAMAME_CLIENT_PEM,
-            &EDAMAME_CLIENT_KEY,
-            &EDAMAME_TARGET,
+            &active.ca_pem,
+            &active.client_pem,
+            &active.client_key,
+            &active.target,
         ) {
             Ok(info) => info,
             Err(e) => {
@@ -669,113 +1216,6 @@ fn handle_list_method_infos(verbose: bool) -> i32 {
     0
 }
 
-fn interactive_mode(verbose: bool) {
-    initialize_core(verbose);
-
-    println!("Entering interactive mode. Type 'exit' to leave.");
-    println!("Usage: <method> [JSON args object or array]");
-    println!("  e.g.  get_score");
-    println!("        get_score {{\"complete_only\": false}}");
-    let stdin = io::stdin();
-    let mut reader = stdin.lock();
-    let mut line = String::new();
-
-    loop {
-        line.clear();
-        print!("> ");
-        io::stdout().flush().unwrap();
-        reader.read_line(&mut line).unwrap();
-
-        let trimmed = line.trim();
-        if trimmed == "exit" {
-            break;
-        }
-        if trimmed.is_empty() {
-            continue;
-        }
-
-        // Split off the method name; treat the rest of the line as the JSON
-        // argument blob (object or array). Anything that is not a JSON object
-        // or array is rejected; positional whitespace-split tokens are not
-        // supported here because the wire format the daemon dispatcher
-        // expects is a single named-arg JSON object.
-        let (command, args_blob) = match trimmed.split_once(char::is_whitespace) {
-            Some((cmd, rest)) => (cmd.to_string(), rest.trim().to_string()),
-            None => (trimmed.to_string(), String::new()),
-        };
-
-        let args_object_json: Option<String> = if args_blob.is_empty() {
-            None
-        } else {
-            match serde_json::from_str::<serde_json::Value>(&args_blob) {
-                Ok(serde_json::Value::Object(_)) => Some(args_blob.clone()),
-                Ok(serde_json::Value::Array(values)) => {
-                    if values.is_empty() {
-                        None
-                    } else {
-                        match fetch_method_meta(&command) {
-                            Ok((_ret, args_meta)) => {
-                                if values.len() != args_meta.len() {
-                                    eprintln!(
-                                        ">>>> Argument count mismatch for {}: provided {}, expected {}",
-                                        command,
-                                        values.len(),
-                                        args_meta.len()
-                                    );
-                                    continue;
-                                }
-                                let mut map = serde_json::Map::with_capacity(args_meta.len());
-                                for ((name, _ty), value) in args_meta.iter().zip(values.into_iter())
-                                {
-                                    map.insert(name.clone(), value);
-                                }
-                                match serde_json::to_string(&serde_json::Value::Object(map)) {
-                                    Ok(s) => Some(s),
-                                    Err(e) => {
-                                        eprintln!(
-                                            ">>>> Error serializing positional arguments: {:?}",
-                                            e
-                                        );
-                                        continue;
-                                    }
-                                }
-                            }
-                            Err(e) => {
-                                eprintln!("{}", e);
-                                continue;
-                            }
-                        }
-                    }
-                }
-                Ok(_) => {
-                    eprintln!(
-                        ">>>> Expected a JSON object {{...}} or array [...] after the method name"
-                    );
-                    continue;
-                }
-                Err(e) => {
-                    eprintln!(">>>> Error parsing JSON arguments: {:?}", e);
-                    continue;
-                }
-            }
-        };
-
-        match rpc_call_remote(
-            &command,
-            args_object_json.as_deref(),
-            &EDAMAME_CA_PEM,
-            &EDAMAME_CLIENT_PEM,
-            &EDAMAME_CLIENT_KEY,
-            &EDAMAME_TARGET,
-        ) {
-            Ok(result) => {
-                let _ = write_stdout(&format!("Result: {}", result));
-            }
-            Err(e) => eprintln!(">>>> Error calling RPC method: {:?}", e),
-        }
-    }
-}
-
 pub fn main() {
     run();
 }
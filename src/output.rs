@@ -0,0 +1,162 @@
+use crate::redact::stdout_result;
+use serde_json::Value;
+use std::fmt;
+use std::str::FromStr;
+
+/// Cells longer than this are truncated in table output.
+const MAX_CELL_WIDTH: usize = 60;
+
+/// How RPC results are printed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// The raw response on one line, prefixed with `Result: `.
+    Json,
+    /// Indented JSON.
+    Pretty,
+    /// Arrays of objects as columns, objects as key/value rows.
+    Table,
+}
+
+impl OutputFormat {
+    pub const NAMES: [&'static str; 3] = ["json", "pretty", "table"];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            OutputFormat::Json => "json",
+            OutputFormat::Pretty => "pretty",
+            OutputFormat::Table => "table",
+        }
+    }
+}
+
+impl fmt::Display for OutputFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.label())
+    }
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "json" => Ok(OutputFormat::Json),
+            "pretty" => Ok(OutputFormat::Pretty),
+            "table" => Ok(OutputFormat::Table),
+            _ => Err(format!(
+                "Unknown output format '{}', expected one of: {}",
+                s,
+                Self::NAMES.join(", ")
+            )),
+        }
+    }
+}
+
+/// Parse a raw RPC result. Results that are not JSON are kept as a string.
+pub fn result_value(result: &str) -> Value {
+    serde_json::from_str(result).unwrap_or_else(|_| Value::String(result.to_string()))
+}
+
+/// Render a raw RPC response. Responses that are not JSON are printed as-is
+/// in every format. With `--redact`, sensitive fields are redacted first.
+pub fn render_result(result: &str, format: OutputFormat) -> String {
+    let result = &stdout_result(result);
+    let parsed = serde_json::from_str::<Value>(result);
+    match (format, parsed) {
+        (OutputFormat::Pretty, Ok(value)) => {
+            serde_json::to_string_pretty(&value).unwrap_or_else(|_| result.to_string())
+        }
+        (OutputFormat::Table, Ok(value)) => render_table(&value),
+        _ => format!("Result: {}", result),
+    }
+}
+
+fn render_table(value: &Value) -> String {
+    match value {
+        Value::Array(rows) if !rows.is_empty() && rows.iter().all(Value::is_object) => {
+            // Columns in order of first appearance across all rows
+            let mut columns: Vec<&str> = Vec::new();
+            for row in rows {
+                for key in row.as_object().into_iter().flat_map(|o| o.keys()) {
+                    if !columns.contains(&key.as_str()) {
+                        columns.push(key);
+                    }
+                }
+            }
+            let header: Vec<String> = columns.iter().map(|c| c.to_string()).collect();
+            let body: Vec<Vec<String>> = rows
+                .iter()
+                .map(|row| {
+                    columns
+                        .iter()
+                        .map(|c| row.get(*c).map(cell).unwrap_or_default())
+                        .collect()
+                })
+                .collect();
+            format_rows(Some(header), body)
+        }
+        Value::Array(items) if !items.is_empty() => {
+            format_rows(None, items.iter().map(|v| vec![cell(v)]).collect())
+        }
+        Value::Object(map) if !map.is_empty() => format_rows(
+            Some(vec!["key".to_string(), "value".to_string()]),
+            map.iter().map(|(k, v)| vec![k.clone(), cell(v)]).collect(),
+        ),
+        Value::Array(_) | Value::Object(_) => "(empty)".to_string(),
+        scalar => cell(scalar),
+    }
+}
+
+/// Text of a table cell: strings without quotes, anything else as compact
+/// JSON, truncated to `MAX_CELL_WIDTH` characters.
+fn cell(value: &Value) -> String {
+    let text = match value {
+        Value::String(s) => s.clone(),
+        Value::Null => String::new(),
+        other => other.to_string(),
+    };
+    let text = text.replace(['\n', '\t'], " ");
+    if text.chars().count() > MAX_CELL_WIDTH {
+        let truncated: String = text.chars().take(MAX_CELL_WIDTH - 1).collect();
+        format!("{}…", truncated)
+    } else {
+        text
+    }
+}
+
+fn format_rows(header: Option<Vec<String>>, body: Vec<Vec<String>>) -> String {
+    let column_count = header
+        .as_ref()
+        .map(Vec::len)
+        .unwrap_or_else(|| body.first().map(Vec::len).unwrap_or(0));
+    let mut widths = vec![0; column_count];
+    for row in header.iter().chain(body.iter()) {
+        for (i, text) in row.iter().enumerate() {
+            widths[i] = widths[i].max(text.chars().count());
+        }
+    }
+
+    let format_row = |row: &[String]| {
+        row.iter()
+            .zip(&widths)
+            .map(|(text, width)| format!("{:<width$}", text, width = *width))
+            .collect::<Vec<_>>()
+            .join("  ")
+            .trim_end()
+            .to_string()
+    };
+
+    let mut lines = Vec::with_capacity(body.len() + 2);
+    if let Some(header) = &header {
+        lines.push(format_row(header));
+        lines.push(
+            widths
+                .iter()
+                .map(|w| "-".repeat(*w))
+                .collect::<Vec<_>>()
+                .join("  "),
+        );
+    }
+    lines.extend(body.iter().map(|row| format_row(row)));
+    lines.join("\n")
+}
@@ -0,0 +1,340 @@
+use crate::classify::{method_class, verb_class, MethodClass};
+use crate::config::{system_config_dir, user_config_file};
+use crate::dispatch::CallError;
+use crate::ERROR_CODE_POLICY;
+use glob::Pattern;
+use lazy_static::lazy_static;
+use serde_json::Value;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Name of the policy file, in the system-wide and in the per-user
+/// configuration directory:
+///
+/// ```json
+/// {
+///   "read_only": false,
+///   "allow": ["get_*", "list_*", "set_demo_mode"],
+///   "deny": ["*_history"],
+///   "deny_classes": ["destructive"]
+/// }
+/// ```
+///
+/// Every key is optional. When `allow` is given, a method has to match one
+/// of its globs.
+pub const POLICY_FILE: &str = "policy.json";
+
+const POLICY_KEYS: [&str; 4] = ["read_only", "allow", "deny", "deny_classes"];
+
+/// Set by `--read-only`.
+static READ_ONLY: AtomicBool = AtomicBool::new(false);
+
+lazy_static! {
+    static ref POLICIES: Vec<Rules> = load_policies();
+}
+
+pub fn set_read_only(read_only: bool) {
+    READ_ONLY.store(read_only, Ordering::SeqCst);
+}
+
+/// The restrictions of one policy source.
+#[derive(Default)]
+struct Rules {
+    /// Where the rules come from, for error messages.
+    source: String,
+    read_only: bool,
+    allow: Option<Vec<Pattern>>,
+    deny: Vec<Pattern>,
+    deny_classes: Vec<MethodClass>,
+    /// Set when the file could not be used; every call is then blocked
+    /// rather than running without the policy.
+    invalid: Option<String>,
+}
+
+impl Rules {
+    /// Why the rules block `method`, of class `class`, if they do.
+    fn violation(&self, method: &str, class: MethodClass) -> Option<String> {
+        if let Some(e) = &self.invalid {
+            return Some(format!(
+                "{} is invalid ({}), so no method may be called",
+                self.source, e
+            ));
+        }
+        // The user's classification overrides cannot loosen a policy: the
+        // class implied by the verb counts too
+        let classes = [class, verb_class(method).0];
+        if let Some(class) = classes.iter().find(|c| self.read_only && c.is_mutating()) {
+            return Some(format!(
+                "{} is {} and {} only allows read-only methods",
+                method, class, self.source
+            ));
+        }
+        if let Some(class) = classes.iter().find(|c| self.deny_classes.contains(c)) {
+            return Some(format!(
+                "{} is {}, a class denied by {}",
+                method, class, self.source
+            ));
+        }
+        if let Some(pattern) = self.deny.iter().find(|p| p.matches(method)) {
+            return Some(format!(
+                "{} matches '{}' in the deny list of {}",
+                method, pattern, self.source
+            ));
+        }
+        if let Some(allow) = &self.allow {
+            if !allow.iter().any(|p| p.matches(method)) {
+                return Some(format!(
+                    "{} is not in the allow list of {}",
+                    method, self.source
+                ));
+            }
+        }
+        None
+    }
+}
+
+fn patterns(value: &Value, key: &str) -> Result<Option<Vec<Pattern>>, String> {
+    match value.get(key) {
+        None => Ok(None),
+        Some(Value::Array(items)) => items
+            .iter()
+            .map(|item| {
+                let glob = item
+                    .as_str()
+                    .ok_or_else(|| format!("\"{}\" must be a list of strings", key))?;
+                Pattern::new(glob).map_err(|e| format!("invalid pattern '{}': {}", glob, e))
+            })
+            .collect::<Result<_, _>>()
+            .map(Some),
+        Some(_) => Err(format!("\"{}\" must be a list of strings", key)),
+    }
+}
+
+fn parse_rules(value: &Value) -> Result<Rules, String> {
+    let Some(map) = value.as_object() else {
+        return Err("expected a JSON object".to_string());
+    };
+    if let Some(key) = map.keys().find(|key| !POLICY_KEYS.contains(&key.as_str())) {
+        return Err(format!("unknown key '{}'", key));
+    }
+    let read_only = match value.get("read_only") {
+        None => false,
+        Some(Value::Bool(read_only)) => *read_only,
+        Some(_) => return Err("\"read_only\" must be true or false".to_string()),
+    };
+    let deny_classes = match value.get("deny_classes") {
+        None => Vec::new(),
+        Some(Value::Array(items)) => items
+            .iter()
+            .map(|item| {
+                item.as_str()
+                    .ok_or_else(|| "\"deny_classes\" must be a list of strings".to_string())
+                    .and_then(MethodClass::from_str)
+            })
+            .collect::<Result<_, _>>()?,
+        Some(_) => return Err("\"deny_classes\" must be a list of strings".to_string()),
+    };
+    Ok(Rules {
+        read_only,
+        allow: patterns(value, "allow")?,
+        deny: patterns(value, "deny")?.unwrap_or_default(),
+        deny_classes,
+        ..Rules::default()
+    })
+}
+
+/// Rules of a policy file, or `None` when there is no file. Unlike other
+/// configuration files, a policy that cannot be read is not ignored.
+fn load_file(path: &Path) -> Option<Rules> {
+    let source = path.display().to_string();
+    let parsed = match fs::read_to_string(path) {
+        Err(e) if e.kind() == ErrorKind::NotFound => return None,
+        Err(e) => Err(e.to_string()),
+        Ok(contents) => serde_json::from_str::<Value>(&contents)
+            .map_err(|e| e.to_string())
+            .and_then(|value| parse_rules(&value)),
+    };
+    Some(match parsed {
+        Ok(rules) => Rules { source, ..rules },
+        Err(e) => Rules {
+            source,
+            invalid: Some(e),
+            ..Rules::default()
+        },
+    })
+}
+
+/// The system-wide policy first, then the user's. Each one can only restrict
+/// what the others allow, so user settings never loosen the system policy.
+fn load_policies() -> Vec<Rules> {
+    let mut policies: Vec<Rules> = load_file(&system_config_dir().join(POLICY_FILE))
+        .into_iter()
+        .collect();
+    if let Some(path) = user_config_file(POLICY_FILE) {
+        policies.extend(load_file(&path));
+    }
+    policies
+}
+
+/// The first of `policies` that blocks `method`, and why.
+fn first_violation<'a>(
+    policies: impl IntoIterator<Item = &'a Rules>,
+    method: &str,
+    class: MethodClass,
+) -> Option<String> {
+    policies
+        .into_iter()
+        .find_map(|rules| rules.violation(method, class))
+}
+
+/// Refuse calls to methods that a policy, or `--read-only`, does not allow.
+pub fn check(method: &str) -> Result<(), CallError> {
+    let flag = Rules {
+        source: "--read-only".to_string(),
+        read_only: READ_ONLY.load(Ordering::SeqCst),
+        ..Rules::default()
+    };
+    match first_violation(
+        POLICIES.iter().chain(std::iter::once(&flag)),
+        method,
+        method_class(method),
+    ) {
+        Some(reason) => Err(CallError {
+            code: ERROR_CODE_POLICY,
+            message: format!("Blocked by policy: {}", reason),
+        }),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn rules(source: &str, value: Value) -> Rules {
+        Rules {
+            source: source.to_string(),
+            ..parse_rules(&value).unwrap()
+        }
+    }
+
+    fn blocked(policies: &[Rules], method: &str, class: MethodClass) -> Option<String> {
+        first_violation(policies, method, class)
+    }
+
+    #[test]
+    fn parses_rules() {
+        let parsed = parse_rules(&json!({
+            "read_only": true,
+            "allow": ["get_*"],
+            "deny": ["*_history"],
+            "deny_classes": ["destructive", "long-running"]
+        }))
+        .unwrap();
+        assert!(parsed.read_only);
+        assert_eq!(parsed.allow.unwrap().len(), 1);
+        assert_eq!(parsed.deny.len(), 1);
+        assert_eq!(
+            parsed.deny_classes,
+            [MethodClass::Destructive, MethodClass::LongRunning]
+        );
+        let empty = parse_rules(&json!({})).unwrap();
+        assert!(!empty.read_only && empty.allow.is_none() && empty.deny.is_empty());
+    }
+
+    #[test]
+    fn rejects_invalid_rules() {
+        for (value, error) in [
+            (json!([]), "expected a JSON object"),
+            (json!({"alow": []}), "unknown key 'alow'"),
+            (
+                json!({"read_only": "yes"}),
+                "\"read_only\" must be true or false",
+            ),
+            (
+                json!({"deny": "get_*"}),
+                "\"deny\" must be a list of strings",
+            ),
+            (json!({"allow": [1]}), "\"allow\" must be a list of strings"),
+            (json!({"deny": ["[a"]}), "invalid pattern '[a'"),
+        ] {
+            let e = parse_rules(&value).err().unwrap();
+            assert!(e.starts_with(error), "{}: {}", value, e);
+        }
+        assert!(parse_rules(&json!({"deny_classes": ["harmless"]})).is_err());
+    }
+
+    #[test]
+    fn applies_allow_deny_and_classes() {
+        let policy = [rules(
+            "policy.json",
+            json!({"allow": ["get_*", "set_demo_mode"], "deny": ["*_history"], "deny_classes": ["destructive"]}),
+        )];
+        assert_eq!(blocked(&policy, "get_score", MethodClass::ReadOnly), None);
+        assert_eq!(
+            blocked(&policy, "set_demo_mode", MethodClass::Mutating),
+            None
+        );
+        assert_eq!(
+            blocked(&policy, "set_pin", MethodClass::Mutating).unwrap(),
+            "set_pin is not in the allow list of policy.json"
+        );
+        assert_eq!(
+            blocked(&policy, "get_score_history", MethodClass::ReadOnly).unwrap(),
+            "get_score_history matches '*_history' in the deny list of policy.json"
+        );
+        // Denied classes win over the allow list
+        assert_eq!(
+            blocked(&policy, "get_secret", MethodClass::Destructive).unwrap(),
+            "get_secret is destructive, a class denied by policy.json"
+        );
+    }
+
+    #[test]
+    fn user_rules_only_restrict_the_system_ones() {
+        let system = rules("/etc/policy.json", json!({"deny": ["set_*"]}));
+        let user = rules("~/policy.json", json!({"allow": ["set_*", "get_*"]}));
+        let policies = [system, user];
+        assert!(blocked(&policies, "set_demo_mode", MethodClass::Mutating)
+            .unwrap()
+            .contains("/etc/policy.json"));
+        assert!(blocked(&policies, "remediate", MethodClass::Mutating)
+            .unwrap()
+            .contains("~/policy.json"));
+        assert_eq!(blocked(&policies, "get_score", MethodClass::ReadOnly), None);
+    }
+
+    #[test]
+    fn invalid_files_block_every_call() {
+        let path = std::env::temp_dir().join(format!("policy-test-{}.json", std::process::id()));
+        fs::write(&path, "{\"deny\": [").unwrap();
+        let invalid = load_file(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert!(load_file(&path).is_none());
+        let reason = blocked(&[invalid], "get_score", MethodClass::ReadOnly).unwrap();
+        assert!(reason.contains("is invalid"), "{}", reason);
+    }
+
+    #[test]
+    fn read_only_ignores_loosening_overrides() {
+        let flag = Rules {
+            source: "--read-only".to_string(),
+            read_only: true,
+            ..Rules::default()
+        };
+        let policies = [flag];
+        assert_eq!(blocked(&policies, "get_score", MethodClass::ReadOnly), None);
+        // An override saying set_demo_mode is read-only does not get past
+        // the class of its verb
+        assert_eq!(
+            blocked(&policies, "set_demo_mode", MethodClass::ReadOnly).unwrap(),
+            "set_demo_mode is mutating and --read-only only allows read-only methods"
+        );
+        // Overrides can still make a method stricter
+        assert!(blocked(&policies, "get_secret", MethodClass::Mutating).is_some());
+    }
+}
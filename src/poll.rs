@@ -0,0 +1,105 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+/// Set by the Ctrl-C handler of polling commands.
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// Granularity at which waits notice Ctrl-C.
+const SLEEP_SLICE: Duration = Duration::from_millis(100);
+
+/// Parse a duration such as `500ms`, `5s`, `2m` or `1h`. A bare number is
+/// taken as seconds. Zero is rejected: polling loops would spin.
+pub fn parse_duration(text: &str) -> Result<Duration, String> {
+    let text = text.trim();
+    let split = text
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(text.len());
+    let (number, unit) = text.split_at(split);
+    let number: f64 = number
+        .parse()
+        .map_err(|_| format!("invalid duration '{}', expected e.g. 500ms, 5s or 2m", text))?;
+    let seconds = match unit.trim() {
+        "ms" => number / 1000.0,
+        "" | "s" => number,
+        "m" => number * 60.0,
+        "h" => number * 3600.0,
+        unit => {
+            return Err(format!(
+                "unknown duration unit '{}', expected ms, s, m or h",
+                unit
+            ))
+        }
+    };
+    match Duration::try_from_secs_f64(seconds) {
+        Ok(duration) if duration.is_zero() => {
+            Err(format!("invalid duration '{}', expected more than 0", text))
+        }
+        Ok(duration) => Ok(duration),
+        Err(_) => Err(format!("duration '{}' is out of range", text)),
+    }
+}
+
+/// Catch Ctrl-C so that a polling loop can stop cleanly. Without this the
+/// process dies on the spot, possibly with the terminal in the middle of a
+/// redraw.
+pub fn install_interrupt_handler() {
+    if let Err(e) = ctrlc::set_handler(|| INTERRUPTED.store(true, Ordering::SeqCst)) {
+        eprintln!(">>>> Error installing the Ctrl-C handler: {}", e);
+    }
+}
+
+pub fn interrupted() -> bool {
+    INTERRUPTED.load(Ordering::SeqCst)
+}
+
+/// Sleep until `deadline`, returning early (with `false`) on Ctrl-C.
+pub fn sleep_until(deadline: Instant) -> bool {
+    loop {
+        if interrupted() {
+            return false;
+        }
+        let now = Instant::now();
+        if now >= deadline {
+            return true;
+        }
+        sleep((deadline - now).min(SLEEP_SLICE));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_durations() {
+        for (text, expected) in [
+            ("500ms", Duration::from_millis(500)),
+            ("5", Duration::from_secs(5)),
+            ("5s", Duration::from_secs(5)),
+            (" 1.5s ", Duration::from_millis(1500)),
+            ("2m", Duration::from_secs(120)),
+            ("1h", Duration::from_secs(3600)),
+        ] {
+            assert_eq!(parse_duration(text), Ok(expected), "{}", text);
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_durations() {
+        for (text, message) in [
+            ("0", "expected more than 0"),
+            ("0s", "expected more than 0"),
+            ("0.0m", "expected more than 0"),
+            ("99999999999999999999h", "out of range"),
+            ("5d", "unknown duration unit 'd'"),
+            ("-5s", "invalid duration"),
+            ("1.2.3s", "invalid duration"),
+            ("abc", "invalid duration"),
+            ("", "invalid duration"),
+        ] {
+            let error = parse_duration(text).unwrap_err();
+            assert!(error.contains(message), "{}: {}", text, error);
+        }
+    }
+}
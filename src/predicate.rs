@@ -0,0 +1,491 @@
+use serde_json::Value;
+use std::cmp::Ordering;
+use std::fmt;
+
+/// A condition on an RPC result, as given to `wait-for --until` and
+/// `assert --expect`:
+///
+/// ```text
+/// stars >= 4
+/// status.is_ready && !status.error
+/// len(active_findings) == 0
+/// items[0].name == "ssh" || . == "ok"
+/// ```
+///
+/// Paths select fields of the result (`.` is the result itself) and are
+/// `null` when missing. Comparisons between values of different types are
+/// false, `==` and `!=` excepted. A value on its own is true unless it is
+/// `false`, `null`, `0`, or an empty string, array or object.
+#[derive(Debug, Clone)]
+pub struct Predicate {
+    source: String,
+    expr: Expr,
+}
+
+#[derive(Debug, Clone)]
+enum Expr {
+    Literal(Value),
+    Path(String),
+    Len(Box<Expr>),
+    Not(Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Compare(Box<Expr>, CompareOp, Box<Expr>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum CompareOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Literal(Value),
+    Ident(String),
+    Compare(CompareOp),
+    Not,
+    And,
+    Or,
+    LParen,
+    RParen,
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Literal(value) => write!(f, "{}", value),
+            Token::Ident(name) => f.write_str(name),
+            Token::Compare(op) => f.write_str(match op {
+                CompareOp::Eq => "==",
+                CompareOp::Ne => "!=",
+                CompareOp::Lt => "<",
+                CompareOp::Le => "<=",
+                CompareOp::Gt => ">",
+                CompareOp::Ge => ">=",
+            }),
+            Token::Not => f.write_str("!"),
+            Token::And => f.write_str("&&"),
+            Token::Or => f.write_str("||"),
+            Token::LParen => f.write_str("("),
+            Token::RParen => f.write_str(")"),
+        }
+    }
+}
+
+fn tokenize(source: &str) -> Result<Vec<Token>, String> {
+    let chars: Vec<char> = source.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let next = chars.get(i + 1).copied();
+        match c {
+            c if c.is_whitespace() => i += 1,
+            '(' => {
+                tokens.push(Token::LParen);
+                i += 1;
+            }
+            ')' => {
+                tokens.push(Token::RParen);
+                i += 1;
+            }
+            '&' if next == Some('&') => {
+                tokens.push(Token::And);
+                i += 2;
+            }
+            '|' if next == Some('|') => {
+                tokens.push(Token::Or);
+                i += 2;
+            }
+            '=' if next == Some('=') => {
+                tokens.push(Token::Compare(CompareOp::Eq));
+                i += 2;
+            }
+            '!' if next == Some('=') => {
+                tokens.push(Token::Compare(CompareOp::Ne));
+                i += 2;
+            }
+            '!' => {
+                tokens.push(Token::Not);
+                i += 1;
+            }
+            '<' | '>' => {
+                let op = match (c, next == Some('=')) {
+                    ('<', false) => CompareOp::Lt,
+                    ('<', true) => CompareOp::Le,
+                    ('>', false) => CompareOp::Gt,
+                    _ => CompareOp::Ge,
+                };
+                tokens.push(Token::Compare(op));
+                i += if next == Some('=') { 2 } else { 1 };
+            }
+            '"' | '\'' => {
+                // Strings follow JSON escaping; single quotes save shell quoting
+                let start = i;
+                i += 1;
+                while i < chars.len() && chars[i] != c {
+                    i += if chars[i] == '\\' { 2 } else { 1 };
+                }
+                if i >= chars.len() {
+                    return Err(format!("unterminated string at column {}", start + 1));
+                }
+                let body: String = chars[start + 1..i].iter().collect();
+                let text = serde_json::from_str::<String>(&format!("\"{}\"", body))
+                    .map_err(|e| format!("invalid string at column {}: {}", start + 1, e))?;
+                tokens.push(Token::Literal(Value::String(text)));
+                i += 1;
+            }
+            c if c.is_ascii_digit() || (c == '-' && next.is_some_and(|n| n.is_ascii_digit())) => {
+                let start = i;
+                i += 1;
+                while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '.') {
+                    i += 1;
+                }
+                let text: String = chars[start..i].iter().collect();
+                let number = serde_json::from_str::<Value>(&text)
+                    .ok()
+                    .filter(Value::is_number)
+                    .ok_or_else(|| format!("invalid number '{}'", text))?;
+                tokens.push(Token::Literal(number));
+            }
+            c if c.is_alphabetic() || c == '_' || c == '.' => {
+                let start = i;
+                while i < chars.len()
+                    && (chars[i].is_alphanumeric() || matches!(chars[i], '_' | '.' | '[' | ']'))
+                {
+                    i += 1;
+                }
+                let text: String = chars[start..i].iter().collect();
+                tokens.push(match text.as_str() {
+                    "true" => Token::Literal(Value::Bool(true)),
+                    "false" => Token::Literal(Value::Bool(false)),
+                    "null" => Token::Literal(Value::Null),
+                    _ => Token::Ident(text),
+                });
+            }
+            c => return Err(format!("unexpected '{}' at column {}", c, i + 1)),
+        }
+    }
+    Ok(tokens)
+}
+
+/// Recursive descent over `or := and ("||" and)*`, `and := comparison
+/// ("&&" comparison)*`, `comparison := unary (op unary)?`, `unary := "!"
+/// unary | operand`.
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn advance(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    fn expect(&mut self, expected: Token) -> Result<(), String> {
+        match self.advance() {
+            Some(token) if token == expected => Ok(()),
+            Some(token) => Err(format!("expected '{}', found '{}'", expected, token)),
+            None => Err(format!("expected '{}' at the end", expected)),
+        }
+    }
+
+    fn or(&mut self) -> Result<Expr, String> {
+        let mut left = self.and()?;
+        while self.peek() == Some(&Token::Or) {
+            self.advance();
+            left = Expr::Or(Box::new(left), Box::new(self.and()?));
+        }
+        Ok(left)
+    }
+
+    fn and(&mut self) -> Result<Expr, String> {
+        let mut left = self.comparison()?;
+        while self.peek() == Some(&Token::And) {
+            self.advance();
+            left = Expr::And(Box::new(left), Box::new(self.comparison()?));
+        }
+        Ok(left)
+    }
+
+    fn comparison(&mut self) -> Result<Expr, String> {
+        let left = self.unary()?;
+        if let Some(Token::Compare(op)) = self.peek().cloned() {
+            self.advance();
+            let right = self.unary()?;
+            return Ok(Expr::Compare(Box::new(left), op, Box::new(right)));
+        }
+        Ok(left)
+    }
+
+    fn unary(&mut self) -> Result<Expr, String> {
+        if self.peek() == Some(&Token::Not) {
+            self.advance();
+            return Ok(Expr::Not(Box::new(self.unary()?)));
+        }
+        self.operand()
+    }
+
+    fn operand(&mut self) -> Result<Expr, String> {
+        match self.advance() {
+            Some(Token::Literal(value)) => Ok(Expr::Literal(value)),
+            Some(Token::LParen) => {
+                let expr = self.or()?;
+                self.expect(Token::RParen)?;
+                Ok(expr)
+            }
+            Some(Token::Ident(name)) if self.peek() == Some(&Token::LParen) => {
+                self.advance();
+                let argument = self.or()?;
+                self.expect(Token::RParen)?;
+                match name.as_str() {
+                    "len" => Ok(Expr::Len(Box::new(argument))),
+                    _ => Err(format!("unknown function '{}', expected len", name)),
+                }
+            }
+            Some(Token::Ident(path)) => Ok(Expr::Path(path)),
+            Some(token) => Err(format!("expected a value, found '{}'", token)),
+            None => Err("expected a value at the end".to_string()),
+        }
+    }
+}
+
+/// Field of `value` at `path`: `.`-separated names and `[index]`
+/// subscripts. A leading `.` is optional and `.` alone is the value itself.
+pub fn lookup<'a>(value: &'a Value, path: &str) -> Option<&'a Value> {
+    let mut current = value;
+    let path = path.strip_prefix('.').unwrap_or(path);
+    for segment in path.split('.').filter(|s| !s.is_empty()) {
+        let (name, subscripts) = segment
+            .find('[')
+            .map(|i| segment.split_at(i))
+            .unwrap_or((segment, ""));
+        if !name.is_empty() {
+            current = current.get(name)?;
+        }
+        for index in subscripts.split(['[', ']']).filter(|s| !s.is_empty()) {
+            current = current.get(index.parse::<usize>().ok()?)?;
+        }
+    }
+    Some(current)
+}
+
+fn truthy(value: &Value) -> bool {
+    match value {
+        Value::Null => false,
+        Value::Bool(b) => *b,
+        Value::Number(n) => n.as_f64() != Some(0.0),
+        Value::String(s) => !s.is_empty(),
+        Value::Array(a) => !a.is_empty(),
+        Value::Object(o) => !o.is_empty(),
+    }
+}
+
+fn compare(left: &Value, op: CompareOp, right: &Value) -> bool {
+    let ordering = match (left, right) {
+        (Value::Number(l), Value::Number(r)) => l.as_f64().partial_cmp(&r.as_f64()),
+        (Value::String(l), Value::String(r)) => Some(l.cmp(r)),
+        _ => None,
+    };
+    match op {
+        CompareOp::Eq => ordering == Some(Ordering::Equal) || left == right,
+        CompareOp::Ne => !(ordering == Some(Ordering::Equal) || left == right),
+        CompareOp::Lt => ordering == Some(Ordering::Less),
+        CompareOp::Le => matches!(ordering, Some(Ordering::Less | Ordering::Equal)),
+        CompareOp::Gt => ordering == Some(Ordering::Greater),
+        CompareOp::Ge => matches!(ordering, Some(Ordering::Greater | Ordering::Equal)),
+    }
+}
+
+impl Expr {
+    fn evaluate(&self, result: &Value) -> Value {
+        match self {
+            Expr::Literal(value) => value.clone(),
+            Expr::Path(path) => lookup(result, path).cloned().unwrap_or(Value::Null),
+            Expr::Len(inner) => match inner.evaluate(result) {
+                Value::Array(a) => Value::from(a.len()),
+                Value::Object(o) => Value::from(o.len()),
+                Value::String(s) => Value::from(s.chars().count()),
+                Value::Null => Value::from(0),
+                _ => Value::Null,
+            },
+            Expr::Not(inner) => Value::Bool(!truthy(&inner.evaluate(result))),
+            Expr::And(l, r) => {
+                Value::Bool(truthy(&l.evaluate(result)) && truthy(&r.evaluate(result)))
+            }
+            Expr::Or(l, r) => {
+                Value::Bool(truthy(&l.evaluate(result)) || truthy(&r.evaluate(result)))
+            }
+            Expr::Compare(l, op, r) => {
+                Value::Bool(compare(&l.evaluate(result), *op, &r.evaluate(result)))
+            }
+        }
+    }
+
+    fn collect_paths<'a>(&'a self, paths: &mut Vec<&'a str>) {
+        match self {
+            Expr::Literal(_) => {}
+            Expr::Path(path) => {
+                if !paths.contains(&path.as_str()) {
+                    paths.push(path);
+                }
+            }
+            Expr::Len(inner) | Expr::Not(inner) => inner.collect_paths(paths),
+            Expr::And(l, r) | Expr::Or(l, r) | Expr::Compare(l, _, r) => {
+                l.collect_paths(paths);
+                r.collect_paths(paths);
+            }
+        }
+    }
+}
+
+impl Predicate {
+    pub fn parse(source: &str) -> Result<Self, String> {
+        let mut parser = Parser {
+            tokens: tokenize(source)?,
+            position: 0,
+        };
+        let expr = parser.or()?;
+        if let Some(token) = parser.peek() {
+            return Err(format!("unexpected '{}' after the expression", token));
+        }
+        Ok(Self {
+            source: source.trim().to_string(),
+            expr,
+        })
+    }
+
+    /// Whether the predicate holds for an RPC result.
+    pub fn matches(&self, result: &Value) -> bool {
+        truthy(&self.expr.evaluate(result))
+    }
+
+    /// The fields the predicate looks at, with their value in `result`,
+    /// e.g. `stars = 3.5`.
+    pub fn observed(&self, result: &Value) -> String {
+        let mut paths = Vec::new();
+        self.expr.collect_paths(&mut paths);
+        paths
+            .iter()
+            .map(|path| {
+                let value = lookup(result, path).cloned().unwrap_or(Value::Null);
+                format!("{} = {}", path, value)
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+impl fmt::Display for Predicate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn holds(source: &str, result: Value) -> bool {
+        Predicate::parse(source).unwrap().matches(&result)
+    }
+
+    #[test]
+    fn tokenizes_negative_numbers() {
+        assert_eq!(
+            tokenize("x > -1.5").unwrap(),
+            vec![
+                Token::Ident("x".to_string()),
+                Token::Compare(CompareOp::Gt),
+                Token::Literal(json!(-1.5)),
+            ]
+        );
+        assert!(holds("delta >= -2", json!({"delta": -2})));
+        assert!(!holds("delta < -2", json!({"delta": -1})));
+        assert!(tokenize("x == 1.2.3").is_err());
+        // A minus sign on its own is not an operator
+        assert!(tokenize("x - 1").is_err());
+    }
+
+    #[test]
+    fn tokenizes_quoted_strings() {
+        assert_eq!(
+            tokenize(r#"'single' "dou\"ble" "é""#).unwrap(),
+            vec![
+                Token::Literal(json!("single")),
+                Token::Literal(json!("dou\"ble")),
+                Token::Literal(json!("é")),
+            ]
+        );
+        assert!(holds("name == 'ssh'", json!({"name": "ssh"})));
+        assert!(holds(r#"name == "a && b""#, json!({"name": "a && b"})));
+        assert_eq!(
+            tokenize("name == 'ssh").unwrap_err(),
+            "unterminated string at column 9"
+        );
+    }
+
+    #[test]
+    fn evaluates_len() {
+        let result = json!({"items": [1, 2, 3], "name": "héllo", "map": {"a": 1}});
+        assert!(holds("len(items) == 3", result.clone()));
+        assert!(holds("len(name) == 5", result.clone()));
+        assert!(holds("len(map) == 1", result.clone()));
+        assert!(holds("len(missing) == 0", result.clone()));
+        assert!(holds("len(.) == 3", result));
+        assert!(Predicate::parse("size(items) == 3").is_err());
+        assert!(Predicate::parse("len(items == 3").is_err());
+    }
+
+    #[test]
+    fn and_binds_tighter_than_or() {
+        let result = json!({"a": true, "b": false, "c": false});
+        // a || (b && c), not (a || b) && c
+        assert!(holds("a || b && c", result.clone()));
+        assert!(!holds("(a || b) && c", result.clone()));
+        // ! binds tighter than comparisons: (!5) == 0 is false == 0
+        assert!(!holds("!x == 0", json!({"x": 5})));
+        assert!(holds("x == 5 && !b", json!({"x": 5, "b": false})));
+        assert!(holds("!(a && b)", result));
+    }
+
+    #[test]
+    fn compares_by_type() {
+        assert!(holds("stars >= 4", json!({"stars": 4.2})));
+        assert!(holds("version < \"2.0\"", json!({"version": "1.9"})));
+        assert!(!holds("stars > \"4\"", json!({"stars": 5})));
+        assert!(holds("stars != \"5\"", json!({"stars": 5})));
+        assert!(holds("items[1].name == null", json!({"items": [{}, {}]})));
+    }
+
+    #[test]
+    fn rejects_trailing_tokens() {
+        assert_eq!(
+            Predicate::parse("a == 1 2").unwrap_err(),
+            "unexpected '2' after the expression"
+        );
+        assert!(Predicate::parse("").is_err());
+        assert!(Predicate::parse("a ==").is_err());
+    }
+
+    #[test]
+    fn reports_observed_values() {
+        let predicate = Predicate::parse("stars >= 4 && len(items) > stars").unwrap();
+        assert_eq!(
+            predicate.observed(&json!({"stars": 3.5, "items": []})),
+            "stars = 3.5, items = []"
+        );
+    }
+}
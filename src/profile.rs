@@ -0,0 +1,134 @@
+use crate::config::{load_json_file, user_config_file};
+use crate::{EDAMAME_CA_PEM, EDAMAME_CLIENT_KEY, EDAMAME_CLIENT_PEM, EDAMAME_TARGET};
+use lazy_static::lazy_static;
+use std::fs;
+use std::sync::RwLock;
+
+/// Profile used when `--profile` is not given. It connects with the
+/// settings built into the binary unless `profiles.json` overrides them.
+pub const DEFAULT_PROFILE: &str = "default";
+
+/// Connection profiles in the per-user configuration directory. Each entry
+/// may override the daemon target and the mTLS material; missing fields fall
+/// back to the built-in values:
+///
+/// ```json
+/// {
+///   "staging": {
+///     "target": "10.0.0.12:50051",
+///     "ca_pem_file": "/etc/edamame/ca.pem",
+///     "client_pem_file": "/etc/edamame/client.pem",
+///     "client_key_file": "/etc/edamame/client.key"
+///   }
+/// }
+/// ```
+pub const PROFILES_FILE: &str = "profiles.json";
+
+/// A named daemon connection.
+#[derive(Clone, Debug)]
+pub struct Profile {
+    pub name: String,
+    pub target: String,
+    pub ca_pem: String,
+    pub client_pem: String,
+    pub client_key: String,
+}
+
+impl Profile {
+    fn builtin(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            target: EDAMAME_TARGET.clone(),
+            ca_pem: EDAMAME_CA_PEM.clone(),
+            client_pem: EDAMAME_CLIENT_PEM.clone(),
+            client_key: EDAMAME_CLIENT_KEY.clone(),
+        }
+    }
+
+    /// Resolve a profile by name from `profiles.json`.
+    pub fn load(name: &str) -> Result<Self, String> {
+        let entry = user_config_file(PROFILES_FILE)
+            .and_then(|path| load_json_file(&path))
+            .and_then(|profiles| profiles.get(name).cloned());
+
+        let Some(entry) = entry else {
+            if name == DEFAULT_PROFILE {
+                return Ok(Self::builtin(name));
+            }
+            return Err(format!(
+                "Unknown profile '{}'. Profiles are defined in {}",
+                name,
+                user_config_file(PROFILES_FILE)
+                    .map(|p| p.display().to_string())
+                    .unwrap_or_else(|| PROFILES_FILE.to_string())
+            ));
+        };
+
+        let mut profile = Self::builtin(name);
+        if let Some(target) = entry.get("target").and_then(|t| t.as_str()) {
+            profile.target = target.to_string();
+        }
+        for (key, field) in [
+            ("ca_pem_file", &mut profile.ca_pem),
+            ("client_pem_file", &mut profile.client_pem),
+            ("client_key_file", &mut profile.client_key),
+        ] {
+            if let Some(path) = entry.get(key).and_then(|p| p.as_str()) {
+                *field = fs::read_to_string(path).map_err(|e| {
+                    format!("Profile '{}': error reading {} {}: {}", name, key, path, e)
+                })?;
+            }
+        }
+        Ok(profile)
+    }
+}
+
+lazy_static! {
+    static ref ACTIVE_PROFILE: RwLock<Option<Profile>> = RwLock::new(None);
+}
+
+/// The profile RPC calls go through. Defaults to the built-in connection.
+pub fn active() -> Profile {
+    ACTIVE_PROFILE
+        .read()
+        .unwrap()
+        .clone()
+        .unwrap_or_else(|| Profile::builtin(DEFAULT_PROFILE))
+}
+
+/// Name of the profile the CLI is currently working with. Local state such
+/// as the REPL history and the catalog cache is kept per profile.
+pub fn active_profile() -> String {
+    active().name
+}
+
+/// Switch to another profile for all subsequent RPC calls.
+pub fn set_active_profile(name: &str) -> Result<(), String> {
+    let profile = Profile::load(name)?;
+    *ACTIVE_PROFILE.write().unwrap() = Some(profile);
+    Ok(())
+}
+
+/// The profile name reduced to characters that are safe in a file name.
+pub fn profile_file_stem(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+/// Names of the configured profiles, `default` first.
+pub fn profile_names() -> Vec<String> {
+    let mut names = vec![DEFAULT_PROFILE.to_string()];
+    if let Some(profiles) = user_config_file(PROFILES_FILE).and_then(|path| load_json_file(&path)) {
+        if let Some(map) = profiles.as_object() {
+            names.extend(map.keys().filter(|k| *k != DEFAULT_PROFILE).cloned());
+        }
+    }
+    names
+}
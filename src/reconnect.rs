@@ -0,0 +1,155 @@
+use crate::catalog::Catalog;
+use crate::dispatch::CallError;
+use crate::profile;
+use edamame_core::api::api_rpc::{rpc_call_remote, rpc_get_api_methods};
+use std::thread::sleep;
+use std::time::Duration;
+
+/// Attempts made to reach the daemon again before giving up.
+const RECONNECT_ATTEMPTS: u32 = 6;
+/// Delay before the first attempt, doubled after each failure.
+const INITIAL_BACKOFF: Duration = Duration::from_millis(500);
+const MAX_BACKOFF: Duration = Duration::from_secs(8);
+
+/// Method used to identify the daemon build, to notice restarts on a new
+/// version.
+const VERSION_METHOD: &str = "get_core_version";
+
+/// Whether the daemon of the active profile answers a catalog request.
+fn daemon_answers() -> bool {
+    let active = profile::active();
+    rpc_get_api_methods(
+        &active.ca_pem,
+        &active.client_pem,
+        &active.client_key,
+        &active.target,
+    )
+    .is_ok()
+}
+
+/// Whether a failed call means the daemon could not be reached. The errors
+/// of the RPC layer do not tell transport failures from the daemon's own,
+/// so a daemon error is followed by a catalog request: if the daemon
+/// answers it, the connection is fine. Calls stopped on the client side
+/// (policy, confirmation) never reached the transport.
+pub fn is_connection_error(error: &CallError) -> bool {
+    error.is_daemon_error() && !daemon_answers()
+}
+
+/// Version reported by the daemon, if it can tell. This is a probe of the
+/// CLI, not a call of the user: it bypasses `dispatch::call`, so it is
+/// neither subject to the method policy nor audited.
+pub fn daemon_version() -> Option<String> {
+    let active = profile::active();
+    rpc_call_remote(
+        VERSION_METHOD,
+        None,
+        &active.ca_pem,
+        &active.client_pem,
+        &active.client_key,
+        &active.target,
+    )
+    .ok()
+    .map(|version| version.trim().trim_matches('"').to_string())
+}
+
+/// What changed when the daemon came back.
+pub struct Reconnected {
+    pub attempts: u32,
+    /// Version known before the connection was lost.
+    pub previous_version: Option<String>,
+    /// Version reported after reconnecting.
+    pub version: Option<String>,
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+}
+
+impl Reconnected {
+    /// One-line report for the user.
+    pub fn describe(&self) -> String {
+        let mut message = format!(
+            "Reconnected to the daemon at {} after {} attempt(s)",
+            profile::active().target,
+            self.attempts
+        );
+        match (&self.previous_version, &self.version) {
+            (Some(previous), Some(version)) if previous != version => message.push_str(&format!(
+                "; it restarted with version {} (was {})",
+                version, previous
+            )),
+            (_, Some(version)) => message.push_str(&format!(" (version {})", version)),
+            (_, None) => {}
+        }
+        if !self.added.is_empty() || !self.removed.is_empty() {
+            message.push_str(&format!(
+                ". Catalog refreshed: {} new, {} removed method(s)",
+                self.added.len(),
+                self.removed.len()
+            ));
+        }
+        message
+    }
+}
+
+/// Wait for the daemon to answer again, backing off exponentially between
+/// attempts. On success the catalog cache is refreshed so that methods of a
+/// new daemon version are known right away; cached signatures are dropped
+/// when the version changed, since they may have changed with it.
+pub fn reconnect(known_version: Option<&str>) -> Result<Reconnected, String> {
+    let mut delay = INITIAL_BACKOFF;
+    let mut last_error = String::new();
+    for attempt in 1..=RECONNECT_ATTEMPTS {
+        eprintln!(
+            "     Reconnecting in {} ms (attempt {}/{})...",
+            delay.as_millis(),
+            attempt,
+            RECONNECT_ATTEMPTS
+        );
+        sleep(delay);
+        let active = profile::active();
+        match rpc_get_api_methods(
+            &active.ca_pem,
+            &active.client_pem,
+            &active.client_key,
+            &active.target,
+        ) {
+            Ok(methods) => {
+                let version = daemon_version();
+                let upgraded = matches!((known_version, &version),
+                    (Some(known), Some(version)) if known != version);
+                let mut catalog = Catalog::load();
+                let added = methods
+                    .iter()
+                    .filter(|m| catalog.methods().binary_search(m).is_err())
+                    .cloned()
+                    .collect();
+                let removed = catalog
+                    .methods()
+                    .iter()
+                    .filter(|m| !methods.contains(m))
+                    .cloned()
+                    .collect();
+                if upgraded {
+                    catalog = Catalog::default();
+                }
+                catalog.set_methods(methods);
+                catalog.save();
+                return Ok(Reconnected {
+                    attempts: attempt,
+                    previous_version: known_version.map(str::to_string),
+                    version,
+                    added,
+                    removed,
+                });
+            }
+            Err(e) => last_error = format!("{:?}", e),
+        }
+        delay = (delay * 2).min(MAX_BACKOFF);
+    }
+    Err(format!(
+        "The daemon at {} is still unreachable after {} attempts: {}",
+        profile::active().target,
+        RECONNECT_ATTEMPTS,
+        last_error
+    ))
+}
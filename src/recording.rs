@@ -0,0 +1,250 @@
+use crate::catalog::now_secs;
+use crate::dispatch;
+use crate::output::result_value;
+use crate::profile::{self, active_profile};
+use crate::redact::{
+    redact_command, redact_text, redact_value, stdout_text, stdout_value, PLACEHOLDER,
+};
+use crate::{write_stdout, ERROR_CODE_MISMATCH, ERROR_CODE_PARAM};
+use serde_json::{json, Map, Value};
+use std::fs::{self, File};
+use std::io::Write;
+use std::time::{Duration, Instant};
+
+/// Recorded values longer than this are truncated in the replay report.
+const MAX_REPORT_WIDTH: usize = 200;
+
+/// Writes a REPL session as NDJSON, one entry per line:
+///
+/// ```json
+/// {"args":null,"command":"get_score","duration_ms":12,"error":null,"method":"get_score","profile":"default","result":{...},"seq":1,"time":1735689600}
+/// {"command":".output table","profile":"default","seq":2,"time":1735689605}
+/// ```
+///
+/// Entries with a `method` are RPC calls with their resolved argument object;
+/// the others are local commands, kept to document the session.
+///
+/// Unless it was created with `redact` off (`--no-redact`), sensitive values
+/// are redacted and call entries have `"redacted":true`. Calls whose
+/// arguments were redacted cannot be replayed.
+pub struct Recorder {
+    file: File,
+    seq: u64,
+    failed: bool,
+    redact: bool,
+}
+
+impl Recorder {
+    pub fn create(path: &str, redact: bool) -> Result<Self, String> {
+        let file = File::create(path).map_err(|e| format!("Error creating {}: {}", path, e))?;
+        Ok(Self {
+            file,
+            seq: 0,
+            failed: false,
+            redact,
+        })
+    }
+
+    /// Record an entry that was handled locally.
+    pub fn record_command(&mut self, command: &str) {
+        let command = if self.redact {
+            redact_text(command)
+        } else {
+            command.to_string()
+        };
+        let mut entry = Map::new();
+        entry.insert("command".to_string(), json!(command));
+        self.write(entry);
+    }
+
+    /// Record an RPC call. `args` is the argument object sent to the daemon.
+    pub fn record_call(
+        &mut self,
+        command: &str,
+        method: &str,
+        args: Option<&str>,
+        outcome: Result<&str, String>,
+        duration: Duration,
+    ) {
+        let args_value = args
+            .and_then(|a| serde_json::from_str::<Value>(a).ok())
+            .unwrap_or(Value::Null);
+        let (mut result, mut error) = match outcome {
+            Ok(result) => (result_value(result), Value::Null),
+            Err(e) => (Value::Null, json!(e)),
+        };
+        let mut command = command.to_string();
+        let mut args_value = args_value;
+        if self.redact {
+            command = redact_command(&command, method, args);
+            args_value = redact_value(args_value);
+            result = redact_value(result);
+            if let Value::String(e) = &error {
+                error = json!(redact_text(e));
+            }
+        }
+        let mut entry = Map::new();
+        entry.insert("command".to_string(), json!(command));
+        entry.insert("method".to_string(), json!(method));
+        entry.insert("args".to_string(), args_value);
+        entry.insert("result".to_string(), result);
+        entry.insert("error".to_string(), error);
+        entry.insert(
+            "duration_ms".to_string(),
+            json!(duration.as_millis() as u64),
+        );
+        if self.redact {
+            entry.insert("redacted".to_string(), json!(true));
+        }
+        self.write(entry);
+    }
+
+    fn write(&mut self, fields: Map<String, Value>) {
+        self.seq += 1;
+        let mut entry = Map::new();
+        entry.insert("seq".to_string(), json!(self.seq));
+        entry.insert("time".to_string(), json!(now_secs()));
+        entry.insert("profile".to_string(), json!(active_profile()));
+        entry.extend(fields);
+        if let Err(e) = writeln!(self.file, "{}", Value::Object(entry)) {
+            // Report once rather than on every entry
+            if !self.failed {
+                eprintln!(">>>> Error writing the session recording: {}", e);
+                self.failed = true;
+            }
+        }
+    }
+}
+
+fn truncate(text: &str) -> String {
+    if text.chars().count() > MAX_REPORT_WIDTH {
+        let truncated: String = text.chars().take(MAX_REPORT_WIDTH - 1).collect();
+        format!("{}…", truncated)
+    } else {
+        text.to_string()
+    }
+}
+
+/// Re-run the calls of a recording against the active profile and report
+/// the ones whose outcome differs from the recording. Results of a redacted
+/// recording are compared once redacted, and its calls with redacted
+/// arguments are skipped.
+pub fn handle_replay(path: &str) -> i32 {
+    let contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(e) => {
+            eprintln!(">>>> Error reading {}: {}", path, e);
+            return ERROR_CODE_PARAM;
+        }
+    };
+
+    let mut entries = Vec::new();
+    for (index, line) in contents.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        match serde_json::from_str::<Value>(line) {
+            // Local commands are not replayed
+            Ok(entry) if entry.get("method").and_then(Value::as_str).is_some() => {
+                entries.push(entry)
+            }
+            Ok(_) => {}
+            Err(e) => {
+                eprintln!(">>>> {}:{}: invalid entry: {}", path, index + 1, e);
+                return ERROR_CODE_PARAM;
+            }
+        }
+    }
+
+    let active = profile::active();
+    let mut identical = 0;
+    let mut different = 0;
+    let mut skipped = 0;
+    for entry in &entries {
+        let method = entry["method"].as_str().unwrap_or_default();
+        let args = match &entry["args"] {
+            Value::Null => None,
+            args => Some(args.to_string()),
+        };
+        let recorded_error = !entry["error"].is_null();
+        let redacted = entry["redacted"].as_bool().unwrap_or(false);
+        let label = stdout_text(format!(
+            "[{}] {}",
+            entry["seq"].as_u64().unwrap_or_default(),
+            entry["command"].as_str().unwrap_or(method)
+        ));
+        if redacted && args.as_ref().is_some_and(|args| args.contains(PLACEHOLDER)) {
+            skipped += 1;
+            let _ = write_stdout(&format!(
+                "{}: SKIPPED (arguments redacted; record with --no-redact to replay it)",
+                label
+            ));
+            continue;
+        }
+
+        let started = Instant::now();
+        let outcome = dispatch::call(method, args.as_deref());
+        let elapsed = started.elapsed().as_millis();
+
+        let difference = match outcome {
+            Ok(result) => {
+                let replayed = if redacted {
+                    redact_value(result_value(&result))
+                } else {
+                    result_value(&result)
+                };
+                if recorded_error {
+                    Some((
+                        format!("error: {}", entry["error"].as_str().unwrap_or_default()),
+                        stdout_value(replayed).to_string(),
+                    ))
+                } else if replayed != entry["result"] {
+                    Some((
+                        stdout_value(entry["result"].clone()).to_string(),
+                        stdout_value(replayed).to_string(),
+                    ))
+                } else {
+                    None
+                }
+            }
+            // A call that failed when recorded is expected to fail again
+            Err(_) if recorded_error => None,
+            Err(e) => Some((
+                stdout_value(entry["result"].clone()).to_string(),
+                format!("error: {}", e),
+            )),
+        };
+
+        match difference {
+            None => {
+                identical += 1;
+                let _ = write_stdout(&format!("{}: same ({} ms)", label, elapsed));
+            }
+            Some((recorded, replayed)) => {
+                different += 1;
+                let _ = write_stdout(&format!("{}: DIFFERS ({} ms)", label, elapsed));
+                let _ = write_stdout(&format!("    recorded: {}", truncate(&recorded)));
+                let _ = write_stdout(&format!("    replayed: {}", truncate(&replayed)));
+            }
+        }
+    }
+
+    let skipped = if skipped > 0 {
+        format!(", {} skipped", skipped)
+    } else {
+        String::new()
+    };
+    let _ = write_stdout(&format!(
+        "Replayed {} call(s) against profile '{}': {} same, {} different{}",
+        entries.len(),
+        active.name,
+        identical,
+        different,
+        skipped
+    ));
+    if different > 0 {
+        ERROR_CODE_MISMATCH
+    } else {
+        0
+    }
+}
@@ -0,0 +1,475 @@
+use crate::config::{load_json_file, user_config_file};
+use lazy_static::lazy_static;
+use regex::Regex;
+use serde_json::{json, Value};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Name of the redaction rules file, in the user configuration directory:
+///
+/// ```json
+/// {
+///   "keys": ["email", "serial"],
+///   "paths": ["$.devices[*].ip_address", "$..mac_address"],
+///   "patterns": ["[A-Za-z0-9._%+-]+@[A-Za-z0-9.-]+\\.[A-Za-z]{2,}"]
+/// }
+/// ```
+///
+/// `keys` adds words to the built-in ones, `paths` are JSONPath expressions
+/// and `patterns` regular expressions matched in strings. Every key is
+/// optional.
+pub const REDACTION_FILE: &str = "redaction.json";
+
+/// What redacted values are replaced with.
+pub const PLACEHOLDER: &str = "<redacted>";
+
+/// Fields whose name has one of these words are always redacted.
+const BUILTIN_KEYS: [&str; 5] = ["pin", "password", "token", "secret", "key"];
+
+/// Set by `--redact`.
+static STDOUT: AtomicBool = AtomicBool::new(false);
+
+lazy_static! {
+    static ref RULES: Rules = load_rules();
+}
+
+pub fn set_redact_stdout(redact: bool) {
+    STDOUT.store(redact, Ordering::SeqCst);
+}
+
+fn redact_stdout() -> bool {
+    STDOUT.load(Ordering::SeqCst)
+}
+
+/// One step of a JSONPath expression.
+#[derive(Debug)]
+enum Selector {
+    Name(String),
+    Index(usize),
+    All,
+}
+
+/// A selector, applied to the children of the current node or, after `..`,
+/// to its descendants at any depth.
+#[derive(Debug)]
+struct Segment {
+    recursive: bool,
+    selector: Selector,
+}
+
+/// Parse the subset of JSONPath used to select fields: `$`, `.name`,
+/// `['name']`, `[0]`, `.*`, `[*]` and `..` for any depth.
+fn parse_path(path: &str) -> Result<Vec<Segment>, String> {
+    let invalid = |reason: &str| format!("invalid JSONPath '{}': {}", path, reason);
+    let Some(mut rest) = path.trim().strip_prefix('$') else {
+        return Err(invalid("expected it to start with $"));
+    };
+    let mut segments = Vec::new();
+    while !rest.is_empty() {
+        let recursive = rest.starts_with("..");
+        let selector = if let Some(bracket) =
+            rest.strip_prefix("..[").or_else(|| rest.strip_prefix('['))
+        {
+            let end = bracket.find(']').ok_or_else(|| invalid("missing ]"))?;
+            let inner = bracket[..end].trim();
+            rest = &bracket[end + 1..];
+            if inner == "*" {
+                Selector::All
+            } else if let Ok(index) = inner.parse() {
+                Selector::Index(index)
+            } else if inner.len() >= 2
+                && (inner.starts_with('\'') && inner.ends_with('\'')
+                    || inner.starts_with('"') && inner.ends_with('"'))
+            {
+                Selector::Name(inner[1..inner.len() - 1].to_string())
+            } else {
+                return Err(invalid(&format!("unsupported selector [{}]", inner)));
+            }
+        } else if let Some(dotted) = rest.strip_prefix("..").or_else(|| rest.strip_prefix('.')) {
+            let end = dotted.find(['.', '[']).unwrap_or(dotted.len());
+            let name = &dotted[..end];
+            rest = &dotted[end..];
+            match name {
+                "" => return Err(invalid("empty field name")),
+                "*" => Selector::All,
+                name => Selector::Name(name.to_string()),
+            }
+        } else {
+            return Err(invalid(&format!("unexpected '{}'", rest)));
+        };
+        segments.push(Segment {
+            recursive,
+            selector,
+        });
+    }
+    if segments.is_empty() {
+        return Err(invalid("it selects the whole value"));
+    }
+    Ok(segments)
+}
+
+/// Replace the nodes of `value` selected by `segments`.
+fn redact_path(value: &mut Value, segments: &[Segment]) {
+    let Some((segment, rest)) = segments.split_first() else {
+        *value = json!(PLACEHOLDER);
+        return;
+    };
+    match (&segment.selector, &mut *value) {
+        (Selector::Name(name), Value::Object(map)) => {
+            if let Some(child) = map.get_mut(name) {
+                redact_path(child, rest);
+            }
+        }
+        (Selector::Index(index), Value::Array(items)) => {
+            if let Some(child) = items.get_mut(*index) {
+                redact_path(child, rest);
+            }
+        }
+        (Selector::All, Value::Object(map)) => {
+            map.values_mut().for_each(|child| redact_path(child, rest))
+        }
+        (Selector::All, Value::Array(items)) => {
+            items.iter_mut().for_each(|child| redact_path(child, rest))
+        }
+        _ => {}
+    }
+    if segment.recursive {
+        let children: Box<dyn Iterator<Item = &mut Value>> = match value {
+            Value::Object(map) => Box::new(map.values_mut()),
+            Value::Array(items) => Box::new(items.iter_mut()),
+            _ => return,
+        };
+        for child in children {
+            redact_path(child, segments);
+        }
+    }
+}
+
+/// The words of a field name, lowercase: `apiKey` and `api-key` both give
+/// `api` and `key`.
+fn words(name: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut previous_lower = false;
+    for c in name.chars() {
+        let boundary = !c.is_alphanumeric() || (c.is_uppercase() && previous_lower);
+        if boundary && !word.is_empty() {
+            words.push(std::mem::take(&mut word));
+        }
+        if c.is_alphanumeric() {
+            word.extend(c.to_lowercase());
+        }
+        previous_lower = c.is_lowercase() || c.is_ascii_digit();
+    }
+    if !word.is_empty() {
+        words.push(word);
+    }
+    words
+}
+
+struct Rules {
+    keys: Vec<String>,
+    paths: Vec<Vec<Segment>>,
+    patterns: Vec<Regex>,
+    /// `key: value` and `key=value` pairs in text, for the same keys.
+    pairs: Regex,
+}
+
+impl Rules {
+    /// Whether a field name has one of the keys as a word, singular or
+    /// plural: `api_key` and `pins` are sensitive, `keyboard` is not.
+    fn is_sensitive(&self, name: &str) -> bool {
+        words(name).iter().any(|word| {
+            self.keys.contains(word)
+                || word
+                    .strip_suffix('s')
+                    .is_some_and(|singular| self.keys.iter().any(|key| key == singular))
+        })
+    }
+
+    fn redact_keys(&self, value: &mut Value) {
+        match value {
+            Value::Object(map) => {
+                for (name, child) in map.iter_mut() {
+                    if self.is_sensitive(name) {
+                        *child = json!(PLACEHOLDER);
+                    } else {
+                        self.redact_keys(child);
+                    }
+                }
+            }
+            Value::Array(items) => items.iter_mut().for_each(|item| self.redact_keys(item)),
+            Value::String(text) if self.patterns.iter().any(|p| p.is_match(text)) => {
+                *text = self.redact_patterns(text);
+            }
+            _ => {}
+        }
+    }
+
+    fn redact_patterns(&self, text: &str) -> String {
+        self.patterns
+            .iter()
+            .fold(text.to_string(), |text, pattern| {
+                pattern.replace_all(&text, PLACEHOLDER).into_owned()
+            })
+    }
+}
+
+fn pairs_regex(keys: &[String]) -> Regex {
+    let keys: Vec<String> = keys.iter().map(|key| regex::escape(key)).collect();
+    // A key, possibly the last word of a longer name (api_key, user-pin) and
+    // plural, then a separator with optional (escaped) quotes, then the value
+    Regex::new(&format!(
+        r#"(?i)(\b(?:[a-z0-9]+[_-])*(?:{})s?\\?"?\s*[:=]\s*\\?"?)[^"\\\s,}}\]]+"#,
+        keys.join("|")
+    ))
+    .expect("valid redaction pattern")
+}
+
+fn string_list(value: &Value, key: &str) -> Vec<String> {
+    match value.get(key) {
+        None => Vec::new(),
+        Some(Value::Array(items)) if items.iter().all(Value::is_string) => items
+            .iter()
+            .filter_map(|item| item.as_str().map(str::to_string))
+            .collect(),
+        Some(_) => {
+            eprintln!(
+                ">>>> Ignoring \"{}\" in {}: expected a list of strings",
+                key, REDACTION_FILE
+            );
+            Vec::new()
+        }
+    }
+}
+
+/// The built-in rules and the ones of the user's redaction file. Rules that
+/// cannot be used are reported and skipped; the built-in ones always apply.
+fn load_rules() -> Rules {
+    let mut keys: Vec<String> = BUILTIN_KEYS.iter().map(|key| key.to_string()).collect();
+    let mut paths = Vec::new();
+    let mut patterns = Vec::new();
+    if let Some(value) = user_config_file(REDACTION_FILE).and_then(|path| load_json_file(&path)) {
+        keys.extend(
+            string_list(&value, "keys")
+                .iter()
+                .flat_map(|key| words(key)),
+        );
+        for path in string_list(&value, "paths") {
+            match parse_path(&path) {
+                Ok(segments) => paths.push(segments),
+                Err(e) => eprintln!(">>>> Ignoring a rule of {}: {}", REDACTION_FILE, e),
+            }
+        }
+        for pattern in string_list(&value, "patterns") {
+            match Regex::new(&pattern) {
+                Ok(regex) => patterns.push(regex),
+                Err(e) => eprintln!(
+                    ">>>> Ignoring a rule of {}: invalid pattern '{}': {}",
+                    REDACTION_FILE, pattern, e
+                ),
+            }
+        }
+    }
+    keys.sort();
+    keys.dedup();
+    Rules {
+        pairs: pairs_regex(&keys),
+        keys,
+        paths,
+        patterns,
+    }
+}
+
+/// Redact a JSON value: fields with a sensitive name, the fields selected by
+/// the JSONPath rules and the parts of strings matched by the patterns.
+pub fn redact_value(mut value: Value) -> Value {
+    for path in &RULES.paths {
+        redact_path(&mut value, path);
+    }
+    RULES.redact_keys(&mut value);
+    value
+}
+
+/// Redact free text, such as an error message: `key: value` pairs with a
+/// sensitive key, quoted or not, and the matches of the patterns.
+pub fn redact_text(text: &str) -> String {
+    let text = RULES
+        .pairs
+        .replace_all(text, format!("${{1}}{}", PLACEHOLDER));
+    RULES.redact_patterns(&text)
+}
+
+/// Redact the command line of a call given its resolved argument object.
+/// Positional arguments are only named in the object, so a command line
+/// with nothing to redact as text may still hold sensitive values: the
+/// method and the redacted object are shown instead, after the `let name =`
+/// of a REPL binding.
+pub fn redact_command(command: &str, method: &str, args: Option<&str>) -> String {
+    let args = args
+        .and_then(|args| serde_json::from_str::<Value>(args).ok())
+        .unwrap_or(Value::Null);
+    let redacted = redact_value(args.clone());
+    let redacted_command = redact_text(command);
+    if redacted != args && redacted_command == command {
+        let binding = command
+            .strip_prefix("let ")
+            .and_then(|binding| binding.split_once('='))
+            .map(|(name, _)| format!("let {} = ", name.trim()))
+            .unwrap_or_default();
+        format!("{}{} {}", binding, method, redacted)
+    } else {
+        redacted_command
+    }
+}
+
+/// Redact a raw RPC result, which is JSON or, failing that, text.
+pub fn redact_result(result: &str) -> String {
+    match serde_json::from_str::<Value>(result) {
+        Ok(value) => redact_value(value).to_string(),
+        Err(_) => redact_text(result),
+    }
+}
+
+/// `redact_value` with `--redact`, for values printed on stdout.
+pub fn stdout_value(value: Value) -> Value {
+    if redact_stdout() {
+        redact_value(value)
+    } else {
+        value
+    }
+}
+
+/// `redact_text` with `--redact`, for text printed on the terminal.
+pub fn stdout_text(text: String) -> String {
+    if redact_stdout() {
+        redact_text(&text)
+    } else {
+        text
+    }
+}
+
+/// `redact_result` with `--redact`.
+pub fn stdout_result(result: &str) -> String {
+    if redact_stdout() {
+        redact_result(result)
+    } else {
+        result.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn builtin_keys() -> Vec<String> {
+        BUILTIN_KEYS.iter().map(|key| key.to_string()).collect()
+    }
+
+    fn redacted(path: &str, mut value: Value) -> Value {
+        redact_path(&mut value, &parse_path(path).unwrap());
+        value
+    }
+
+    fn redact_pairs(text: &str) -> String {
+        pairs_regex(&builtin_keys())
+            .replace_all(text, format!("${{1}}{}", PLACEHOLDER))
+            .into_owned()
+    }
+
+    #[test]
+    fn parses_paths() {
+        let segments = parse_path("$.devices[*]..['ip address'][0]").unwrap();
+        let summary: Vec<String> = segments
+            .iter()
+            .map(|segment| format!("{} {:?}", segment.recursive, segment.selector))
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                "false Name(\"devices\")",
+                "false All",
+                "true Name(\"ip address\")",
+                "false Index(0)",
+            ]
+        );
+        assert!(parse_path("$..*").unwrap()[0].recursive);
+        for invalid in ["devices", "$", "$.", "$.a[", "$[a]", "$a"] {
+            assert!(parse_path(invalid).is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn redacts_paths() {
+        let value = json!({"devices": [{"ip": "10.0.0.1", "name": "a"}, {"ip": "10.0.0.2"}]});
+        assert_eq!(
+            redacted("$.devices[*].ip", value.clone()),
+            json!({"devices": [{"ip": PLACEHOLDER, "name": "a"}, {"ip": PLACEHOLDER}]})
+        );
+        assert_eq!(
+            redacted("$.devices[1]", value.clone()),
+            json!({"devices": [{"ip": "10.0.0.1", "name": "a"}, PLACEHOLDER]})
+        );
+        assert_eq!(
+            redacted("$..name", json!({"name": 1, "a": {"b": [{"name": 2}]}})),
+            json!({"name": PLACEHOLDER, "a": {"b": [{"name": PLACEHOLDER}]}})
+        );
+        // Paths that do not match leave the value alone
+        assert_eq!(redacted("$.missing[3].ip", value.clone()), value);
+    }
+
+    #[test]
+    fn splits_words() {
+        assert_eq!(words("apiKey"), ["api", "key"]);
+        assert_eq!(words("api-key"), ["api", "key"]);
+        assert_eq!(words("API_KEY"), ["api", "key"]);
+        assert_eq!(words("user2Pin"), ["user2", "pin"]);
+        assert_eq!(words("keyboard"), ["keyboard"]);
+        assert!(words("__").is_empty());
+    }
+
+    #[test]
+    fn detects_sensitive_names() {
+        let rules = Rules {
+            pairs: pairs_regex(&builtin_keys()),
+            keys: builtin_keys(),
+            paths: Vec::new(),
+            patterns: vec![Regex::new(r"\d{4}-\d{4}").unwrap()],
+        };
+        assert!(rules.is_sensitive("apiKey"));
+        assert!(rules.is_sensitive("ADMIN_PASSWORD"));
+        assert!(!rules.is_sensitive("keyboard"));
+        assert!(!rules.is_sensitive("spinner"));
+        for plural in ["pins", "tokens", "passwords", "secrets", "keys", "apiKeys"] {
+            assert!(rules.is_sensitive(plural), "{}", plural);
+        }
+        assert!(!rules.is_sensitive("pinss"));
+        let mut value = json!({"auth": {"token": "t"}, "pins": ["1"], "card": "1234-5678 ok"});
+        rules.redact_keys(&mut value);
+        assert_eq!(
+            value,
+            json!({"auth": {"token": PLACEHOLDER}, "pins": PLACEHOLDER, "card": "<redacted> ok"})
+        );
+    }
+
+    #[test]
+    fn redacts_pairs_in_text() {
+        assert_eq!(redact_pairs("pin: 1234"), "pin: <redacted>");
+        assert_eq!(
+            redact_pairs("PASSWORD=hunter2 next"),
+            "PASSWORD=<redacted> next"
+        );
+        assert_eq!(
+            redact_pairs(r#"{"api_key":"abc","name":"x"}"#),
+            r#"{"api_key":"<redacted>","name":"x"}"#
+        );
+        assert_eq!(
+            redact_pairs(r#"Error(\"user-token\": \"abc\")"#),
+            r#"Error(\"user-token\": \"<redacted>\")"#
+        );
+        // Keys that only contain a sensitive word are left alone
+        assert_eq!(redact_pairs("keyboard: us"), "keyboard: us");
+        assert_eq!(redact_pairs("spin=3"), "spin=3");
+        assert_eq!(redact_pairs("tokens: abc"), "tokens: <redacted>");
+        assert_eq!(redact_pairs("keyss: abc"), "keyss: abc");
+    }
+}
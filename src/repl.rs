@@ -0,0 +1,405 @@
+use crate::args::prepare_args;
+use crate::catalog::Catalog;
+use crate::classify::method_class;
+use crate::config::user_data_dir;
+use crate::dispatch;
+use crate::output::{render_result, result_value};
+use crate::profile::{active_profile, profile_file_stem};
+use crate::reconnect::{self, is_connection_error};
+use crate::recording::Recorder;
+use crate::redact::{redact_command, redact_text};
+use crate::{initialize_core, print_method_help_from_core, write_stdout, ERROR_CODE_PARAM};
+use commands::{run_meta_command, Flow, Session};
+use helper::ReplHelper;
+use rustyline::error::ReadlineError;
+use rustyline::history::DefaultHistory;
+use rustyline::{Cmd, CompletionType, Config, Editor, KeyCode, KeyEvent, Modifiers};
+use serde_json::Value;
+use std::fs;
+use std::io::{self, BufRead, IsTerminal, Lines, StdinLock};
+use std::path::PathBuf;
+use std::time::Instant;
+
+mod commands;
+mod helper;
+mod multiline;
+mod vars;
+
+type ReplEditor = Editor<ReplHelper, DefaultHistory>;
+
+const PROMPT: &str = "> ";
+/// Prompt shown while a multi-line entry is still open.
+const CONTINUATION_PROMPT: &str = ".. ";
+
+/// Number of history entries kept per profile.
+const MAX_HISTORY_ENTRIES: usize = 1000;
+
+/// History file of the active profile, e.g.
+/// `~/.local/share/edamame_cli/history/default.txt`.
+fn history_path() -> Option<PathBuf> {
+    user_data_dir().map(|dir| {
+        dir.join("history")
+            .join(format!("{}.txt", profile_file_stem(&active_profile())))
+    })
+}
+
+/// Add an entry to the history and append it to the profile's file.
+fn save_history(editor: &mut ReplEditor, entry: &str) {
+    let _ = editor.add_history_entry(entry);
+    // Looked up on every entry: .connect switches to another profile's file
+    if let Some(path) = history_path() {
+        if let Err(e) = editor.append_history(&path) {
+            eprintln!(">>>> Error saving history to {}: {}", path.display(), e);
+        }
+    }
+}
+
+/// Whether the arguments of a call entry (`let` included) are a JSON array.
+fn has_positional_args(entry: &str) -> bool {
+    let call = entry
+        .strip_prefix("let ")
+        .and_then(|binding| binding.split_once('='))
+        .map_or(entry, |(_, rhs)| rhs.trim());
+    call.split_once(char::is_whitespace)
+        .is_some_and(|(_, args)| args.trim_start().starts_with('['))
+}
+
+/// Method list for completion and hints: the cached catalog, refreshed from
+/// the daemon when it is stale.
+fn load_catalog() -> Catalog {
+    let mut catalog = Catalog::load();
+    if !catalog.is_fresh() {
+        match catalog.refresh() {
+            Ok(()) => catalog.save(),
+            Err(e) => eprintln!(">>>> {}", e),
+        }
+    }
+    catalog
+}
+
+fn build_editor(catalog: Catalog) -> rustyline::Result<ReplEditor> {
+    let config = Config::builder()
+        .max_history_size(MAX_HISTORY_ENTRIES)?
+        .history_ignore_dups(true)?
+        .history_ignore_space(true)
+        .auto_add_history(false)
+        .completion_type(CompletionType::List)
+        .build();
+    let mut editor = ReplEditor::with_config(config)?;
+    editor.set_helper(Some(ReplHelper::new(catalog)));
+    // Alt-Enter inserts a newline instead of submitting the entry
+    editor.bind_sequence(KeyEvent(KeyCode::Enter, Modifiers::ALT), Cmd::Newline);
+    Ok(editor)
+}
+
+/// Options of the `interactive` subcommand.
+pub struct ReplOptions<'a> {
+    /// NDJSON file the session is recorded to.
+    pub record: Option<&'a str>,
+    /// Redact the recording (on unless `--no-redact`).
+    pub redact_record: bool,
+    /// Print each entry before its output when input is not a terminal.
+    pub echo: bool,
+    /// Stop at the first failing entry when input is not a terminal.
+    pub fail_fast: bool,
+}
+
+/// Next line of input: from the line editor on a terminal, otherwise read
+/// from stdin as-is without prompts. The end of input maps to `Eof`.
+fn read_line(
+    editor: &mut ReplEditor,
+    script: &mut Option<Lines<StdinLock<'static>>>,
+    prompt: &str,
+) -> rustyline::Result<String> {
+    match script {
+        Some(lines) => match lines.next() {
+            Some(line) => Ok(line?),
+            None => Err(ReadlineError::Eof),
+        },
+        None => editor.readline(prompt),
+    }
+}
+
+pub fn interactive_mode(verbose: bool, options: &ReplOptions) -> i32 {
+    let mut session = Session::default();
+    if let Some(path) = options.record {
+        match Recorder::create(path, options.redact_record) {
+            Ok(recorder) => session.recorder = Some(recorder),
+            Err(e) => {
+                eprintln!(">>>> {}", e);
+                return ERROR_CODE_PARAM;
+            }
+        }
+    }
+
+    initialize_core(verbose);
+    session.daemon_version = reconnect::daemon_version();
+
+    // Piped command lists run as scripts: no banner, prompts or history
+    let is_terminal = io::stdin().is_terminal();
+    let mut script = (!is_terminal).then(|| io::stdin().lock().lines());
+
+    if is_terminal {
+        println!(
+            "Entering interactive mode. Type 'exit' or press Ctrl-D to leave, .help for commands."
+        );
+        println!("Usage: <method> [JSON args object or array]");
+        println!("  e.g.  get_score");
+        println!("        get_score {{\"complete_only\": false}}");
+        println!("Tab completes method and argument names, Ctrl-R searches the history,");
+        println!("Ctrl-C cancels the current entry, Alt-Enter starts a new line.");
+        println!(
+            "JSON arguments may span several lines: input continues until brackets are balanced."
+        );
+    }
+
+    let mut editor = match build_editor(load_catalog()) {
+        Ok(editor) => editor,
+        Err(e) => {
+            eprintln!(">>>> Error initializing the line editor: {}", e);
+            return ERROR_CODE_PARAM;
+        }
+    };
+
+    if let Some(path) = history_path().filter(|_| is_terminal) {
+        if let Some(dir) = path.parent() {
+            let _ = fs::create_dir_all(dir);
+        }
+        // A missing history file just means this is the first session
+        let _ = editor.load_history(&path);
+    }
+
+    // Lines of an entry whose brackets or strings are still open
+    let mut pending = String::new();
+    let mut succeeded = 0;
+    let mut failed = 0;
+    // Exit code of the first failing entry
+    let mut exit_code = 0;
+
+    loop {
+        let prompt = if pending.is_empty() {
+            PROMPT
+        } else {
+            CONTINUATION_PROMPT
+        };
+        let line = match read_line(&mut editor, &mut script, prompt) {
+            Ok(line) => line,
+            // Ctrl-C drops the entry being edited but keeps the session
+            Err(ReadlineError::Interrupted) => {
+                if !pending.is_empty() {
+                    pending.clear();
+                    eprintln!("(entry cancelled)");
+                }
+                continue;
+            }
+            // Ctrl-D, or the end of piped input
+            Err(ReadlineError::Eof) => {
+                if !pending.is_empty() {
+                    eprintln!(">>>> Discarding incomplete entry at end of input");
+                }
+                break;
+            }
+            Err(e) => {
+                eprintln!(">>>> Error reading input: {}", e);
+                break;
+            }
+        };
+
+        if !pending.is_empty() {
+            pending.push('\n');
+        }
+        pending.push_str(&line);
+        if !multiline::is_complete(&pending) {
+            continue;
+        }
+        let entry = std::mem::take(&mut pending);
+
+        let trimmed = entry.trim();
+        if trimmed.is_empty() {
+            continue;
+        }
+        let is_call = !trimmed.starts_with('.') && trimmed != "exit";
+        if is_terminal && !is_call {
+            save_history(&mut editor, &redact_text(trimmed));
+        } else if options.echo && !is_terminal {
+            for (i, line) in trimmed.lines().enumerate() {
+                let prompt = if i == 0 { PROMPT } else { CONTINUATION_PROMPT };
+                let _ = write_stdout(&format!("{}{}", prompt, line));
+            }
+        }
+
+        if trimmed == "exit" {
+            break;
+        }
+        let outcome = if trimmed.starts_with('.') {
+            // Meta-commands are handled locally and never reach the daemon
+            if let Some(recorder) = &mut session.recorder {
+                recorder.record_command(trimmed);
+            }
+            match run_meta_command(trimmed, &mut session, &mut editor) {
+                Flow::Continue => Ok(()),
+                Flow::Failed(code) => Err(code),
+                Flow::Exit => break,
+            }
+        } else if let Some(binding) = trimmed.strip_prefix("let ") {
+            run_let(trimmed, binding, &mut session)
+        } else {
+            execute_line(trimmed, trimmed, &mut session).map(|_| ())
+        };
+        if is_terminal && is_call {
+            // Saved once the arguments are resolved, so that positional
+            // values are redacted too. An entry whose arguments could not be
+            // resolved may hold them in clear and is not saved.
+            match session.last_call.take() {
+                Some((method, args)) => save_history(
+                    &mut editor,
+                    &redact_command(trimmed, &method, args.as_deref()),
+                ),
+                None if !has_positional_args(trimmed) => {
+                    save_history(&mut editor, &redact_text(trimmed))
+                }
+                None => {}
+            }
+        }
+        if std::mem::take(&mut session.catalog_changed) {
+            if let Some(helper) = editor.helper_mut() {
+                helper.set_catalog(Catalog::load());
+            }
+        }
+
+        match outcome {
+            Ok(()) => succeeded += 1,
+            Err(code) => {
+                failed += 1;
+                if exit_code == 0 {
+                    exit_code = code;
+                }
+                if options.fail_fast && !is_terminal {
+                    eprintln!(">>>> Stopping at the first error (--fail-fast)");
+                    break;
+                }
+            }
+        }
+    }
+
+    if is_terminal {
+        return 0;
+    }
+    eprintln!("Summary: {} succeeded, {} failed", succeeded, failed);
+    exit_code
+}
+
+/// `let <name> = <method> [args]` binds the result of a call,
+/// `let <name> = <reference>` a value from an earlier result.
+fn run_let(entry: &str, binding: &str, session: &mut Session) -> Result<(), i32> {
+    let Some((name, rhs)) = binding.split_once('=') else {
+        eprintln!(">>>> Usage: let <name> = <method> [JSON args] | let <name> = <$reference>");
+        return Err(ERROR_CODE_PARAM);
+    };
+    let (name, rhs) = (name.trim(), rhs.trim());
+    if !vars::is_valid_name(name) {
+        eprintln!(
+            ">>>> Invalid variable name '{}': use letters, digits and '_', not starting with a digit",
+            name
+        );
+        return Err(ERROR_CODE_PARAM);
+    }
+    let value = if rhs.starts_with('$') {
+        if let Some(recorder) = &mut session.recorder {
+            recorder.record_command(entry);
+        }
+        match session.vars.resolve_reference(rhs) {
+            Ok(value) => value,
+            Err(e) => {
+                eprintln!(">>>> {}", e);
+                return Err(ERROR_CODE_PARAM);
+            }
+        }
+    } else {
+        execute_line(entry, rhs, session)?
+    };
+    session.vars.bind(name, value);
+    Ok(())
+}
+
+/// Run one RPC call. On success the result is printed, recorded as the next
+/// `$N` and returned; on failure the exit code for it is returned. `entry` is
+/// the input as typed, for the recording.
+fn execute_line(entry: &str, trimmed: &str, session: &mut Session) -> Result<Value, i32> {
+    // Split off the method name; the rest of the line is the JSON argument
+    // blob, checked and mapped the same way as for `rpc`.
+    let (command, args_blob) = match trimmed.split_once(char::is_whitespace) {
+        Some((cmd, rest)) => (cmd.to_string(), rest.trim().to_string()),
+        None => (trimmed.to_string(), String::new()),
+    };
+    // References to earlier results ($_, $1, ${name.field}) become JSON literals
+    let args_blob = match session.vars.substitute(&args_blob) {
+        Ok(args_blob) => args_blob,
+        Err(e) => {
+            eprintln!(">>>> {}", e);
+            return Err(ERROR_CODE_PARAM);
+        }
+    };
+
+    let args_object_json = prepare_args(&command, &args_blob).inspect_err(|_| {
+        print_method_help_from_core(&command);
+    })?;
+    session.last_call = Some((command.clone(), args_object_json.clone()));
+
+    let started = Instant::now();
+    let mut result = dispatch::call(&command, args_object_json.as_deref());
+    if let Err(error) = &result {
+        if is_connection_error(error) {
+            eprintln!(">>>> Lost the connection to the daemon: {}", error);
+            match reconnect::reconnect(session.daemon_version.as_deref()) {
+                Ok(reconnected) => {
+                    eprintln!("     {}", reconnected.describe());
+                    session.daemon_version = reconnected.version;
+                    session.catalog_changed = true;
+                    // A mutating call may have reached the daemon before the
+                    // connection dropped; running it twice is up to the user
+                    if method_class(&command).is_mutating() {
+                        eprintln!(
+                            "     {} may change state and was not retried; run it again if needed",
+                            command
+                        );
+                    } else {
+                        result = dispatch::call(&command, args_object_json.as_deref());
+                    }
+                }
+                Err(e) => eprintln!(">>>> {}", e),
+            }
+        }
+    }
+    let elapsed = started.elapsed();
+    if let Some(recorder) = &mut session.recorder {
+        recorder.record_call(
+            entry,
+            &command,
+            args_object_json.as_deref(),
+            result.as_deref().map_err(ToString::to_string),
+            elapsed,
+        );
+    }
+    let value = match result {
+        Ok(result) => {
+            let _ = write_stdout(&render_result(&result, session.output));
+            let value = result_value(&result);
+            session.vars.push_result(value.clone());
+            Ok(value)
+        }
+        Err(e) if e.is_daemon_error() => {
+            eprintln!(">>>> Error calling RPC method: {}", e);
+            print_method_help_from_core(&command);
+            Err(e.code)
+        }
+        Err(e) => {
+            eprintln!(">>>> {}", e);
+            Err(e.code)
+        }
+    };
+    if session.timing {
+        eprintln!("({} ms)", elapsed.as_millis());
+    }
+    value
+}
@@ -0,0 +1,375 @@
+use super::vars::Variables;
+use super::{history_path, ReplEditor};
+use crate::catalog::Catalog;
+use crate::classify::describe_class;
+use crate::output::OutputFormat;
+use crate::profile::{active, active_profile, set_active_profile};
+use crate::reconnect::daemon_version;
+use crate::recording::Recorder;
+use crate::script::handle_script;
+use crate::{
+    best_suggestion, print_method_help_with_meta, write_stdout, ERROR_CODE_PARAM,
+    ERROR_CODE_SERVER_ERROR,
+};
+
+/// A REPL command handled locally. Every input line starting with `.` is a
+/// meta-command and is never sent to the daemon.
+pub struct MetaCommand {
+    pub name: &'static str,
+    pub usage: &'static str,
+    pub help: &'static str,
+    /// Fixed values of the argument, offered by Tab completion.
+    pub values: &'static [&'static str],
+}
+
+pub const META_COMMANDS: &[MetaCommand] = &[
+    MetaCommand {
+        name: ".help",
+        usage: "[method]",
+        help: "List the commands, or show the signature and examples of a method",
+        values: &[],
+    },
+    MetaCommand {
+        name: ".methods",
+        usage: "[pattern]",
+        help: "List methods, filtered by a substring or a glob such as get_*",
+        values: &[],
+    },
+    MetaCommand {
+        name: ".output",
+        usage: "[json|pretty|table]",
+        help: "Show or set how results are printed",
+        values: &OutputFormat::NAMES,
+    },
+    MetaCommand {
+        name: ".timing",
+        usage: "[on|off]",
+        help: "Show or set whether the duration of each call is printed",
+        values: &["on", "off"],
+    },
+    MetaCommand {
+        name: ".vars",
+        usage: "",
+        help: "List the results ($_, $1, ...) and the names bound with let",
+        values: &[],
+    },
+    MetaCommand {
+        name: ".connect",
+        usage: "<profile>",
+        help: "Switch to another connection profile",
+        values: &[],
+    },
+    MetaCommand {
+        name: ".reload",
+        usage: "",
+        help: "Refresh the method catalog from the daemon",
+        values: &[],
+    },
+    MetaCommand {
+        name: ".script",
+        usage: "<file>",
+        help: "Run a script file (see 'edamame_cli script --help')",
+        values: &[],
+    },
+    MetaCommand {
+        name: ".history",
+        usage: "[count]",
+        help: "Show the most recent history entries (all by default)",
+        values: &[],
+    },
+    MetaCommand {
+        name: ".clear",
+        usage: "",
+        help: "Clear the screen",
+        values: &[],
+    },
+    MetaCommand {
+        name: ".exit",
+        usage: "",
+        help: "Leave interactive mode (same as 'exit' or Ctrl-D)",
+        values: &[],
+    },
+];
+
+pub fn find_meta_command(name: &str) -> Option<&'static MetaCommand> {
+    META_COMMANDS.iter().find(|c| c.name == name)
+}
+
+/// Settings changed by meta-commands and values kept for the rest of the
+/// session.
+pub struct Session {
+    pub output: OutputFormat,
+    pub timing: bool,
+    pub vars: Variables,
+    /// Set by `interactive --record`.
+    pub recorder: Option<Recorder>,
+    /// Version of the connected daemon, to tell whether it came back on a
+    /// different one after a restart.
+    pub daemon_version: Option<String>,
+    /// Set when the catalog cache was refreshed behind the line editor's back.
+    pub catalog_changed: bool,
+    /// Method and resolved arguments of the last call, to redact the entry
+    /// saved to the history.
+    pub last_call: Option<(String, Option<String>)>,
+}
+
+impl Default for Session {
+    fn default() -> Self {
+        Self {
+            output: OutputFormat::Json,
+            timing: false,
+            vars: Variables::default(),
+            recorder: None,
+            daemon_version: None,
+            catalog_changed: false,
+            last_call: None,
+        }
+    }
+}
+
+/// Whether the REPL keeps reading input after a meta-command.
+pub enum Flow {
+    Continue,
+    /// The command failed with this exit code; the REPL carries on unless
+    /// it runs a script with `--fail-fast`.
+    Failed(i32),
+    Exit,
+}
+
+pub fn run_meta_command(line: &str, session: &mut Session, editor: &mut ReplEditor) -> Flow {
+    let (name, arg) = match line.split_once(char::is_whitespace) {
+        Some((name, arg)) => (name, arg.trim()),
+        None => (line, ""),
+    };
+
+    let outcome = match name {
+        ".help" => help(arg, editor),
+        ".methods" => methods(arg, editor),
+        ".output" => {
+            if arg.is_empty() {
+                let _ = write_stdout(&format!("Output format: {}", session.output));
+            } else {
+                match arg.parse::<OutputFormat>() {
+                    Ok(format) => {
+                        session.output = format;
+                        let _ = write_stdout(&format!("Output format: {}", format));
+                    }
+                    Err(e) => {
+                        eprintln!(">>>> {}", e);
+                        return Flow::Failed(ERROR_CODE_PARAM);
+                    }
+                }
+            }
+            Ok(())
+        }
+        ".timing" => {
+            match arg {
+                "" => {}
+                "on" => session.timing = true,
+                "off" => session.timing = false,
+                _ => {
+                    eprintln!(">>>> Usage: .timing [on|off]");
+                    return Flow::Failed(ERROR_CODE_PARAM);
+                }
+            }
+            let _ = write_stdout(&format!(
+                "Timing: {}",
+                if session.timing { "on" } else { "off" }
+            ));
+            Ok(())
+        }
+        ".vars" => {
+            let lines = session.vars.describe();
+            if lines.is_empty() {
+                let _ = write_stdout("No results yet");
+            }
+            for line in lines {
+                let _ = write_stdout(&line);
+            }
+            Ok(())
+        }
+        ".connect" => connect(arg, session, editor),
+        ".reload" => reload(editor),
+        ".script" => {
+            if arg.is_empty() {
+                eprintln!(">>>> Usage: .script <file>");
+                return Flow::Failed(ERROR_CODE_PARAM);
+            }
+            match handle_script(arg, &[]) {
+                0 => Ok(()),
+                code => Err(code),
+            }
+        }
+        ".history" => history(arg, editor),
+        ".clear" => {
+            let _ = editor.clear_screen();
+            Ok(())
+        }
+        ".exit" => return Flow::Exit,
+        _ => {
+            eprintln!(">>>> Unknown command '{}'", name);
+            let names: Vec<String> = META_COMMANDS.iter().map(|c| c.name.to_string()).collect();
+            if let Some(suggestion) = best_suggestion(name, &names) {
+                eprintln!("     Did you mean '{}' ?", suggestion);
+            }
+            eprintln!("     Type .help for the list of commands");
+            Err(ERROR_CODE_PARAM)
+        }
+    };
+    match outcome {
+        Ok(()) => Flow::Continue,
+        Err(code) => Flow::Failed(code),
+    }
+}
+
+fn help(method: &str, editor: &mut ReplEditor) -> Result<(), i32> {
+    if method.is_empty() {
+        let _ = write_stdout("Commands:");
+        for command in META_COMMANDS {
+            let _ = write_stdout(&format!(
+                "  {:<30} {}",
+                format!("{} {}", command.name, command.usage).trim_end(),
+                command.help
+            ));
+        }
+        let _ =
+            write_stdout("\nAnything else is an RPC call: <method> [JSON args object or array]");
+        let _ = write_stdout("  e.g.  get_score");
+        let _ = write_stdout("        get_score {\"complete_only\": false}");
+        let _ = write_stdout("\nEvery result is kept as $1, $2, ... and the latest one as $_.");
+        let _ = write_stdout("Bind a name with 'let s = get_score' and reuse values in arguments:");
+        let _ = write_stdout("        get_threat_info {\"name\": $_[0].name}");
+        let _ = write_stdout("        set_goal {\"stars\": ${s.stars}}");
+        return Ok(());
+    }
+
+    let Some(helper) = editor.helper() else {
+        return Ok(());
+    };
+    if !helper.is_method(method) {
+        eprintln!(">>>> Unknown method '{}'", method);
+        if let Some(suggestion) = best_suggestion(method, &helper.methods()) {
+            eprintln!("     Did you mean '{}' ?", suggestion);
+        }
+        return Err(ERROR_CODE_PARAM);
+    }
+    match helper.meta_or_fetch(method) {
+        Ok((return_type, args_meta)) => {
+            print_method_help_with_meta(method, &return_type, &args_meta);
+            Ok(())
+        }
+        Err(e) => {
+            eprintln!(">>>> {}", e);
+            Err(ERROR_CODE_SERVER_ERROR)
+        }
+    }
+}
+
+fn methods(pattern: &str, editor: &mut ReplEditor) -> Result<(), i32> {
+    let Some(helper) = editor.helper() else {
+        return Ok(());
+    };
+    let matcher: Box<dyn Fn(&str) -> bool> = if pattern.contains(['*', '?', '[']) {
+        match glob::Pattern::new(pattern) {
+            Ok(glob) => Box::new(move |m| glob.matches(m)),
+            Err(e) => {
+                eprintln!(">>>> Invalid pattern '{}': {}", pattern, e);
+                return Err(ERROR_CODE_PARAM);
+            }
+        }
+    } else {
+        Box::new(|m| m.contains(pattern))
+    };
+
+    let methods: Vec<String> = helper
+        .methods()
+        .into_iter()
+        .filter(|m| matcher(m))
+        .collect();
+    for method in &methods {
+        let _ = write_stdout(&format!("  {:<50} {}", method, describe_class(method)));
+    }
+    let _ = write_stdout(&format!("({} methods)", methods.len()));
+    Ok(())
+}
+
+fn connect(name: &str, session: &mut Session, editor: &mut ReplEditor) -> Result<(), i32> {
+    if name.is_empty() {
+        let profile = active();
+        let _ = write_stdout(&format!(
+            "Connected to profile '{}' ({})",
+            profile.name, profile.target
+        ));
+        return Ok(());
+    }
+
+    let previous = active_profile();
+    if let Err(e) = set_active_profile(name) {
+        eprintln!(">>>> {}", e);
+        return Err(ERROR_CODE_PARAM);
+    }
+    // Only switch once the daemon behind the new profile answers
+    let mut catalog = Catalog::load();
+    if let Err(e) = catalog.refresh() {
+        eprintln!(">>>> {}", e);
+        eprintln!(">>>> Staying on profile '{}'", previous);
+        let _ = set_active_profile(&previous);
+        return Err(ERROR_CODE_SERVER_ERROR);
+    }
+    catalog.save();
+    session.daemon_version = daemon_version();
+    let method_count = catalog.methods().len();
+    if let Some(helper) = editor.helper_mut() {
+        helper.set_catalog(catalog);
+    }
+
+    // Each profile has its own history
+    let _ = editor.clear_history();
+    if let Some(path) = history_path() {
+        let _ = editor.load_history(&path);
+    }
+
+    let profile = active();
+    let _ = write_stdout(&format!(
+        "Connected to profile '{}' ({}), {} methods available",
+        profile.name, profile.target, method_count
+    ));
+    Ok(())
+}
+
+fn reload(editor: &mut ReplEditor) -> Result<(), i32> {
+    let mut catalog = Catalog::load();
+    if let Err(e) = catalog.refresh() {
+        eprintln!(">>>> {}", e);
+        return Err(ERROR_CODE_SERVER_ERROR);
+    }
+    catalog.save();
+    let _ = write_stdout(&format!(
+        "Reloaded the catalog: {} methods available",
+        catalog.methods().len()
+    ));
+    if let Some(helper) = editor.helper_mut() {
+        helper.set_catalog(catalog);
+    }
+    Ok(())
+}
+
+fn history(count: &str, editor: &mut ReplEditor) -> Result<(), i32> {
+    let entries: Vec<&String> = editor.history().iter().collect();
+    let count = if count.is_empty() {
+        entries.len()
+    } else {
+        match count.parse::<usize>() {
+            Ok(count) => count.min(entries.len()),
+            Err(_) => {
+                eprintln!(">>>> Usage: .history [count]");
+                return Err(ERROR_CODE_PARAM);
+            }
+        }
+    };
+    let start = entries.len() - count;
+    for (i, entry) in entries.iter().enumerate().skip(start) {
+        let _ = write_stdout(&format!("{:>5}  {}", i + 1, entry));
+    }
+    Ok(())
+}
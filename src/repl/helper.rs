@@ -0,0 +1,272 @@
+use super::commands::{find_meta_command, META_COMMANDS};
+use crate::catalog::{Catalog, MethodMeta};
+use crate::format_signature;
+use crate::profile::profile_names;
+use rustyline::completion::{Completer, Pair};
+use rustyline::highlight::Highlighter;
+use rustyline::hint::{Hint, Hinter};
+use rustyline::validate::Validator;
+use rustyline::{Context, Helper};
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::collections::HashSet;
+
+/// Words the REPL understands besides method names.
+const BUILTIN_COMMANDS: &[&str] = &["exit", "let"];
+
+/// rustyline helper of the REPL: Tab completion of method names and argument
+/// names, and an inline hint with the signature of the method being typed.
+pub struct ReplHelper {
+    catalog: RefCell<Catalog>,
+    /// Methods whose metadata could not be fetched, so that hints do not
+    /// query the daemon again on every keystroke.
+    no_meta: RefCell<HashSet<String>>,
+}
+
+impl ReplHelper {
+    pub fn new(catalog: Catalog) -> Self {
+        Self {
+            catalog: RefCell::new(catalog),
+            no_meta: RefCell::new(HashSet::new()),
+        }
+    }
+
+    /// Swap in a catalog fetched for another profile or after `.reload`.
+    pub fn set_catalog(&mut self, catalog: Catalog) {
+        self.catalog = RefCell::new(catalog);
+        self.no_meta.borrow_mut().clear();
+    }
+
+    /// Method names, sorted.
+    pub fn methods(&self) -> Vec<String> {
+        self.catalog.borrow().methods().to_vec()
+    }
+
+    pub fn meta_or_fetch(&self, method: &str) -> Result<MethodMeta, String> {
+        self.catalog.borrow_mut().meta_or_fetch(method)
+    }
+
+    pub fn is_method(&self, name: &str) -> bool {
+        self.catalog
+            .borrow()
+            .methods()
+            .binary_search_by(|m| m.as_str().cmp(name))
+            .is_ok()
+    }
+
+    /// Metadata of a known method, fetched from the daemon once and cached.
+    fn meta(&self, method: &str) -> Option<MethodMeta> {
+        if !self.is_method(method) || self.no_meta.borrow().contains(method) {
+            return None;
+        }
+        match self.catalog.borrow_mut().meta_or_fetch(method) {
+            Ok(meta) => Some(meta),
+            Err(_) => {
+                self.no_meta.borrow_mut().insert(method.to_string());
+                None
+            }
+        }
+    }
+
+    fn complete_method(&self, prefix: &str) -> Vec<Pair> {
+        let catalog = self.catalog.borrow();
+        BUILTIN_COMMANDS
+            .iter()
+            .map(|c| c.to_string())
+            .chain(META_COMMANDS.iter().map(|c| c.name.to_string()))
+            .chain(catalog.methods().iter().cloned())
+            .filter(|name| name.starts_with(prefix))
+            .map(|name| Pair {
+                display: name.clone(),
+                replacement: format!("{} ", name),
+            })
+            .collect()
+    }
+
+    /// Argument of a meta-command: method names for `.help` and `.methods`,
+    /// profile names for `.connect`, the fixed values of the others.
+    fn complete_meta_arg(&self, command: &str, line: &str, pos: usize) -> (usize, Vec<Pair>) {
+        let start = line[..pos]
+            .rfind(char::is_whitespace)
+            .map(|i| i + 1)
+            .unwrap_or(0);
+        let prefix = &line[start..pos];
+        let values: Vec<String> = match command {
+            ".help" | ".methods" => self.methods(),
+            ".connect" => profile_names(),
+            _ => find_meta_command(command)
+                .map(|c| c.values.iter().map(|v| v.to_string()).collect())
+                .unwrap_or_default(),
+        };
+        let candidates = values
+            .into_iter()
+            .filter(|v| v.starts_with(prefix))
+            .map(|v| Pair {
+                display: v.clone(),
+                replacement: v,
+            })
+            .collect();
+        (start, candidates)
+    }
+
+    /// Argument names of `method` that fit a key being typed at `pos` in the
+    /// JSON blob. Returns the start of the replaced text and the candidates.
+    fn complete_arg_name(&self, method: &str, line: &str, pos: usize) -> (usize, Vec<Pair>) {
+        let before = &line[..pos];
+        let word_start = before
+            .rfind(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+            .map(|i| i + 1)
+            .unwrap_or(0);
+        let prefix = &before[word_start..];
+        let start = if before[..word_start].ends_with('"') {
+            word_start - 1
+        } else {
+            word_start
+        };
+
+        // Only complete in key position: right after '{' or ','
+        let key_position = before[..start].trim_end().ends_with(['{', ',']);
+        if !key_position {
+            return (pos, Vec::new());
+        }
+
+        let Some((_, args_meta)) = self.meta(method) else {
+            return (pos, Vec::new());
+        };
+        let candidates = args_meta
+            .iter()
+            .filter(|(name, _)| name.starts_with(prefix))
+            .filter(|(name, _)| !line.contains(&format!("\"{}\"", name)))
+            .map(|(name, arg_type)| Pair {
+                display: format!("{}: {}", name, arg_type),
+                replacement: format!("\"{}\": ", name),
+            })
+            .collect();
+        (start, candidates)
+    }
+}
+
+/// Splits the line into the method name and the offset where its arguments
+/// start, if the method name is complete (followed by whitespace).
+fn split_method(line: &str) -> Option<(&str, usize)> {
+    let trimmed_start = line.len() - line.trim_start().len();
+    let rest = &line[trimmed_start..];
+    let end = rest.find(char::is_whitespace)?;
+    Some((&rest[..end], trimmed_start + end))
+}
+
+impl Completer for ReplHelper {
+    type Candidate = Pair;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<Pair>)> {
+        match split_method(line) {
+            Some((command, args_start)) if pos > args_start && command.starts_with('.') => {
+                Ok(self.complete_meta_arg(command, line, pos))
+            }
+            Some((method, args_start)) if pos > args_start => {
+                Ok(self.complete_arg_name(method, line, pos))
+            }
+            _ => {
+                let start = line.len() - line.trim_start().len();
+                let prefix = line[start..pos.max(start)].trim_end();
+                Ok((start, self.complete_method(prefix)))
+            }
+        }
+    }
+}
+
+/// Inline hint. Signature hints are informational only: unlike the rest of a
+/// method name, they are not inserted when the hint is accepted.
+pub struct ReplHint {
+    display: String,
+    completion: Option<String>,
+}
+
+impl Hint for ReplHint {
+    fn display(&self) -> &str {
+        &self.display
+    }
+
+    fn completion(&self) -> Option<&str> {
+        self.completion.as_deref()
+    }
+}
+
+impl Hinter for ReplHelper {
+    type Hint = ReplHint;
+
+    fn hint(&self, line: &str, pos: usize, _ctx: &Context<'_>) -> Option<ReplHint> {
+        if pos < line.len() || line.trim().is_empty() {
+            return None;
+        }
+        match split_method(line) {
+            // Show the signature until the user starts typing arguments
+            Some((method, args_start)) if line[args_start..].trim().is_empty() => {
+                self.signature_hint(method, "")
+            }
+            Some(_) => None,
+            None => {
+                let prefix = line.trim_start();
+                if self.is_method(prefix) || find_meta_command(prefix).is_some() {
+                    return self.signature_hint(prefix, "  ");
+                }
+                // Still typing the name: offer the rest of a unique match
+                let catalog = self.catalog.borrow();
+                let mut matches = META_COMMANDS
+                    .iter()
+                    .map(|c| c.name)
+                    .chain(catalog.methods().iter().map(String::as_str))
+                    .filter(|m| m.starts_with(prefix));
+                let first = matches.next()?;
+                if matches.next().is_some() {
+                    return None;
+                }
+                let rest = first[prefix.len()..].to_string();
+                Some(ReplHint {
+                    display: rest.clone(),
+                    completion: Some(rest),
+                })
+            }
+        }
+    }
+}
+
+impl ReplHelper {
+    fn signature_hint(&self, method: &str, separator: &str) -> Option<ReplHint> {
+        if let Some(command) = find_meta_command(method) {
+            let usage = if command.usage.is_empty() {
+                String::new()
+            } else {
+                format!("{} ", command.usage)
+            };
+            return Some(ReplHint {
+                display: format!("{}# {}- {}", separator, usage, command.help),
+                completion: None,
+            });
+        }
+        let (return_type, args_meta) = self.meta(method)?;
+        Some(ReplHint {
+            display: format!(
+                "{}# {}",
+                separator,
+                format_signature(method, &return_type, &args_meta)
+            ),
+            completion: None,
+        })
+    }
+}
+
+impl Highlighter for ReplHelper {
+    fn highlight_hint<'h>(&self, hint: &'h str) -> Cow<'h, str> {
+        Cow::Owned(format!("\x1b[2m{}\x1b[0m", hint))
+    }
+}
+
+impl Validator for ReplHelper {}
+
+impl Helper for ReplHelper {}
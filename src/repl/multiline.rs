@@ -0,0 +1,72 @@
+/// Whether a REPL entry can be executed: every `{`/`[` is closed and no
+/// string literal is left open. Surplus closing brackets count as complete
+/// so that the JSON parser reports them instead of waiting for more input.
+pub fn is_complete(input: &str) -> bool {
+    let mut depth: usize = 0;
+    let mut in_string = false;
+    let mut escaped = false;
+
+    for c in input.chars() {
+        if in_string {
+            if escaped {
+                escaped = false;
+            } else if c == '\\' {
+                escaped = true;
+            } else if c == '"' {
+                in_string = false;
+            }
+            continue;
+        }
+        match c {
+            '"' => in_string = true,
+            '{' | '[' => depth += 1,
+            '}' | ']' => {
+                if depth == 0 {
+                    return true;
+                }
+                depth -= 1;
+            }
+            _ => {}
+        }
+    }
+    depth == 0 && !in_string
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn waits_for_open_brackets_and_strings() {
+        for input in [
+            "set_demo_mode {",
+            "m {\"a\": [1, 2",
+            "m {\"a\": \"unterminated",
+            "m {\"a\": \"ends with an escaped quote \\\"",
+            "m [{}, [",
+        ] {
+            assert!(!is_complete(input), "{}", input);
+        }
+    }
+
+    #[test]
+    fn accepts_balanced_entries() {
+        for input in [
+            "",
+            "get_score",
+            "m {\"a\": [1, {\"b\": 2}]}",
+            "m {\"a\": \"{[ inside a string\"}",
+            "m {\"a\": \"escaped \\\" quote and backslash \\\\\"}",
+            "m [\"\\\\\"]",
+        ] {
+            assert!(is_complete(input), "{}", input);
+        }
+    }
+
+    #[test]
+    fn leaves_surplus_closers_to_the_parser() {
+        assert!(is_complete("m }"));
+        assert!(is_complete("m {\"a\": 1}}"));
+        assert!(is_complete("m ] {"));
+    }
+}
@@ -0,0 +1,322 @@
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+/// Values shorter than this are shown in full by `.vars`.
+const MAX_PREVIEW_WIDTH: usize = 60;
+
+/// Results of the session and the names bound with `let`. Results are
+/// numbered from 1 in call order (`$1`, `$2`, ...); `$_` is the latest one.
+#[derive(Default)]
+pub struct Variables {
+    results: Vec<Value>,
+    named: BTreeMap<String, Value>,
+}
+
+/// Whether `name` can be bound with `let`.
+pub fn is_valid_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+        && name != "_"
+}
+
+impl Variables {
+    /// Record a call result as the next `$N`.
+    pub fn push_result(&mut self, value: Value) {
+        self.results.push(value);
+    }
+
+    pub fn bind(&mut self, name: &str, value: Value) {
+        self.named.insert(name.to_string(), value);
+    }
+
+    fn lookup(&self, name: &str) -> Result<&Value, String> {
+        if name == "_" {
+            return self
+                .results
+                .last()
+                .ok_or_else(|| "$_ is not set: no call has returned a result yet".to_string());
+        }
+        if let Ok(index) = name.parse::<usize>() {
+            return index
+                .checked_sub(1)
+                .and_then(|i| self.results.get(i))
+                .ok_or_else(|| {
+                    format!(
+                        "${} is not set: {} result(s) so far",
+                        index,
+                        self.results.len()
+                    )
+                });
+        }
+        self.named
+            .get(name)
+            .ok_or_else(|| format!("Unknown variable '${}', see .vars", name))
+    }
+
+    /// Resolve a single reference such as `$_`, `$2.items[0]` or `${s.stars}`.
+    /// The whole input must be the reference.
+    pub fn resolve_reference(&self, input: &str) -> Result<Value, String> {
+        let input = input.trim();
+        match parse_reference(input, 0) {
+            Some((reference, end)) if end == input.len() => self.evaluate(&reference),
+            _ => Err(format!("Invalid reference '{}'", input)),
+        }
+    }
+
+    /// Replace every reference outside JSON strings in an argument blob with
+    /// the JSON text of its value.
+    pub fn substitute(&self, blob: &str) -> Result<String, String> {
+        let mut output = String::with_capacity(blob.len());
+        let mut in_string = false;
+        let mut escaped = false;
+        let mut i = 0;
+        while i < blob.len() {
+            let c = blob[i..].chars().next().unwrap_or_default();
+            if in_string {
+                if escaped {
+                    escaped = false;
+                } else if c == '\\' {
+                    escaped = true;
+                } else if c == '"' {
+                    in_string = false;
+                }
+            } else if c == '"' {
+                in_string = true;
+            } else if c == '$' {
+                let (reference, end) = parse_reference(blob, i).ok_or_else(|| {
+                    format!("Invalid reference at column {}: {}", i + 1, &blob[i..])
+                })?;
+                output.push_str(&self.evaluate(&reference)?.to_string());
+                i = end;
+                continue;
+            }
+            output.push(c);
+            i += c.len_utf8();
+        }
+        Ok(output)
+    }
+
+    fn evaluate(&self, reference: &Reference) -> Result<Value, String> {
+        let mut value = self.lookup(&reference.name)?;
+        let mut path = format!("${}", reference.name);
+        for segment in &reference.path {
+            let next = match segment {
+                Segment::Field(field) => {
+                    path.push_str(&format!(".{}", field));
+                    value.get(field.as_str())
+                }
+                Segment::Index(index) => {
+                    path.push_str(&format!("[{}]", index));
+                    value.get(*index)
+                }
+            };
+            value = next.ok_or_else(|| format!("{} does not exist", path))?;
+        }
+        Ok(value.clone())
+    }
+
+    /// Lines of the `.vars` listing.
+    pub fn describe(&self) -> Vec<String> {
+        let mut lines = Vec::new();
+        if let Some(last) = self.results.last() {
+            lines.push(format!("  {:<12} {}", "$_", preview(last)));
+        }
+        for (i, value) in self.results.iter().enumerate() {
+            lines.push(format!(
+                "  {:<12} {}",
+                format!("${}", i + 1),
+                preview(value)
+            ));
+        }
+        for (name, value) in &self.named {
+            lines.push(format!("  {:<12} {}", format!("${}", name), preview(value)));
+        }
+        lines
+    }
+}
+
+fn preview(value: &Value) -> String {
+    let text = value.to_string();
+    if text.chars().count() > MAX_PREVIEW_WIDTH {
+        let truncated: String = text.chars().take(MAX_PREVIEW_WIDTH - 1).collect();
+        format!("{}…", truncated)
+    } else {
+        text
+    }
+}
+
+enum Segment {
+    Field(String),
+    Index(usize),
+}
+
+struct Reference {
+    name: String,
+    path: Vec<Segment>,
+}
+
+/// Parse a reference starting at the `$` at `start`. Returns it and the
+/// offset right after it.
+fn parse_reference(input: &str, start: usize) -> Option<(Reference, usize)> {
+    let bytes = input.as_bytes();
+    if bytes.get(start) != Some(&b'$') {
+        return None;
+    }
+    if bytes.get(start + 1) == Some(&b'{') {
+        let close = start + 2 + input[start + 2..].find('}')?;
+        let (reference, end) = parse_path(&input[..close], start + 2)?;
+        return (end == close).then_some((reference, close + 1));
+    }
+    parse_path(input, start + 1)
+}
+
+/// Parse `name(.field|[index])*` from `start`.
+fn parse_path(input: &str, start: usize) -> Option<(Reference, usize)> {
+    let bytes = input.as_bytes();
+    let is_ident = |b: u8| b.is_ascii_alphanumeric() || b == b'_';
+
+    let mut end = start;
+    while end < bytes.len() && is_ident(bytes[end]) {
+        end += 1;
+    }
+    let name = &input[start..end];
+    let valid = !name.is_empty()
+        && (name == "_" || name.bytes().all(|b| b.is_ascii_digit()) || is_valid_name(name));
+    if !valid {
+        return None;
+    }
+
+    let mut path = Vec::new();
+    loop {
+        match bytes.get(end) {
+            Some(b'.') if bytes.get(end + 1).is_some_and(|b| is_ident(*b)) => {
+                let field_start = end + 1;
+                end = field_start;
+                while end < bytes.len() && is_ident(bytes[end]) {
+                    end += 1;
+                }
+                path.push(Segment::Field(input[field_start..end].to_string()));
+            }
+            Some(b'[') => {
+                let close = end + input[end..].find(']')?;
+                let index = input[end + 1..close].trim().parse::<usize>().ok()?;
+                path.push(Segment::Index(index));
+                end = close + 1;
+            }
+            _ => break,
+        }
+    }
+    Some((
+        Reference {
+            name: name.to_string(),
+            path,
+        },
+        end,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn session() -> Variables {
+        let mut vars = Variables::default();
+        vars.push_result(json!({"stars": 3, "items": [{"id": "a"}, {"id": "b"}]}));
+        vars.push_result(json!("latest"));
+        vars.bind("s", json!({"stars": 4}));
+        vars
+    }
+
+    #[test]
+    fn parses_references() {
+        for (input, start, name, segments, end) in [
+            ("$_", 0, "_", 0, 2),
+            ("$1.items[1].id", 0, "1", 3, 14),
+            ("x $s.stars,", 2, "s", 1, 10),
+            ("${s.stars}x", 0, "s", 1, 10),
+            ("$1[ 0 ]", 0, "1", 1, 7),
+            // A trailing dot is not part of the reference
+            ("$s.", 0, "s", 0, 2),
+        ] {
+            let (reference, parsed_end) = parse_reference(input, start).unwrap();
+            assert_eq!(reference.name, name, "{}", input);
+            assert_eq!(reference.path.len(), segments, "{}", input);
+            assert_eq!(parsed_end, end, "{}", input);
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_references() {
+        for input in ["$", "$-1", "${s", "${s.stars x}", "$1[x]", "$1[0", "s"] {
+            assert!(parse_reference(input, 0).is_none(), "{}", input);
+        }
+    }
+
+    #[test]
+    fn resolves_references() {
+        let vars = session();
+        assert_eq!(vars.resolve_reference("$_"), Ok(json!("latest")));
+        assert_eq!(vars.resolve_reference(" $1.items[1].id "), Ok(json!("b")));
+        assert_eq!(vars.resolve_reference("${s.stars}"), Ok(json!(4)));
+        assert!(vars.resolve_reference("$s extra").is_err());
+    }
+
+    #[test]
+    fn reports_missing_values() {
+        let vars = session();
+        for (input, message) in [
+            ("$3", "$3 is not set: 2 result(s) so far"),
+            ("$0", "$0 is not set"),
+            ("$1.items[5]", "$1.items[5] does not exist"),
+            ("$1.stars.count", "$1.stars.count does not exist"),
+            ("$nope", "Unknown variable '$nope'"),
+        ] {
+            let error = vars.resolve_reference(input).unwrap_err();
+            assert!(error.contains(message), "{}: {}", input, error);
+        }
+        let error = Variables::default().resolve_reference("$_").unwrap_err();
+        assert!(error.contains("$_ is not set"), "{}", error);
+    }
+
+    #[test]
+    fn substitutes_references_outside_strings() {
+        let vars = session();
+        assert_eq!(
+            vars.substitute(r#"{"stars": $s.stars, "id": $1.items[0].id}"#),
+            Ok(r#"{"stars": 4, "id": "a"}"#.to_string())
+        );
+        assert_eq!(
+            vars.substitute(r#"["$_", "\"$s", $_]"#),
+            Ok(r#"["$_", "\"$s", "latest"]"#.to_string())
+        );
+        assert_eq!(
+            vars.substitute(r#"{"é": ${s}}"#),
+            Ok(r#"{"é": {"stars":4}}"#.to_string())
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_substitutions() {
+        let vars = session();
+        assert_eq!(
+            vars.substitute("[1, $]"),
+            Err("Invalid reference at column 5: $]".to_string())
+        );
+        assert!(vars
+            .substitute("[$9]")
+            .unwrap_err()
+            .contains("$9 is not set"));
+    }
+
+    #[test]
+    fn validates_names() {
+        for name in ["s", "_tmp", "score2"] {
+            assert!(is_valid_name(name), "{}", name);
+        }
+        for name in ["", "_", "2x", "a-b", "é"] {
+            assert!(!is_valid_name(name), "{}", name);
+        }
+    }
+}
@@ -0,0 +1,324 @@
+use crate::args::prepare_args;
+use crate::catalog::Catalog;
+use crate::output::result_value;
+use crate::{dispatch, write_stdout, ERROR_CODE_PARAM, ERROR_CODE_SCRIPT, ERROR_CODE_SERVER_ERROR};
+use rhai::serde::{from_dynamic, to_dynamic};
+use rhai::{Array, Dynamic, Engine, EvalAltResult, Position, Scope};
+use serde_json::{json, Value};
+use std::cell::{Cell, RefCell};
+use std::fs;
+use std::rc::Rc;
+
+type ScriptResult<T> = Result<T, Box<EvalAltResult>>;
+
+/// Limits of the engine, so that a runaway script stops with an error
+/// instead of spinning or exhausting memory.
+const MAX_OPERATIONS: u64 = 50_000_000;
+const MAX_CALL_LEVELS: usize = 64;
+const MAX_EXPR_DEPTH: usize = 64;
+const MAX_STRING_SIZE: usize = 10 * 1024 * 1024;
+const MAX_ARRAY_SIZE: usize = 1_000_000;
+const MAX_MAP_SIZE: usize = 1_000_000;
+
+/// Highest code `exit()` accepts: exit statuses are a byte.
+const MAX_EXIT_CODE: i32 = 255;
+
+/// Field set on the errors of `call_error`, so that a map thrown by the
+/// script itself (`throw #{code: 0}`) does not choose the exit code.
+const CALL_ERROR_MARKER: &str = "_call_error";
+
+/// Error raised in the script by `rpc()`, `methods()` and `info()`. Scripts
+/// can catch it as a map with `method`, `code` and `message` fields; when it
+/// is not caught, `code` is the exit code of the script.
+fn call_error(method: &str, code: i32, message: &str) -> Box<EvalAltResult> {
+    let value = json!({
+        "method": method,
+        "code": code,
+        "message": message,
+        CALL_ERROR_MARKER: true,
+    });
+    let value = to_dynamic(value).unwrap_or_else(|_| Dynamic::from(message.to_string()));
+    EvalAltResult::ErrorRuntime(value, Position::NONE).into()
+}
+
+fn to_value(value: &Dynamic) -> ScriptResult<Value> {
+    from_dynamic::<Value>(value)
+}
+
+/// `rpc(method)` / `rpc(method, args)`. The arguments are a map, an array of
+/// positional values or a JSON string, and go through the same checks as
+/// `rpc` on the command line. Returns the result as a script value.
+fn rpc(method: &str, args: &Dynamic) -> ScriptResult<Dynamic> {
+    let json_args = if args.is_unit() {
+        String::new()
+    } else if let Some(text) = args.read_lock::<rhai::ImmutableString>() {
+        text.to_string()
+    } else {
+        to_value(args)?.to_string()
+    };
+    let args = prepare_args(method, &json_args)
+        .map_err(|code| call_error(method, code, "invalid arguments"))?;
+    let result = dispatch::call(method, args.as_deref())
+        .map_err(|e| call_error(method, e.code, &e.message))?;
+    to_dynamic(result_value(&result))
+}
+
+/// The cached catalog, refreshed from the daemon when it is stale.
+fn fresh_catalog(catalog: &RefCell<Catalog>) -> ScriptResult<()> {
+    let mut catalog = catalog.borrow_mut();
+    if !catalog.is_fresh() {
+        catalog
+            .refresh()
+            .map_err(|e| call_error("methods", ERROR_CODE_SERVER_ERROR, &e))?;
+        catalog.save();
+    }
+    Ok(())
+}
+
+/// An engine with the CLI functions registered:
+///
+/// - `rpc(method)`, `rpc(method, args)`: call a method
+/// - `methods()`: names of the daemon methods
+/// - `info(method)`: `#{method, returns, args: [#{name, type}]}`
+/// - `to_json(value)`, `to_json_pretty(value)`, plus the built-in
+///   `parse_json(text)`
+/// - `print(...)` on stdout and `debug(...)` on stderr
+/// - `exit(code)`: stop with an exit code, kept in `exit_code`
+///
+/// The engine is built without module support, so scripts cannot `import`
+/// files, and it has no other filesystem or network functions: the daemon
+/// connection of the active profile is all a script can reach. Operations,
+/// call depth and the size of strings, arrays and maps are limited.
+fn build_engine(catalog: Rc<RefCell<Catalog>>, exit_code: Rc<Cell<i32>>) -> Engine {
+    let mut engine = Engine::new();
+    engine
+        .set_max_operations(MAX_OPERATIONS)
+        .set_max_call_levels(MAX_CALL_LEVELS)
+        .set_max_expr_depths(MAX_EXPR_DEPTH, MAX_EXPR_DEPTH)
+        .set_max_string_size(MAX_STRING_SIZE)
+        .set_max_array_size(MAX_ARRAY_SIZE)
+        .set_max_map_size(MAX_MAP_SIZE);
+    engine.on_print(|text| {
+        let _ = write_stdout(text);
+    });
+    engine.on_debug(|text, _source, position| {
+        if position.is_none() {
+            eprintln!("{}", text);
+        } else {
+            eprintln!("[line {}] {}", position.line().unwrap_or(0), text);
+        }
+    });
+
+    engine.register_fn("rpc", |method: &str| rpc(method, &Dynamic::UNIT));
+    engine.register_fn("rpc", |method: &str, args: Dynamic| rpc(method, &args));
+
+    let methods_catalog = catalog.clone();
+    engine.register_fn("methods", move || -> ScriptResult<Array> {
+        fresh_catalog(&methods_catalog)?;
+        Ok(methods_catalog
+            .borrow()
+            .methods()
+            .iter()
+            .map(|m| Dynamic::from(m.clone()))
+            .collect())
+    });
+    engine.register_fn("info", move |method: &str| -> ScriptResult<Dynamic> {
+        let mut catalog = catalog.borrow_mut();
+        let cached = catalog.meta(method).is_some();
+        let (returns, args) = catalog
+            .meta_or_fetch(method)
+            .map_err(|e| call_error(method, ERROR_CODE_SERVER_ERROR, &e))?;
+        if !cached {
+            catalog.save();
+        }
+        let args: Vec<Value> = args
+            .into_iter()
+            .map(|(name, arg_type)| json!({ "name": name, "type": arg_type }))
+            .collect();
+        to_dynamic(json!({ "method": method, "returns": returns, "args": args }))
+    });
+
+    // The engine turns the built-in exit(value) into a normal end of the
+    // script, so the code is passed on the side. Other values would end the
+    // script with 0 through the built-in, so they are errors.
+    engine.register_fn("exit", move |code: rhai::INT| -> ScriptResult<()> {
+        let code = i32::try_from(code)
+            .ok()
+            .filter(|code| (0..=MAX_EXIT_CODE).contains(code))
+            .ok_or_else(|| {
+                format!(
+                    "exit code {} is out of range, expected 0 to {}",
+                    code, MAX_EXIT_CODE
+                )
+            })?;
+        exit_code.set(code);
+        Err(EvalAltResult::Exit(Dynamic::from(code), Position::NONE).into())
+    });
+    engine.register_fn("exit", |value: Dynamic| -> ScriptResult<()> {
+        Err(format!("exit() expects an integer code, got {}", value.type_name()).into())
+    });
+
+    engine.register_fn("to_json", |value: Dynamic| -> ScriptResult<String> {
+        Ok(to_value(&value)?.to_string())
+    });
+    engine.register_fn("to_json_pretty", |value: Dynamic| -> ScriptResult<String> {
+        Ok(serde_json::to_string_pretty(&to_value(&value)?).unwrap_or_default())
+    });
+    engine
+}
+
+/// Exit code of a script that stopped with an error. Only the errors of
+/// `call_error` carry their own code.
+fn error_code(path: &str, error: &EvalAltResult) -> i32 {
+    match error {
+        EvalAltResult::ErrorRuntime(value, position) => {
+            if let Ok(failure) = to_value(value) {
+                if failure[CALL_ERROR_MARKER] != json!(true) {
+                    eprintln!(">>>> {}: {}", path, error);
+                    return ERROR_CODE_SCRIPT;
+                }
+                if let Some(code) = failure["code"].as_i64() {
+                    eprintln!(
+                        ">>>> {}: {} failed ({}): {}",
+                        path,
+                        failure["method"].as_str().unwrap_or_default(),
+                        position,
+                        failure["message"].as_str().unwrap_or_default()
+                    );
+                    return code as i32;
+                }
+            }
+            eprintln!(">>>> {}: {}", path, error);
+            ERROR_CODE_SCRIPT
+        }
+        _ => {
+            eprintln!(">>>> {}: {}", path, error);
+            ERROR_CODE_SCRIPT
+        }
+    }
+}
+
+/// Run a script file. `vars` are set as constants before it starts. Exits
+/// with the code given to `exit()`, the code of an uncaught `rpc()` error,
+/// or `ERROR_CODE_SCRIPT` for any other script error.
+pub fn handle_script(path: &str, vars: &[(String, Value)]) -> i32 {
+    let source = match fs::read_to_string(path) {
+        Ok(source) => source,
+        Err(e) => {
+            eprintln!(">>>> Error reading {}: {}", path, e);
+            return ERROR_CODE_PARAM;
+        }
+    };
+    let exit_code = Rc::new(Cell::new(0));
+    let engine = build_engine(Rc::new(RefCell::new(Catalog::load())), exit_code.clone());
+    let ast = match engine.compile(&source) {
+        Ok(ast) => ast,
+        Err(e) => {
+            eprintln!(">>>> {}: {}", path, e);
+            return ERROR_CODE_PARAM;
+        }
+    };
+
+    let mut scope = Scope::new();
+    for (name, value) in vars {
+        match to_dynamic(value) {
+            Ok(value) => {
+                scope.push_constant_dynamic(name.as_str(), value);
+            }
+            Err(e) => {
+                eprintln!(">>>> Invalid value for {}: {}", name, e);
+                return ERROR_CODE_PARAM;
+            }
+        }
+    }
+
+    match engine.run_ast_with_scope(&mut scope, &ast) {
+        Ok(()) => exit_code.get(),
+        Err(e) => error_code(path, &e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Exit code of a script run like `handle_script` does.
+    fn run(source: &str) -> i32 {
+        let exit_code = Rc::new(Cell::new(0));
+        let engine = build_engine(Rc::new(RefCell::new(Catalog::default())), exit_code.clone());
+        match engine.run(source) {
+            Ok(()) => exit_code.get(),
+            Err(e) => error_code("test.rhai", &e),
+        }
+    }
+
+    #[test]
+    fn exits_with_the_given_code() {
+        assert_eq!(run("let x = 1;"), 0);
+        assert_eq!(run("exit(3); exit(4);"), 3);
+        assert_eq!(run("exit(0)"), 0);
+        assert_eq!(run("exit(255)"), 255);
+        assert_eq!(run("if true { exit(9) } print(\"not reached\");"), 9);
+        assert_eq!(run("fn stop() { exit(4) } stop();"), 4);
+    }
+
+    #[test]
+    fn rejects_invalid_exit_codes() {
+        for source in [
+            "exit(256)",
+            "exit(-1)",
+            "exit(1 << 40)",
+            "exit(1.5)",
+            "exit(\"x\")",
+            "exit(())",
+        ] {
+            assert_eq!(run(source), ERROR_CODE_SCRIPT, "{}", source);
+        }
+    }
+
+    #[test]
+    fn takes_exit_codes_from_call_errors_only() {
+        let error = call_error("get_score", ERROR_CODE_SERVER_ERROR, "daemon down");
+        assert_eq!(error_code("test.rhai", &error), ERROR_CODE_SERVER_ERROR);
+        // A map thrown by the script does not choose the exit code
+        assert_eq!(run("throw #{code: 0}"), ERROR_CODE_SCRIPT);
+        assert_eq!(run("throw \"failed\""), ERROR_CODE_SCRIPT);
+        assert_eq!(run("let x = 1 / 0;"), ERROR_CODE_SCRIPT);
+        assert_eq!(run("undefined_function()"), ERROR_CODE_SCRIPT);
+    }
+
+    #[test]
+    fn lets_scripts_catch_call_errors() {
+        let source = r#"
+            try {
+                throw_call_error();
+            } catch (e) {
+                if e.code == 2 && e.method == "get_score" { exit(42) }
+            }
+        "#;
+        let exit_code = Rc::new(Cell::new(0));
+        let mut engine = build_engine(Rc::new(RefCell::new(Catalog::default())), exit_code.clone());
+        engine.register_fn("throw_call_error", || -> ScriptResult<()> {
+            Err(call_error(
+                "get_score",
+                ERROR_CODE_SERVER_ERROR,
+                "daemon down",
+            ))
+        });
+        assert!(engine.run(source).is_ok());
+        assert_eq!(exit_code.get(), 42);
+    }
+
+    #[test]
+    fn converts_values_to_json() {
+        assert_eq!(
+            to_value(
+                &Engine::new()
+                    .eval::<Dynamic>("#{a: [1, true, ()]}")
+                    .unwrap()
+            )
+            .unwrap(),
+            json!({"a": [1, true, null]})
+        );
+    }
+}
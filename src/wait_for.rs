@@ -0,0 +1,117 @@
+use crate::args::{prepare_args, prepare_args_with_meta};
+use crate::dispatch::{self, CallError};
+use crate::output::{render_result, result_value, OutputFormat};
+use crate::poll::{install_interrupt_handler, sleep_until};
+use crate::predicate::Predicate;
+use crate::redact::stdout_value;
+use crate::{fetch_method_meta, write_stdout, ERROR_CODE_SERVER_ERROR, ERROR_CODE_TIMEOUT};
+use serde_json::Value;
+use std::time::{Duration, Instant};
+
+pub struct WaitOptions {
+    pub until: Predicate,
+    pub timeout: Duration,
+    pub interval: Duration,
+}
+
+/// The argument object of the call, resolved on the first call that gets
+/// that far and kept in `resolved`. Positional arguments need the method
+/// metadata, which the daemon cannot give before it is up: failing to get it
+/// is a daemon error, so it is tried again at the next poll. Invalid
+/// arguments are not. The metadata is fetched here rather than by
+/// `prepare_args` to tell the two apart.
+fn resolve_args(
+    method: &str,
+    json_args: &str,
+    resolved: &mut Option<Option<String>>,
+) -> Result<Option<String>, CallError> {
+    if let Some(args) = resolved {
+        return Ok(args.clone());
+    }
+    let positional = matches!(
+        serde_json::from_str::<Value>(json_args),
+        Ok(Value::Array(values)) if !values.is_empty()
+    );
+    let args = if positional {
+        let meta = fetch_method_meta(method).map_err(|e| CallError {
+            code: ERROR_CODE_SERVER_ERROR,
+            message: e,
+        })?;
+        prepare_args_with_meta(method, json_args, meta)
+    } else {
+        prepare_args(method, json_args)
+    };
+    let args = args.map_err(|code| CallError {
+        code,
+        message: "invalid arguments".to_string(),
+    })?;
+    *resolved = Some(args.clone());
+    Ok(args)
+}
+
+/// Poll a method until its result satisfies the predicate. Calls that fail,
+/// typically because the daemon is still starting, count as not ready yet.
+/// The last result is printed either way; timing out (or Ctrl-C) exits with
+/// `ERROR_CODE_TIMEOUT`.
+pub fn handle_wait_for(method: &str, json_args: &str, options: &WaitOptions) -> i32 {
+    let mut args = None;
+    install_interrupt_handler();
+    let started = Instant::now();
+    let deadline = started + options.timeout;
+    let mut last: Result<String, CallError>;
+    // Progress is only reported when it changes, to keep CI logs short
+    let mut last_status = String::new();
+
+    loop {
+        let polled = Instant::now();
+        last = resolve_args(method, json_args, &mut args)
+            .and_then(|args| dispatch::call(method, args.as_deref()));
+        let status = match &last {
+            Ok(result) => {
+                let value = result_value(result);
+                if options.until.matches(&value) {
+                    eprintln!(
+                        "'{}' holds after {} s",
+                        options.until,
+                        started.elapsed().as_secs()
+                    );
+                    let _ = write_stdout(&render_result(result, OutputFormat::Json));
+                    return 0;
+                }
+                // Checked on the result, shown redacted
+                format!(
+                    "not yet: {}",
+                    options.until.observed(&stdout_value(value.clone()))
+                )
+            }
+            // Polling only helps with daemon errors
+            Err(e) if !e.is_daemon_error() => {
+                eprintln!(">>>> {}", e);
+                return e.code;
+            }
+            Err(e) => format!("error: {}", e),
+        };
+        if status != last_status {
+            eprintln!("[{} s] {}", started.elapsed().as_secs(), status);
+            last_status = status;
+        }
+
+        let next = (polled + options.interval).min(deadline);
+        if Instant::now() >= deadline || !sleep_until(next) {
+            break;
+        }
+    }
+
+    eprintln!(
+        ">>>> Gave up waiting for '{}' after {} s",
+        options.until,
+        started.elapsed().as_secs()
+    );
+    match last {
+        Ok(result) => {
+            let _ = write_stdout(&render_result(&result, OutputFormat::Json));
+        }
+        Err(e) => eprintln!(">>>> Last call failed: {}", e),
+    }
+    ERROR_CODE_TIMEOUT
+}
@@ -0,0 +1,284 @@
+use crate::args::prepare_args;
+use crate::catalog::now_secs;
+use crate::dispatch;
+use crate::output::result_value;
+use crate::poll::{install_interrupt_handler, interrupted, sleep_until};
+use crate::redact::stdout_value;
+use crate::{write_stdout, ERROR_CODE_SERVER_ERROR};
+use serde_json::{json, Value};
+use std::collections::BTreeSet;
+use std::io::{self, IsTerminal};
+use std::time::{Duration, Instant};
+
+const HIGHLIGHT: &str = "\x1b[1;33m";
+const ERROR_STYLE: &str = "\x1b[31m";
+const RESET: &str = "\x1b[0m";
+const CLEAR_SCREEN: &str = "\x1b[H\x1b[2J";
+
+/// Path of the whole result in change lists.
+const ROOT_PATH: &str = "(result)";
+
+pub struct WatchOptions {
+    pub interval: Duration,
+    /// Stop after this many calls.
+    pub count: Option<u64>,
+    /// Stop at the first result that differs from the previous one.
+    pub until_change: bool,
+}
+
+/// Paths (`a.b[0].c`) whose value differs between two results, including
+/// fields that appeared or disappeared.
+fn changed_paths(old: &Value, new: &Value, path: &str, changed: &mut BTreeSet<String>) {
+    match (old, new) {
+        (Value::Object(old), Value::Object(new)) => {
+            let added = new.keys().filter(|key| !old.contains_key(*key));
+            for key in old.keys().chain(added) {
+                let child = if path.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{}.{}", path, key)
+                };
+                match (old.get(key), new.get(key)) {
+                    (Some(o), Some(n)) => changed_paths(o, n, &child, changed),
+                    _ => {
+                        changed.insert(child);
+                    }
+                }
+            }
+        }
+        (Value::Array(old), Value::Array(new)) => {
+            for index in 0..old.len().max(new.len()) {
+                let child = format!("{}[{}]", path, index);
+                match (old.get(index), new.get(index)) {
+                    (Some(o), Some(n)) => changed_paths(o, n, &child, changed),
+                    _ => {
+                        changed.insert(child);
+                    }
+                }
+            }
+        }
+        (old, new) if old != new => {
+            changed.insert(if path.is_empty() {
+                ROOT_PATH.to_string()
+            } else {
+                path.to_string()
+            });
+        }
+        _ => {}
+    }
+}
+
+/// Indented JSON with the values at `changed` paths highlighted.
+fn render_highlighted(
+    value: &Value,
+    path: &str,
+    indent: usize,
+    changed: &BTreeSet<String>,
+) -> String {
+    let is_changed = changed.contains(path) || (path.is_empty() && changed.contains(ROOT_PATH));
+    let text = match value {
+        Value::Object(map) if !map.is_empty() && !is_changed => {
+            let pad = "  ".repeat(indent + 1);
+            let fields: Vec<String> = map
+                .iter()
+                .map(|(key, child)| {
+                    let child_path = if path.is_empty() {
+                        key.clone()
+                    } else {
+                        format!("{}.{}", path, key)
+                    };
+                    format!(
+                        "{}{}: {}",
+                        pad,
+                        Value::String(key.clone()),
+                        render_highlighted(child, &child_path, indent + 1, changed)
+                    )
+                })
+                .collect();
+            format!("{{\n{}\n{}}}", fields.join(",\n"), "  ".repeat(indent))
+        }
+        Value::Array(items) if !items.is_empty() && !is_changed => {
+            let pad = "  ".repeat(indent + 1);
+            let items: Vec<String> = items
+                .iter()
+                .enumerate()
+                .map(|(index, child)| {
+                    let child_path = format!("{}[{}]", path, index);
+                    format!(
+                        "{}{}",
+                        pad,
+                        render_highlighted(child, &child_path, indent + 1, changed)
+                    )
+                })
+                .collect();
+            format!("[\n{}\n{}]", items.join(",\n"), "  ".repeat(indent))
+        }
+        // Changed containers are highlighted as a whole
+        other => {
+            let pretty = serde_json::to_string_pretty(other).unwrap_or_default();
+            pretty.replace('\n', &format!("\n{}", "  ".repeat(indent)))
+        }
+    };
+    if is_changed {
+        format!("{}{}{}", HIGHLIGHT, text, RESET)
+    } else {
+        text
+    }
+}
+
+/// Call a method repeatedly. On a terminal the result is redrawn in place
+/// with the fields that changed since the previous call highlighted;
+/// otherwise each snapshot that differs from the previous one is printed as
+/// an NDJSON line. Exits with `ERROR_CODE_SERVER_ERROR` when the last call
+/// failed.
+pub fn handle_watch(method: &str, json_args: &str, options: &WatchOptions) -> i32 {
+    let args = match prepare_args(method, json_args) {
+        Ok(args) => args,
+        Err(code) => return code,
+    };
+    install_interrupt_handler();
+    let is_terminal = io::stdout().is_terminal();
+    let title = match &args {
+        Some(args) => format!("{} {}", method, args),
+        None => method.to_string(),
+    };
+
+    let mut previous: Option<Value> = None;
+    let mut tick = 0;
+    let mut failed;
+    loop {
+        tick += 1;
+        let started = Instant::now();
+        let outcome = match dispatch::call(method, args.as_deref()) {
+            Ok(result) => Ok(stdout_value(result_value(&result))),
+            // Not sent (e.g. not confirmed): the next calls would not be either
+            Err(e) if !e.is_daemon_error() => {
+                eprintln!(">>>> {}", e);
+                return e.code;
+            }
+            Err(e) => Err(e.message),
+        };
+        failed = outcome.is_err();
+        // Errors take part in the diff so that failures and recoveries show
+        let snapshot = match &outcome {
+            Ok(value) => value.clone(),
+            Err(e) => Value::String(format!("error: {}", e)),
+        };
+        let mut changed = BTreeSet::new();
+        if let Some(previous) = &previous {
+            changed_paths(previous, &snapshot, "", &mut changed);
+        }
+
+        if is_terminal {
+            let body = match &outcome {
+                Ok(value) => render_highlighted(value, "", 0, &changed),
+                Err(e) => format!(
+                    "{}>>>> Error calling RPC method: {}{}",
+                    ERROR_STYLE, e, RESET
+                ),
+            };
+            let mut screen = format!(
+                "{}Every {:?}: {}    call {}\n\n{}",
+                CLEAR_SCREEN, options.interval, title, tick, body
+            );
+            if !changed.is_empty() {
+                let paths: Vec<&str> = changed.iter().map(String::as_str).collect();
+                screen.push_str(&format!("\n\nChanged: {}", paths.join(", ")));
+            }
+            let _ = write_stdout(&screen);
+        } else if previous.is_none() || !changed.is_empty() {
+            let mut line = json!({
+                "tick": tick,
+                "time": now_secs(),
+                "changed": changed,
+            });
+            match outcome {
+                Ok(value) => line["result"] = value,
+                Err(e) => line["error"] = json!(e),
+            }
+            let _ = write_stdout(&line.to_string());
+        }
+
+        previous = Some(snapshot);
+        if options.until_change && !changed.is_empty() {
+            break;
+        }
+        if options.count.is_some_and(|count| tick >= count) {
+            break;
+        }
+        if !sleep_until(started + options.interval) {
+            break;
+        }
+    }
+    if is_terminal && interrupted() {
+        // Leave the prompt below the last snapshot
+        println!();
+    }
+    if failed {
+        ERROR_CODE_SERVER_ERROR
+    } else {
+        0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn diff(old: Value, new: Value) -> Vec<String> {
+        let mut changed = BTreeSet::new();
+        changed_paths(&old, &new, "", &mut changed);
+        changed.into_iter().collect()
+    }
+
+    #[test]
+    fn finds_no_changes_in_equal_results() {
+        let result = json!({"a": [1, {"b": null}], "c": "x"});
+        assert!(diff(result.clone(), result).is_empty());
+    }
+
+    #[test]
+    fn finds_changed_nested_fields() {
+        assert_eq!(
+            diff(
+                json!({"score": {"stars": 3, "label": "ok"}, "name": "host"}),
+                json!({"score": {"stars": 4, "label": "ok"}, "name": "host"})
+            ),
+            ["score.stars"]
+        );
+        // Added and removed fields
+        assert_eq!(
+            diff(json!({"a": 1, "b": 2}), json!({"b": 2, "c": 3})),
+            ["a", "c"]
+        );
+    }
+
+    #[test]
+    fn finds_changed_array_items() {
+        assert_eq!(
+            diff(
+                json!({"items": [{"id": 1}, {"id": 2}]}),
+                json!({"items": [{"id": 1}, {"id": 5}, {"id": 6}]})
+            ),
+            ["items[1].id", "items[2]"]
+        );
+        assert_eq!(diff(json!([1, 2, 3]), json!([1])), ["[1]", "[2]"]);
+    }
+
+    #[test]
+    fn reports_type_changes_at_their_path() {
+        assert_eq!(diff(json!({"a": {"b": 1}}), json!({"a": [1]})), ["a"]);
+        assert_eq!(diff(json!("up"), json!("down")), [ROOT_PATH]);
+        assert_eq!(diff(json!(1), json!(1.0)), [ROOT_PATH]);
+    }
+
+    #[test]
+    fn highlights_changed_values_only() {
+        let changed = BTreeSet::from(["b".to_string()]);
+        let text = render_highlighted(&json!({"a": 1, "b": 2}), "", 0, &changed);
+        assert_eq!(
+            text,
+            format!("{{\n  \"a\": 1,\n  \"b\": {}2{}\n}}", HIGHLIGHT, RESET)
+        );
+    }
+}
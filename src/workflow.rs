@@ -0,0 +1,813 @@
+use crate::args::{plan_args, prepare_args};
+use crate::dispatch::{self, CallError};
+use crate::output::result_value;
+use crate::poll::parse_duration;
+use crate::predicate::{lookup, Predicate};
+use crate::redact::stdout_value;
+use crate::{
+    best_suggestion, write_stdout, ERROR_CODE_MISMATCH, ERROR_CODE_PARAM, ERROR_CODE_SERVER_ERROR,
+};
+use serde_json::{Map, Value};
+use std::fs;
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+const STEP_KEYS: [&str; 8] = [
+    "name",
+    "method",
+    "args",
+    "when",
+    "capture",
+    "expect",
+    "retry",
+    "on_failure",
+];
+const WORKFLOW_KEYS: [&str; 3] = ["name", "vars", "steps"];
+
+/// Delay between attempts when `retry` only gives a count.
+const DEFAULT_RETRY_DELAY: Duration = Duration::from_secs(1);
+
+/// How steps call methods: `dispatch::call`, except in tests.
+type Call<'a> = &'a mut dyn FnMut(&str, Option<&str>) -> Result<String, CallError>;
+
+/// A workflow file:
+///
+/// ```yaml
+/// name: CI setup
+/// vars:
+///   demo: true
+/// steps:
+///   - name: score
+///     method: get_score
+///     args: {complete_only: false}
+///     capture: {stars: stars}        # variable: path in the result
+///     expect: stars >= 3             # or a list of conditions
+///     retry: {attempts: 3, delay: 2s} # or just a count
+///   - method: set_demo_mode
+///     args: {enabled: $demo}         # ${name.path} inside longer strings, $$ for $
+///     when: demo && stars < 5        # condition on the variables
+///     on_failure:
+///       - method: reset_all
+/// ```
+struct Workflow {
+    name: String,
+    vars: Map<String, Value>,
+    steps: Vec<Step>,
+}
+
+struct Step {
+    name: String,
+    method: String,
+    args: Value,
+    /// All must hold on the variables for the step to run.
+    when: Vec<Predicate>,
+    capture: Vec<(String, String)>,
+    expect: Vec<Predicate>,
+    attempts: u32,
+    delay: Duration,
+    on_failure: Vec<Step>,
+}
+
+enum StepOutcome {
+    Done,
+    Skipped,
+    Failed(i32),
+}
+
+/// Reject keys that are not in `allowed`, suggesting the closest one.
+fn check_keys(map: &Map<String, Value>, allowed: &[&str], context: &str) -> Result<(), String> {
+    let allowed: Vec<String> = allowed.iter().map(|k| k.to_string()).collect();
+    for key in map.keys() {
+        if !allowed.contains(key) {
+            let hint = best_suggestion(key, &allowed)
+                .map(|s| format!(", did you mean '{}'?", s))
+                .unwrap_or_default();
+            return Err(format!("{}: unknown key '{}'{}", context, key, hint));
+        }
+    }
+    Ok(())
+}
+
+fn parse_predicates(value: &Value, context: &str) -> Result<Vec<Predicate>, String> {
+    let sources: Vec<&Value> = match value {
+        Value::Null => Vec::new(),
+        Value::Array(items) => items.iter().collect(),
+        other => vec![other],
+    };
+    sources
+        .into_iter()
+        .map(|source| {
+            let source = source
+                .as_str()
+                .ok_or_else(|| format!("{}: conditions must be strings", context))?;
+            Predicate::parse(source)
+                .map_err(|e| format!("{}: invalid condition '{}': {}", context, source, e))
+        })
+        .collect()
+}
+
+fn parse_steps(value: &Value, context: &str) -> Result<Vec<Step>, String> {
+    let Some(entries) = value.as_array() else {
+        return Err(format!("{}: expected a list of steps", context));
+    };
+    entries
+        .iter()
+        .enumerate()
+        .map(|(index, entry)| parse_step(entry, &format!("{} step {}", context, index + 1)))
+        .collect()
+}
+
+/// The number of attempts of a retry, which must fit in a `u32`.
+fn retry_attempts(value: Option<&Value>, context: &str) -> Result<u32, String> {
+    match value {
+        None => Ok(0),
+        Some(count) => count
+            .as_u64()
+            .and_then(|count| u32::try_from(count).ok())
+            .ok_or_else(|| format!("{}: invalid retry attempts {}", context, count)),
+    }
+}
+
+fn parse_step(entry: &Value, context: &str) -> Result<Step, String> {
+    let Some(map) = entry.as_object() else {
+        return Err(format!("{}: expected a mapping", context));
+    };
+    check_keys(map, &STEP_KEYS, context)?;
+    let Some(method) = map.get("method").and_then(Value::as_str) else {
+        return Err(format!("{}: expected a \"method\" string", context));
+    };
+    let name = map
+        .get("name")
+        .and_then(Value::as_str)
+        .unwrap_or(method)
+        .to_string();
+    let context = format!("{} ({})", context, name);
+
+    let capture = match map.get("capture") {
+        None => Vec::new(),
+        Some(Value::Object(capture)) => capture
+            .iter()
+            .map(|(var, path)| match path.as_str() {
+                Some(path) => Ok((var.clone(), path.to_string())),
+                None => Err(format!("{}: capture paths must be strings", context)),
+            })
+            .collect::<Result<_, _>>()?,
+        Some(_) => return Err(format!("{}: capture must map names to paths", context)),
+    };
+    let (attempts, delay) = match map.get("retry") {
+        None => (1, DEFAULT_RETRY_DELAY),
+        Some(count @ Value::Number(_)) => {
+            (retry_attempts(Some(count), &context)?, DEFAULT_RETRY_DELAY)
+        }
+        Some(Value::Object(retry)) => {
+            let attempts = retry_attempts(retry.get("attempts"), &context)?;
+            let delay = match retry.get("delay") {
+                None => DEFAULT_RETRY_DELAY,
+                // Same range as the string form, see `parse_duration`
+                Some(Value::Number(seconds)) => {
+                    Duration::try_from_secs_f64(seconds.as_f64().unwrap_or(-1.0))
+                        .ok()
+                        .filter(|delay| !delay.is_zero())
+                        .ok_or_else(|| {
+                            format!("{}: invalid retry delay {} (seconds)", context, seconds)
+                        })?
+                }
+                Some(Value::String(delay)) => {
+                    parse_duration(delay).map_err(|e| format!("{}: {}", context, e))?
+                }
+                Some(_) => return Err(format!("{}: invalid retry delay", context)),
+            };
+            (attempts, delay)
+        }
+        Some(_) => return Err(format!("{}: invalid retry", context)),
+    };
+    if attempts == 0 {
+        return Err(format!("{}: retry needs at least 1 attempt", context));
+    }
+
+    Ok(Step {
+        method: method.to_string(),
+        args: map.get("args").cloned().unwrap_or(Value::Null),
+        when: parse_predicates(map.get("when").unwrap_or(&Value::Null), &context)?,
+        capture,
+        expect: parse_predicates(map.get("expect").unwrap_or(&Value::Null), &context)?,
+        attempts,
+        delay,
+        on_failure: match map.get("on_failure") {
+            None => Vec::new(),
+            Some(steps) => parse_steps(steps, &format!("{} on_failure", context))?,
+        },
+        name,
+    })
+}
+
+fn load_workflow(path: &str) -> Result<Workflow, String> {
+    let contents =
+        fs::read_to_string(path).map_err(|e| format!("Error reading {}: {}", path, e))?;
+    let document: Value =
+        serde_yaml::from_str(&contents).map_err(|e| format!("Error parsing {}: {}", path, e))?;
+    let Some(map) = document.as_object() else {
+        return Err(format!("{}: expected a mapping with \"steps\"", path));
+    };
+    check_keys(map, &WORKFLOW_KEYS, path)?;
+    Ok(Workflow {
+        name: map
+            .get("name")
+            .and_then(Value::as_str)
+            .unwrap_or(path)
+            .to_string(),
+        vars: match map.get("vars") {
+            None => Map::new(),
+            Some(Value::Object(vars)) => vars.clone(),
+            Some(_) => return Err(format!("{}: vars must be a mapping", path)),
+        },
+        steps: parse_steps(map.get("steps").unwrap_or(&Value::Null), path)?,
+    })
+}
+
+/// Parse `--var name=value`. The value is parsed as JSON and taken as a
+/// plain string when it is not valid JSON.
+pub fn parse_var(text: &str) -> Result<(String, Value), String> {
+    let Some((name, raw_value)) = text.split_once('=') else {
+        return Err(format!("invalid variable '{}': expected name=value", text));
+    };
+    let value = serde_json::from_str::<Value>(raw_value)
+        .unwrap_or_else(|_| Value::String(raw_value.to_string()));
+    Ok((name.to_string(), value))
+}
+
+/// The variable path of a string that is a reference as a whole (`$name`,
+/// `$name.field` or `${name.field}`). `$$` escapes a literal `$`.
+fn whole_reference(text: &str) -> Option<&str> {
+    if text.starts_with("$$") {
+        return None;
+    }
+    if let Some(inner) = text.strip_prefix("${").and_then(|t| t.strip_suffix('}')) {
+        return (!inner.contains("${")).then_some(inner);
+    }
+    let path = text.strip_prefix('$')?;
+    let valid = path.starts_with(|c: char| c.is_alphabetic() || c == '_')
+        && path
+            .chars()
+            .all(|c| c.is_alphanumeric() || matches!(c, '_' | '.' | '[' | ']'));
+    valid.then_some(path)
+}
+
+fn resolve(path: &str, vars: &Value) -> Result<Value, String> {
+    lookup(vars, path)
+        .cloned()
+        .ok_or_else(|| format!("unknown variable '{}'", path))
+}
+
+/// Replace variable references in step arguments. A string that is a single
+/// reference takes the referenced value with its JSON type; `${...}` inside a
+/// longer string is replaced by its text, and `$$` by `$`.
+fn interpolate(value: &Value, vars: &Value) -> Result<Value, String> {
+    match value {
+        Value::String(text) => {
+            if let Some(path) = whole_reference(text) {
+                return resolve(path, vars);
+            }
+            let mut output = String::new();
+            let mut rest = text.as_str();
+            while let Some(start) = rest.find('$') {
+                output.push_str(&rest[..start]);
+                rest = &rest[start..];
+                if let Some(after) = rest.strip_prefix("$$") {
+                    output.push('$');
+                    rest = after;
+                    continue;
+                }
+                let Some(end) = rest.strip_prefix("${").and_then(|_| rest.find('}')) else {
+                    output.push('$');
+                    rest = &rest[1..];
+                    continue;
+                };
+                match resolve(&rest[2..end], vars)? {
+                    Value::String(s) => output.push_str(&s),
+                    other => output.push_str(&other.to_string()),
+                }
+                rest = &rest[end + 1..];
+            }
+            output.push_str(rest);
+            Ok(Value::String(output))
+        }
+        Value::Array(items) => items
+            .iter()
+            .map(|item| interpolate(item, vars))
+            .collect::<Result<_, _>>()
+            .map(Value::Array),
+        Value::Object(map) => map
+            .iter()
+            .map(|(key, item)| Ok((key.clone(), interpolate(item, vars)?)))
+            .collect::<Result<_, String>>()
+            .map(Value::Object),
+        other => Ok(other.clone()),
+    }
+}
+
+fn report(indent: &str, text: &str) {
+    let _ = write_stdout(&format!("{}{}", indent, text));
+}
+
+fn run_steps(
+    steps: &[Step],
+    vars: &mut Map<String, Value>,
+    indent: &str,
+    call: Call,
+) -> (usize, usize, i32) {
+    let mut done = 0;
+    let mut skipped = 0;
+    for (index, step) in steps.iter().enumerate() {
+        let label = format!("[{}/{}] {}", index + 1, steps.len(), step.name);
+        match run_step(step, vars, indent, &label, call) {
+            StepOutcome::Done => done += 1,
+            StepOutcome::Skipped => skipped += 1,
+            StepOutcome::Failed(code) => return (done, skipped, code),
+        }
+    }
+    (done, skipped, 0)
+}
+
+fn run_step(
+    step: &Step,
+    vars: &mut Map<String, Value>,
+    indent: &str,
+    label: &str,
+    call: Call,
+) -> StepOutcome {
+    let context = Value::Object(vars.clone());
+    if let Some(when) = step.when.iter().find(|when| !when.matches(&context)) {
+        report(indent, &format!("{}: skipped (when: {})", label, when));
+        return StepOutcome::Skipped;
+    }
+
+    let args = match interpolate(&step.args, &context) {
+        Ok(Value::Null) => String::new(),
+        Ok(args) => args.to_string(),
+        Err(e) => {
+            report(indent, &format!("{}: FAILED: {}", label, e));
+            return fail(step, vars, indent, ERROR_CODE_PARAM, call);
+        }
+    };
+    let args = match prepare_args(&step.method, &args) {
+        Ok(args) => args,
+        Err(code) => {
+            report(indent, &format!("{}: FAILED: invalid arguments", label));
+            return fail(step, vars, indent, code, call);
+        }
+    };
+
+    let mut failure = (String::new(), ERROR_CODE_SERVER_ERROR);
+    for attempt in 1..=step.attempts {
+        let started = Instant::now();
+        match call(&step.method, args.as_deref()) {
+            Ok(raw) => {
+                let elapsed = started.elapsed().as_millis();
+                let result = result_value(&raw);
+                let unmet: Vec<&Predicate> =
+                    step.expect.iter().filter(|p| !p.matches(&result)).collect();
+                // Conditions are checked on the result, values shown redacted
+                let shown = stdout_value(result.clone());
+                if unmet.is_empty() {
+                    report(indent, &format!("{}: ok ({} ms)", label, elapsed));
+                    for predicate in &step.expect {
+                        report(
+                            indent,
+                            &format!("      PASS {} ({})", predicate, predicate.observed(&shown)),
+                        );
+                    }
+                    for (var, path) in &step.capture {
+                        let value = lookup(&result, path).cloned().unwrap_or(Value::Null);
+                        vars.insert(var.clone(), value);
+                    }
+                    return StepOutcome::Done;
+                }
+                let unmet: Vec<String> = unmet
+                    .iter()
+                    .map(|p| format!("{} ({})", p, p.observed(&shown)))
+                    .collect();
+                failure = (
+                    format!("expected {}", unmet.join(", ")),
+                    ERROR_CODE_MISMATCH,
+                );
+            }
+            // Not sent (e.g. not confirmed): retrying would not change that
+            Err(e) if !e.is_daemon_error() => {
+                report(indent, &format!("{}: FAILED: {}", label, e));
+                return fail(step, vars, indent, e.code, call);
+            }
+            Err(e) => failure = (e.message, e.code),
+        }
+        if attempt < step.attempts {
+            report(
+                indent,
+                &format!(
+                    "{}: attempt {}/{} failed: {}; retrying in {:?}",
+                    label, attempt, step.attempts, failure.0, step.delay
+                ),
+            );
+            sleep(step.delay);
+        }
+    }
+    let attempts = if step.attempts > 1 {
+        format!(" after {} attempts", step.attempts)
+    } else {
+        String::new()
+    };
+    report(
+        indent,
+        &format!("{}: FAILED{}: {}", label, attempts, failure.0),
+    );
+    fail(step, vars, indent, failure.1, call)
+}
+
+/// Run the cleanup steps of a failed step.
+fn fail(
+    step: &Step,
+    vars: &mut Map<String, Value>,
+    indent: &str,
+    code: i32,
+    call: Call,
+) -> StepOutcome {
+    if !step.on_failure.is_empty() {
+        let nested = format!("{}      ", indent);
+        report(&nested, "on_failure:");
+        let (_, _, cleanup_code) = run_steps(&step.on_failure, vars, &nested, call);
+        if cleanup_code != 0 {
+            report(&nested, "(cleanup failed)");
+        }
+    }
+    StepOutcome::Failed(code)
+}
+
+/// `--dry-run`: show the call each step would make, with its `on_failure`
+/// steps, without running anything. `when` conditions are shown rather than
+/// evaluated, and captured variables hold a placeholder naming the step and
+/// path they come from. Returns the number of steps that would be sent, the
+/// number rejected and the exit code of the first rejection.
+fn dry_run_steps(
+    steps: &[Step],
+    vars: &mut Map<String, Value>,
+    indent: &str,
+) -> (usize, usize, i32) {
+    let mut planned = 0;
+    let mut rejected = 0;
+    let mut exit_code = 0;
+    let detail = format!("{}      ", indent);
+    for (index, step) in steps.iter().enumerate() {
+        let label = format!("[{}/{}] {}", index + 1, steps.len(), step.name);
+        let context = Value::Object(vars.clone());
+        let args = interpolate(&step.args, &context).map(|args| match args {
+            Value::Null => String::new(),
+            args => args.to_string(),
+        });
+        let plan = match &args {
+            Ok(args) => plan_args(&step.method, args),
+            Err(e) => Err((e.clone(), ERROR_CODE_PARAM)),
+        };
+        let (dry_run, code) = dispatch::dry_run(
+            &step.method,
+            plan.as_ref()
+                .map_err(|(error, code)| (error.as_str(), *code)),
+        );
+        if code == 0 {
+            planned += 1;
+            let args = match &dry_run["args"] {
+                Value::Null => String::new(),
+                args => format!(" {}", args),
+            };
+            report(indent, &format!("{}: {}{}", label, step.method, args));
+        } else {
+            rejected += 1;
+            if exit_code == 0 {
+                exit_code = code;
+            }
+            let error = dry_run["error"].as_str().unwrap_or_default();
+            report(indent, &format!("{}: REJECTED: {}", label, error));
+        }
+
+        let mapping: Vec<String> = dry_run["mapping"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .map(|m| {
+                format!(
+                    "{} -> {} ({})",
+                    m["position"],
+                    m["name"].as_str().unwrap_or_default(),
+                    m["type"].as_str().unwrap_or_default()
+                )
+            })
+            .collect();
+        if !mapping.is_empty() {
+            report(&detail, &format!("mapping: {}", mapping.join(", ")));
+        }
+        for warning in dry_run["warnings"].as_array().into_iter().flatten() {
+            report(
+                &detail,
+                &format!("warning: {}", warning.as_str().unwrap_or_default()),
+            );
+        }
+        for when in &step.when {
+            report(&detail, &format!("when: {}", when));
+        }
+        for predicate in &step.expect {
+            report(&detail, &format!("expect: {}", predicate));
+        }
+        for (var, path) in &step.capture {
+            report(&detail, &format!("capture: {} <- {}", var, path));
+            vars.insert(
+                var.clone(),
+                Value::String(format!("<{} of step '{}'>", path, step.name)),
+            );
+        }
+        if !step.on_failure.is_empty() {
+            report(&detail, "on_failure:");
+            let (nested_planned, nested_rejected, code) =
+                dry_run_steps(&step.on_failure, vars, &detail);
+            planned += nested_planned;
+            rejected += nested_rejected;
+            if exit_code == 0 {
+                exit_code = code;
+            }
+        }
+    }
+    (planned, rejected, exit_code)
+}
+
+/// Run a workflow file and print a report line per step. Stops at the first
+/// failing step after its `on_failure` steps; the exit code is the one of
+/// that failure.
+pub fn handle_run(path: &str, overrides: &[(String, Value)], dry_run: bool) -> i32 {
+    let workflow = match load_workflow(path) {
+        Ok(workflow) => workflow,
+        Err(e) => {
+            eprintln!(">>>> {}", e);
+            return ERROR_CODE_PARAM;
+        }
+    };
+    let mut vars = workflow.vars;
+    for (name, value) in overrides {
+        vars.insert(name.clone(), value.clone());
+    }
+    if dry_run {
+        report(
+            "",
+            &format!(
+                "Workflow '{}' ({} steps, dry run)",
+                workflow.name,
+                workflow.steps.len()
+            ),
+        );
+        let (planned, rejected, code) = dry_run_steps(&workflow.steps, &mut vars, "");
+        eprintln!(
+            "Dry run: {} step(s) would be sent, {} rejected",
+            planned, rejected
+        );
+        return code;
+    }
+
+    report(
+        "",
+        &format!(
+            "Workflow '{}' ({} steps)",
+            workflow.name,
+            workflow.steps.len()
+        ),
+    );
+    let (done, skipped, code) = run_steps(&workflow.steps, &mut vars, "", &mut dispatch::call);
+    let failed = usize::from(code != 0);
+    eprintln!(
+        "Summary: {} done, {} skipped, {} failed, {} not run",
+        done,
+        skipped,
+        failed,
+        workflow.steps.len() - done - skipped - failed
+    );
+    code
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn step(value: Value) -> Result<Step, String> {
+        parse_step(&value, "step 1")
+    }
+
+    fn vars() -> Value {
+        json!({"stars": 4.5, "name": "ssh", "score": {"items": [{"id": 7}]}})
+    }
+
+    /// Run steps with a fake daemon that answers `results[method]`, or
+    /// fails for methods it does not know. Returns the methods called, in
+    /// order, and the outcome of `run_steps`.
+    fn run(
+        steps: Value,
+        variables: Value,
+        results: Value,
+    ) -> (Vec<String>, (usize, usize, i32), Map<String, Value>) {
+        let steps = parse_steps(&steps, "test").unwrap();
+        let mut vars = variables.as_object().unwrap().clone();
+        let mut calls = Vec::new();
+        let mut call = |method: &str, _args: Option<&str>| {
+            calls.push(method.to_string());
+            match results.get(method) {
+                Some(result) => Ok(result.to_string()),
+                None => Err(CallError {
+                    code: ERROR_CODE_SERVER_ERROR,
+                    message: format!("{} failed", method),
+                }),
+            }
+        };
+        let outcome = run_steps(&steps, &mut vars, "", &mut call);
+        (calls, outcome, vars)
+    }
+
+    #[test]
+    fn finds_whole_references() {
+        assert_eq!(whole_reference("$stars"), Some("stars"));
+        assert_eq!(whole_reference("$score.items[0]"), Some("score.items[0]"));
+        assert_eq!(
+            whole_reference("${score.items[0].id}"),
+            Some("score.items[0].id")
+        );
+        for text in [
+            "stars", "$1", "$ stars", "$stars!", "$$stars", "a $stars", "${a}${b}",
+        ] {
+            assert_eq!(whole_reference(text), None, "{}", text);
+        }
+    }
+
+    #[test]
+    fn interpolates_arguments() {
+        let args = json!({
+            "stars": "$stars",
+            "id": "${score.items[0].id}",
+            "text": "${name} has ${stars} stars",
+            "list": ["$name", 1, null],
+            "price": "$$5 or $5",
+            "literal": "$$HOME",
+            "open": "${name"
+        });
+        assert_eq!(
+            interpolate(&args, &vars()).unwrap(),
+            json!({
+                "stars": 4.5,
+                "id": 7,
+                "text": "ssh has 4.5 stars",
+                "list": ["ssh", 1, null],
+                "price": "$5 or $5",
+                "literal": "$HOME",
+                "open": "${name"
+            })
+        );
+        assert_eq!(
+            interpolate(&json!("$HOME"), &vars()).unwrap_err(),
+            "unknown variable 'HOME'"
+        );
+        assert!(interpolate(&json!("at ${missing.path}"), &vars()).is_err());
+    }
+
+    #[test]
+    fn parses_retries() {
+        let parsed = step(json!({"method": "get_score"})).unwrap();
+        assert_eq!((parsed.attempts, parsed.delay), (1, DEFAULT_RETRY_DELAY));
+        let parsed = step(json!({"method": "get_score", "retry": 3})).unwrap();
+        assert_eq!((parsed.attempts, parsed.delay), (3, DEFAULT_RETRY_DELAY));
+        for delay in [json!("500ms"), json!(0.5)] {
+            let parsed =
+                step(json!({"method": "get_score", "retry": {"attempts": 2, "delay": delay}}))
+                    .unwrap();
+            assert_eq!(parsed.delay, Duration::from_millis(500));
+        }
+        for retry in [
+            json!(0),
+            json!(-1),
+            json!(1.5),
+            json!(u64::from(u32::MAX) + 1),
+            json!({"delay": "1s"}),
+            json!({"attempts": 2, "delay": 0}),
+            json!({"attempts": 2, "delay": "0s"}),
+            json!({"attempts": 2, "delay": -1}),
+            json!({"attempts": 2, "delay": 1e300}),
+            json!({"attempts": 2, "delay": true}),
+            json!("3"),
+        ] {
+            assert!(
+                step(json!({"method": "get_score", "retry": retry})).is_err(),
+                "{}",
+                retry
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_steps() {
+        assert_eq!(
+            step(json!({"method": "get_score", "expct": "stars > 1"})).err(),
+            Some("step 1: unknown key 'expct', did you mean 'expect'?".to_string())
+        );
+        assert!(step(json!({"name": "no method"})).is_err());
+        assert!(step(json!({"method": "get_score", "expect": ["stars > 1", 2]})).is_err());
+        assert!(step(json!({"method": "get_score", "when": "stars >"})).is_err());
+        assert!(step(json!({"method": "get_score", "capture": {"s": 1}})).is_err());
+    }
+
+    #[test]
+    fn skips_steps_when_conditions_do_not_hold() {
+        let (calls, outcome, _) = run(
+            json!([
+                {"method": "get_score", "when": "stars > 4"},
+                {"method": "get_threats", "when": ["stars > 4", "name == 'other'"]},
+                {"method": "get_status", "when": "!missing"}
+            ]),
+            vars(),
+            json!({"get_score": {}, "get_threats": {}, "get_status": {}}),
+        );
+        assert_eq!(calls, ["get_score", "get_status"]);
+        assert_eq!(outcome, (2, 1, 0));
+    }
+
+    #[test]
+    fn captures_and_checks_results() {
+        let (calls, outcome, vars) = run(
+            json!([
+                {"method": "get_score", "capture": {"stars": "stars"}, "expect": "stars >= 3"},
+                {"method": "set_demo_mode", "when": "stars < 5"}
+            ]),
+            json!({}),
+            json!({"get_score": {"stars": 4}, "set_demo_mode": true}),
+        );
+        assert_eq!(calls, ["get_score", "set_demo_mode"]);
+        assert_eq!(outcome, (2, 0, 0));
+        assert_eq!(vars["stars"], json!(4));
+
+        let (calls, outcome, vars) = run(
+            json!([
+                {"method": "get_score", "capture": {"stars": "stars"}, "expect": "stars >= 5"},
+                {"method": "set_demo_mode"}
+            ]),
+            json!({}),
+            json!({"get_score": {"stars": 4}, "set_demo_mode": true}),
+        );
+        assert_eq!(calls, ["get_score"]);
+        assert_eq!(outcome, (0, 0, ERROR_CODE_MISMATCH));
+        // Failed steps capture nothing
+        assert!(vars.get("stars").is_none());
+    }
+
+    #[test]
+    fn runs_cleanup_steps_in_order_after_a_failure() {
+        let (calls, outcome, _) = run(
+            json!([
+                {"method": "get_score"},
+                {
+                    "method": "fail_setup",
+                    "on_failure": [
+                        {"method": "reset_first"},
+                        {"method": "fail_cleanup", "on_failure": [{"method": "reset_nested"}]},
+                        {"method": "reset_last"}
+                    ]
+                },
+                {"method": "get_status"}
+            ]),
+            json!({}),
+            json!({"get_score": {}, "reset_first": {}, "reset_nested": {}, "reset_last": {}}),
+        );
+        // A failing cleanup step runs its own cleanup and stops the others
+        assert_eq!(
+            calls,
+            [
+                "get_score",
+                "fail_setup",
+                "reset_first",
+                "fail_cleanup",
+                "reset_nested"
+            ]
+        );
+        assert_eq!(outcome, (1, 0, ERROR_CODE_SERVER_ERROR));
+    }
+
+    #[test]
+    fn does_not_retry_calls_that_were_not_sent() {
+        let steps = parse_steps(
+            &json!([{"method": "clear_all", "retry": {"attempts": 3, "delay": "1ms"}}]),
+            "test",
+        )
+        .unwrap();
+        let mut attempts = 0;
+        let mut call = |_: &str, _: Option<&str>| {
+            attempts += 1;
+            Err(CallError {
+                code: crate::ERROR_CODE_NOT_CONFIRMED,
+                message: "not confirmed".to_string(),
+            })
+        };
+        let outcome = run_steps(&steps, &mut Map::new(), "", &mut call);
+        assert_eq!(outcome, (0, 0, crate::ERROR_CODE_NOT_CONFIRMED));
+        assert_eq!(attempts, 1);
+    }
+}